@@ -0,0 +1,120 @@
+use _serde::{de::IgnoredAny, Deserialize};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_querystring::{from_bytes, ParseMode};
+
+#[derive(Deserialize)]
+#[serde(crate = "_serde")]
+struct Skipped {
+    #[allow(dead_code)]
+    value: u32,
+    #[allow(dead_code)]
+    large: IgnoredAny,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "_serde")]
+struct Read {
+    #[allow(dead_code)]
+    value: u32,
+    #[allow(dead_code)]
+    large: String,
+}
+
+/// A 1MB percent encoded value for the `large` key
+fn large_value() -> Vec<u8> {
+    let mut input = b"value=1&large=".to_vec();
+    while input.len() < 1 << 20 {
+        input.extend_from_slice(b"%20a");
+    }
+    input
+}
+
+/// A subtree of about 1MB under the `large` key
+fn large_subtree() -> Vec<u8> {
+    let mut input = b"value=1".to_vec();
+    let mut index = 0;
+    while input.len() < 1 << 20 {
+        input.extend_from_slice(format!("&large[{}][key]=%20a", index % 1024).as_bytes());
+        index += 1;
+    }
+    input
+}
+
+fn ignored_any(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ignored_any");
+
+    let input = large_value();
+    group.bench_function("large_value_skipped", |b| {
+        b.iter(|| from_bytes::<Skipped>(black_box(&input), ParseMode::Duplicate).unwrap())
+    });
+    group.bench_function("large_value_read", |b| {
+        b.iter(|| from_bytes::<Read>(black_box(&input), ParseMode::Duplicate).unwrap())
+    });
+
+    let input = large_subtree();
+    group.bench_function("large_subtree_skipped", |b| {
+        b.iter(|| from_bytes::<Skipped>(black_box(&input), ParseMode::Brackets).unwrap())
+    });
+
+    group.finish();
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "_serde")]
+#[allow(dead_code)]
+struct Flat {
+    f0: u32,
+    f1: u32,
+    f2: u32,
+    f3: u32,
+    f4: u32,
+    f5: String,
+    f6: String,
+    f7: String,
+    f8: String,
+    f9: String,
+    f10: bool,
+    f11: bool,
+    f12: bool,
+    f13: bool,
+    f14: bool,
+    f15: f64,
+    f16: f64,
+    f17: f64,
+    f18: f64,
+    f19: f64,
+}
+
+/// A flat querystring with the 20 fields of `Flat`, each assigned once
+fn flat_input() -> Vec<u8> {
+    let values = [
+        "1", "1", "1", "1", "1", "a%20b", "a%20b", "a%20b", "a%20b", "a%20b",
+    ];
+    let values = values
+        .iter()
+        .chain(["true"; 5].iter())
+        .chain(["1.5"; 5].iter());
+
+    let pairs: Vec<_> = values
+        .enumerate()
+        .map(|(index, value)| format!("f{}={}", index, value))
+        .collect();
+    pairs.join("&").into_bytes()
+}
+
+fn flat_struct(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flat_struct");
+
+    let input = flat_input();
+    group.bench_function("urlencoded", |b| {
+        b.iter(|| from_bytes::<Flat>(black_box(&input), ParseMode::UrlEncoded).unwrap())
+    });
+    group.bench_function("duplicate", |b| {
+        b.iter(|| from_bytes::<Flat>(black_box(&input), ParseMode::Duplicate).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, ignored_any, flat_struct);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde_querystring::{BracketsQS, DelimiterQS, DuplicateQS, UrlEncodedQS};
+
+/// About 1MB of short pairs, with a few keys repeated many times
+fn short_pairs() -> Vec<u8> {
+    let mut input = Vec::with_capacity(1 << 20);
+    let mut index = 0;
+    while input.len() < 1 << 20 {
+        if !input.is_empty() {
+            input.push(b'&');
+        }
+        input.extend_from_slice(format!("k{}=v%20{}", index % 64, index).as_bytes());
+        index += 1;
+    }
+    input
+}
+
+/// A single pair with a 1MB value
+fn long_value() -> Vec<u8> {
+    let mut input = b"key=".to_vec();
+    input.resize(1 << 20, b'a');
+    input
+}
+
+/// A thousand pairs with percent encoded keys, of which only a few are looked up
+fn wide_query() -> Vec<u8> {
+    let mut input = Vec::new();
+    for index in 0..1000 {
+        if !input.is_empty() {
+            input.push(b'&');
+        }
+        input.extend_from_slice(format!("field%5F{}=value", index).as_bytes());
+    }
+    input
+}
+
+fn parse(c: &mut Criterion) {
+    for (name, input) in [("short_pairs", short_pairs()), ("long_value", long_value())] {
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+
+        group.bench_function("urlencoded", |b| {
+            b.iter(|| UrlEncodedQS::parse(black_box(&input)))
+        });
+        group.bench_function("duplicate", |b| {
+            b.iter(|| DuplicateQS::parse(black_box(&input)))
+        });
+        group.bench_function("delimiter", |b| {
+            b.iter(|| DelimiterQS::parse(black_box(&input), b'|'))
+        });
+        group.bench_function("brackets", |b| {
+            b.iter(|| BracketsQS::parse(black_box(&input)))
+        });
+
+        group.finish();
+    }
+}
+
+fn lazy_keys(c: &mut Criterion) {
+    let input = wide_query();
+    let lookups: [&[u8]; 3] = [b"field_0", b"field_500", b"field_999"];
+
+    let mut group = c.benchmark_group("wide_query");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+
+    group.bench_function("eager_keys", |b| {
+        b.iter(|| {
+            let parser = DuplicateQS::parse(black_box(&input));
+            lookups.map(|key| parser.value(key))
+        })
+    });
+    group.bench_function("lazy_keys", |b| {
+        b.iter(|| {
+            let parser = DuplicateQS::parse_lazy(black_box(&input));
+            lookups.map(|key| parser.value(key))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parse, lazy_keys);
+criterion_main!(benches);
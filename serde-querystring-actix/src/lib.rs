@@ -161,10 +161,12 @@ where
 ///     );
 /// }
 /// ```
+type ErrorHandler = dyn Fn(QueryStringPayloadError, &HttpRequest) -> Error + Send + Sync;
+
 #[derive(Clone)]
 pub struct QueryStringConfig {
     mode: serde_querystring::de::ParseMode,
-    ehandler: Option<Arc<dyn Fn(QueryStringPayloadError, &HttpRequest) -> Error + Send + Sync>>,
+    ehandler: Option<Arc<ErrorHandler>>,
 }
 
 impl QueryStringConfig {
@@ -229,14 +231,14 @@ mod tests {
     async fn test_service_request_extract() {
         let req = TestRequest::with_uri("/name/user1/").to_srv_request();
         assert!(QueryString::<Id>::from_query(
-            &req.query_string(),
+            req.query_string(),
             serde_querystring::de::ParseMode::UrlEncoded
         )
         .is_err());
 
         let req = TestRequest::with_uri("/name/user1/?id=test").to_srv_request();
         let mut s = QueryString::<Id>::from_query(
-            &req.query_string(),
+            req.query_string(),
             serde_querystring::de::ParseMode::UrlEncoded,
         )
         .unwrap();
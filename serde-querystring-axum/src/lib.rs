@@ -262,7 +262,7 @@ mod tests {
         }
 
         async fn handler(q: QueryString<Params>) -> String {
-            format!("{}-{}", q.n.get(0).unwrap(), q.n.get(2).unwrap())
+            format!("{}-{}", q.n.first().unwrap(), q.n.get(2).unwrap())
         }
 
         let app = Router::new()
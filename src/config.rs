@@ -0,0 +1,735 @@
+use crate::decode::{decode_if, malformed_escape_index, Decode};
+use crate::encode::PercentEncodeSet;
+use crate::error::{ConfigError, Error, ErrorKind};
+
+/// An enum used to choose the parsing method for deserialization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// The simplest parser for querystring.
+    /// It parses the whole querystring, and overwrites each repeated key’s value.
+    /// It does not support vectors, maps nor tuples, but provides the best performance.
+    ///
+    /// It's the fast path for flat structs with a single value per key, the last value of a
+    /// repeated key wins and deserializing sequences fails.
+    ///
+    /// More description at ([UrlEncodedQs](crate::UrlEncodedQS))
+    UrlEncoded,
+
+    /// A querystring parser with support for vectors/lists of values by repeating keys.
+    /// (ex. `"key=value1&key=value2"`)
+    ///
+    /// More description at ([DuplicateQs](crate::DuplicateQS))
+    Duplicate,
+
+    /// A querystring parser with support for vectors/lists of values by the use of a delimiter byte.
+    /// (ex. `"key=value1|value2|value3"`). Holds the delimiter as a single byte `Delimiter(b'|')`
+    ///
+    /// More description at ([DelimiterQs](crate::DelimiterQS))
+    Delimiter(u8),
+
+    /// A querystring parser with support for vectors/lists, maps and enums
+    /// by the use of brackets(like qs or PHP).(ex. `key[2]=value2&key[1]=value1"`)
+    ///
+    /// More description at ([BracketsQs](crate::BracketsQS))
+    Brackets,
+}
+
+/// The values accepted when deserializing a `bool`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoolFormat {
+    /// `1`, `on`, `true` and an empty value for `true`, `0`, `off` and `false` for `false`
+    Default,
+
+    /// `yes` for `true` and `no` for `false`, ignoring the ascii case (ex. `Yes` or `NO`)
+    YesNo,
+}
+
+impl Default for BoolFormat {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+/// The values accepted when deserializing floats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// Decimals with an optional exponent, ex. `1.5` or `1.5e-3`
+    Default,
+
+    /// Only decimals, values in scientific notation like `1.5e-3` are rejected
+    NoExponent,
+}
+
+impl Default for FloatFormat {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+/// What to do with pairs having an empty key and a value, ex. `=value`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyKeys {
+    /// Store the value under the `""` key
+    Keep,
+
+    /// Drop the pair, as if it wasn't in the querystring
+    Skip,
+
+    /// Fail with an `InvalidKey` error
+    Reject,
+}
+
+impl Default for EmptyKeys {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+/// The order of the values of a sequence in brackets mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeqOrder {
+    /// Values are sorted by their index, ex. `a[1]=x&a[0]=y` gives `[y, x]`
+    ByIndex,
+
+    /// Values keep the order they appear in the querystring and the indexes are ignored,
+    /// ex. `a[1]=x&a[0]=y` gives `[x, y]`
+    BySubmission,
+}
+
+impl Default for SeqOrder {
+    fn default() -> Self {
+        Self::ByIndex
+    }
+}
+
+/// The order of the keys of structs and maps when serializing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOrder {
+    /// Keys are written in the order they are serialized, ex. the declared order of fields
+    Preserve,
+
+    /// Keys are sorted by their percent encoded bytes at each level of nesting, so the output
+    /// doesn't depend on the order of fields or the iteration order of maps like `HashMap`
+    Sorted,
+}
+
+impl Default for KeyOrder {
+    fn default() -> Self {
+        Self::Preserve
+    }
+}
+
+/// Options used for parsing and deserializing querystrings
+///
+/// It holds the `ParseMode` used for deserialization, and the options shared by all the parsers.
+/// The default values keep the behavior of the plain `parse` methods.
+///
+/// `Config` only holds plain flags and limits, so it is `Copy` and passing it to many parse or
+/// `from_bytes_with_config` calls costs nothing. Options added later are kept cheap to copy as well.
+///
+/// # Example
+/// ```rust
+/// use serde_querystring::{Config, DuplicateQS, ParseMode};
+///
+/// let config = Config::new(ParseMode::Duplicate).newline_terminators(true);
+///
+/// let parser = DuplicateQS::try_parse(b"foo=bar\r\nfoo=baz\n", &config).unwrap();
+/// assert_eq!(
+///     parser.values(b"foo"),
+///     Some(vec![Some("bar".as_bytes().into()), Some("baz".as_bytes().into())])
+/// );
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub(crate) mode: ParseMode,
+    pub(crate) newline_terminators: bool,
+    pub(crate) question_mark_terminators: bool,
+    pub(crate) empty_keys: EmptyKeys,
+    pub(crate) skip_empty_segments: bool,
+    pub(crate) lenient_numbers: bool,
+    pub(crate) case_insensitive_enums: bool,
+    pub(crate) detect_types: bool,
+    pub(crate) hex_integers: bool,
+    pub(crate) max_params: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) literal_encoded_brackets: bool,
+    pub(crate) max_key_len: Option<usize>,
+    pub(crate) max_value_len: Option<usize>,
+    pub(crate) max_seq_len: Option<usize>,
+    pub(crate) max_values_per_key: Option<usize>,
+    pub(crate) reject_malformed_escapes: bool,
+    pub(crate) validate_utf8: bool,
+    pub(crate) percent_decode: bool,
+    pub(crate) plus_as_space_in_keys: bool,
+    pub(crate) plus_as_space_in_values: bool,
+    pub(crate) bool_format: BoolFormat,
+    pub(crate) float_format: FloatFormat,
+    pub(crate) seq_order: SeqOrder,
+    pub(crate) fill_seq_gaps: Option<usize>,
+    pub(crate) encode_set: PercentEncodeSet,
+    pub(crate) write_none: bool,
+    pub(crate) key_order: KeyOrder,
+    pub(crate) null_tokens: &'static [&'static str],
+    pub(crate) empty_numbers_as_none: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new(ParseMode::Duplicate)
+    }
+}
+
+impl From<ParseMode> for Config {
+    fn from(mode: ParseMode) -> Self {
+        Self::new(mode)
+    }
+}
+
+impl Config {
+    pub fn new(mode: ParseMode) -> Self {
+        Self {
+            mode,
+            newline_terminators: false,
+            question_mark_terminators: false,
+            empty_keys: EmptyKeys::Keep,
+            skip_empty_segments: false,
+            lenient_numbers: false,
+            case_insensitive_enums: false,
+            detect_types: false,
+            hex_integers: false,
+            max_params: None,
+            max_depth: None,
+            literal_encoded_brackets: false,
+            max_key_len: None,
+            max_value_len: None,
+            max_seq_len: None,
+            max_values_per_key: None,
+            reject_malformed_escapes: false,
+            validate_utf8: false,
+            percent_decode: true,
+            plus_as_space_in_keys: true,
+            plus_as_space_in_values: true,
+            bool_format: BoolFormat::Default,
+            float_format: FloatFormat::Default,
+            seq_order: SeqOrder::ByIndex,
+            fill_seq_gaps: None,
+            encode_set: PercentEncodeSet::FORM,
+            write_none: false,
+            key_order: KeyOrder::Preserve,
+            null_tokens: &[],
+            empty_numbers_as_none: false,
+        }
+    }
+
+    /// A preset following the semantics of the `serde_qs` crate, to ease migrating from it
+    ///
+    /// It uses the brackets mode, with indexed(`a[0]=1`) or empty(`a[]=1`) brackets for
+    /// sequences and subkeys(`a[b]=c`) for nested structs and maps, limits the nesting to
+    /// 5 levels and writes spaces as `+` when serializing, like `serde_qs` does.
+    ///
+    /// Known differences:
+    /// - Keys nested deeper than 5 levels fail with an error.
+    /// - Repeated keys without brackets, ex. `a=1&a=2`, are accepted as sequences, and the
+    ///   last value is used for single values.
+    pub fn qs_compatible() -> Self {
+        Self::new(ParseMode::Brackets)
+            .max_depth(5)
+            .encode_set(PercentEncodeSet::FORM)
+    }
+
+    /// A preset following the semantics of the `qs` package of Node.js, for querystrings shared
+    /// with javascript frontends
+    ///
+    /// It uses the brackets mode, where empty brackets(`a[]=1&a[]=2`) append to a sequence and
+    /// subkeys(`a[b]=c`) nest, and values like `a=1,2` are kept as a single value like `qs` does
+    /// without its `comma` option. Like the defaults of `qs`, the nesting is limited to 5 levels
+    /// and the number of pairs to 1000, and spaces are written as `%20` when serializing.
+    ///
+    /// Known differences:
+    /// - Querystrings exceeding the limits fail with an error, instead of ignoring the rest.
+    /// - Indexes above 20 are still sequence indexes, `qs` turns them into map keys.
+    /// - Brackets are not percent encoded in the serialized keys.
+    pub fn node_qs_compatible() -> Self {
+        Self::new(ParseMode::Brackets)
+            .max_depth(5)
+            .max_params(1000)
+            .encode_set(PercentEncodeSet::STRICT.keep(b"-._~"))
+    }
+
+    /// Set the parsing method used for deserialization
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Treat `\n` and `\r` like `&`, ending the current pair. (default: `false`)
+    ///
+    /// Useful when querystrings are read line by line, ex. from a file, so `"key=value\n"`
+    /// gives `value` instead of `value\n`.
+    pub fn newline_terminators(mut self, enabled: bool) -> Self {
+        self.newline_terminators = enabled;
+        self
+    }
+
+    /// Treat `?` like `&`, ending the current pair. (default: `false`)
+    ///
+    /// By default a `?` inside the query is a part of the key or value, ex. `a=1?b=2` gives
+    /// `1?b=2` for `a`. It's meant for dirty input from clients appending a second query with `?`,
+    /// so `a=1?b=2` gives `1` for `a` and `2` for `b`.
+    pub fn question_mark_terminators(mut self, enabled: bool) -> Self {
+        self.question_mark_terminators = enabled;
+        self
+    }
+
+    /// Return an error for pairs with an empty key, ex `"=value"`, instead of storing them
+    /// under the `""` key. (default: `false`)
+    ///
+    /// Empty segments like `"&&"` have neither a key nor a value and are not affected.
+    /// It's a shorthand for `empty_keys(EmptyKeys::Reject)`.
+    pub fn reject_empty_keys(mut self, enabled: bool) -> Self {
+        self.empty_keys = if enabled {
+            EmptyKeys::Reject
+        } else {
+            EmptyKeys::Keep
+        };
+        self
+    }
+
+    /// Set what to do with pairs having an empty key, ex `"=value"`. (default: `EmptyKeys::Keep`)
+    ///
+    /// Empty segments like `"&&"` have neither a key nor a value and are not affected, see
+    /// `skip_empty_segments` for them.
+    pub fn empty_keys(mut self, empty_keys: EmptyKeys) -> Self {
+        self.empty_keys = empty_keys;
+        self
+    }
+
+    /// Skip empty segments, ex. the ones between `"a=1&&&b=2"`, instead of storing them as an
+    /// empty key without a value. (default: `false`)
+    pub fn skip_empty_segments(mut self, enabled: bool) -> Self {
+        self.skip_empty_segments = enabled;
+        self
+    }
+
+    /// Accept a leading `+` and `_` digit separators in numbers, ex. `+5` or `1_000`. (default: `false`)
+    ///
+    /// Numbers are parsed from the raw values, so the `+` sign should not be percent encoded.
+    pub fn lenient_numbers(mut self, enabled: bool) -> Self {
+        self.lenient_numbers = enabled;
+        self
+    }
+
+    /// Match unit enum variants given as values ignoring the ascii case, ex. `side=left` for
+    /// `Side::Left`. (default: `false`)
+    ///
+    /// Variants given as subkeys in brackets mode, ex. `shape[circle][r]=1`, are still matched
+    /// exactly.
+    pub fn case_insensitive_enums(mut self, enabled: bool) -> Self {
+        self.case_insensitive_enums = enabled;
+        self
+    }
+
+    /// Visit the values which look like integers, floats or `true`/`false` as those types in
+    /// `deserialize_any`, instead of strings. (default: `false`)
+    ///
+    /// It's meant for dynamic values like `serde_json::Value`, typed fields are always parsed
+    /// as their own type. Numbers are detected with the number options of the config, ex.
+    /// `hex_integers`, and floats which are not finite are kept as strings.
+    ///
+    /// Internally tagged enums, `#[serde(tag = "type")]`, need it for fields which are not strings,
+    /// as serde buffers their fields through `deserialize_any`, ex. `type=Circle&r=1`. Values
+    /// like `1` are then buffered as numbers, and fail for `String` fields of those enums.
+    pub fn detect_types(mut self, enabled: bool) -> Self {
+        self.detect_types = enabled;
+        self
+    }
+
+    /// Accept `0x` prefixed hex integers, ex. `0xFF` or `-0x1a`, besides decimals. (default: `false`)
+    ///
+    /// Floats are always parsed as decimals.
+    pub fn hex_integers(mut self, enabled: bool) -> Self {
+        self.hex_integers = enabled;
+        self
+    }
+
+    /// Return an error if the querystring has more than `limit` pairs. (default: no limit)
+    ///
+    /// Empty segments like `"&&"` are not counted.
+    pub fn max_params(mut self, limit: usize) -> Self {
+        self.max_params = Some(limit);
+        self
+    }
+
+    /// Keep percent encoded brackets(`%5B` and `%5D`) in keys as part of their names, instead
+    /// of nesting them, ex. `a%5Bb%5D=1` is the key `a[b]` in brackets mode. (default: `false`)
+    ///
+    /// The serializer always encodes the brackets in the names of keys, so names like `a[b]`
+    /// round trip with this option.
+    pub fn literal_encoded_brackets(mut self, enabled: bool) -> Self {
+        self.literal_encoded_brackets = enabled;
+        self
+    }
+
+    /// Return an error for keys nested deeper than `depth` levels of brackets,
+    /// ex. `"a[b][c]=value"` has a depth of 2. (default: no limit)
+    ///
+    /// It's only used in brackets mode, `validate` rejects it in the other modes.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Return an error for keys longer than `limit` bytes before percent decoding.
+    /// (default: no limit)
+    ///
+    /// In brackets mode, the limit applies to the whole key including its subkeys,
+    /// ex. 7 for `a[b][c]`.
+    pub fn max_key_len(mut self, limit: usize) -> Self {
+        self.max_key_len = Some(limit);
+        self
+    }
+
+    /// Return an error for values longer than `limit` bytes before percent decoding.
+    /// (default: no limit)
+    ///
+    /// In delimiter mode, the limit applies to the whole value and not each of its parts.
+    pub fn max_value_len(mut self, limit: usize) -> Self {
+        self.max_value_len = Some(limit);
+        self
+    }
+
+    /// Return an error when deserializing a sequence with more than `limit` values.
+    /// (default: no limit)
+    ///
+    /// Unlike `max_params`, it bounds the values of a single field, ex. the repeated
+    /// values of a key in duplicate mode.
+    pub fn max_seq_len(mut self, limit: usize) -> Self {
+        self.max_seq_len = Some(limit);
+        self
+    }
+
+    /// Deserialize the values matching one of `tokens` as `None` for `Option` fields, ex.
+    /// `&["null", ""]` for `x=null` and `x=`. (default: no tokens)
+    ///
+    /// Values are compared after percent decoding, and only for `Option` fields, so `x=null`
+    /// is still `"null"` for a `String`. In duplicate mode all the values of the key should
+    /// match, and in brackets mode the key should have a single value without subkeys.
+    pub fn null_tokens(mut self, tokens: &'static [&'static str]) -> Self {
+        self.null_tokens = tokens;
+        self
+    }
+
+    /// Deserialize empty values as `None` for `Option` fields, so `x=` for an `Option<u32>` or an
+    /// `Option<bool>` doesn't fail to parse. (default: `false`)
+    ///
+    /// The option is picked before the type inside it is deserialized, so the rule is the same
+    /// for all of them and `x=` is `None` for an `Option<String>` too, while it's still `""` for a
+    /// `String`. The empty values are matched the same way as `null_tokens`.
+    pub fn empty_numbers_as_none(mut self, enabled: bool) -> Self {
+        self.empty_numbers_as_none = enabled;
+        self
+    }
+
+    /// Return an error if a key is assigned more than `limit` times. (default: no limit)
+    ///
+    /// Unlike `max_seq_len`, it's checked while parsing, so the values are never stored. In
+    /// brackets mode, the assignments to the subkeys of a key are counted for the key, ex.
+    /// `a[0]=1&a[1]=2` has two values for `a`.
+    pub fn max_values_per_key(mut self, limit: usize) -> Self {
+        self.max_values_per_key = Some(limit);
+        self
+    }
+
+    /// Return an error for `%` signs not followed by two hex digits, ex. `"key=100%"`,
+    /// instead of keeping them as is. (default: `false`)
+    pub fn reject_malformed_escapes(mut self, enabled: bool) -> Self {
+        self.reject_malformed_escapes = enabled;
+        self
+    }
+
+    /// Return an error for keys and values that are not valid utf8 after percent decoding,
+    /// ex. `"key=%FF"`, when parsing instead of when deserializing them. (default: `false`)
+    ///
+    /// The error holds the key of the invalid pair, even if the value is the invalid part.
+    pub fn validate_utf8(mut self, enabled: bool) -> Self {
+        self.validate_utf8 = enabled;
+        self
+    }
+
+    /// Percent decode keys and values and replace `+` with spaces. (default: `true`)
+    ///
+    /// When disabled, keys and values are used exactly as they appear in the input, ex. to pass
+    /// them through to another service without changing their encoding.
+    pub fn percent_decode(mut self, enabled: bool) -> Self {
+        self.percent_decode = enabled;
+        self
+    }
+
+    /// Replace `+` with spaces when percent decoding keys. (default: `true`)
+    ///
+    /// When disabled, a `+` in a key is kept as it is, ex. to match a field renamed to `a+b`
+    /// with `a+b=1`, while `%20` is still decoded to a space.
+    pub fn plus_as_space_in_keys(mut self, enabled: bool) -> Self {
+        self.plus_as_space_in_keys = enabled;
+        self
+    }
+
+    /// Replace `+` with spaces when percent decoding values. (default: `true`)
+    ///
+    /// When disabled, a `+` in a value is kept as it is, ex. for timestamps with a `+HH:MM` offset.
+    pub fn plus_as_space_in_values(mut self, enabled: bool) -> Self {
+        self.plus_as_space_in_values = enabled;
+        self
+    }
+
+    /// Set the values accepted for booleans. (default: `BoolFormat::Default`)
+    pub fn bool_format(mut self, format: BoolFormat) -> Self {
+        self.bool_format = format;
+        self
+    }
+
+    /// Set the values accepted for floats. (default: `FloatFormat::Default`)
+    pub fn float_format(mut self, format: FloatFormat) -> Self {
+        self.float_format = format;
+        self
+    }
+
+    /// Set the order of the values of sequences in brackets mode. (default: `SeqOrder::ByIndex`)
+    ///
+    /// Nested values sharing an index, ex. `a[0][x]=1&a[0][y]=2`, still form a single value.
+    pub fn seq_order(mut self, order: SeqOrder) -> Self {
+        self.seq_order = order;
+        self
+    }
+
+    /// Fill the missing indexes of sequences in brackets mode with empty values, up to `limit`
+    /// values. (default: disabled, the values are collapsed)
+    ///
+    /// For `a[0]=x&a[3]=y`, a `Vec<Option<String>>` gets `[Some(x), None, None, Some(y)]`.
+    /// Sequences with an index of `limit` or more fail with an error, so a single large index
+    /// can't allocate a huge sequence. It can't be combined with `SeqOrder::BySubmission`.
+    pub fn fill_seq_gaps(mut self, limit: usize) -> Self {
+        self.fill_seq_gaps = Some(limit);
+        self
+    }
+
+    /// Set the bytes kept as they are when serializing keys and values. (default: `PercentEncodeSet::FORM`)
+    ///
+    /// It's only used for serialization, parsing accepts both `+` and `%20` for spaces.
+    pub fn encode_set(mut self, set: PercentEncodeSet) -> Self {
+        self.encode_set = set;
+        self
+    }
+
+    /// Write `None` values as a key with an empty value, ex. `key=`, instead of skipping
+    /// them. (default: `false`)
+    ///
+    /// It's only used for serialization, nested structs and maps which are `None` are written
+    /// with their prefix as the key, ex. `inner=`.
+    pub fn write_none(mut self, enabled: bool) -> Self {
+        self.write_none = enabled;
+        self
+    }
+
+    /// Set the order of the keys of structs and maps when serializing.
+    /// (default: `KeyOrder::Preserve`)
+    ///
+    /// With `KeyOrder::Sorted`, the output is deterministic, ex. for cache keys or signatures.
+    /// The values of a key keep their order, and `to_writer` writes the root entries once they
+    /// are all serialized.
+    pub fn key_order(mut self, order: KeyOrder) -> Self {
+        self.key_order = order;
+        self
+    }
+
+    /// Checks the options for contradictory settings, ex. `&` as the delimiter of
+    /// `ParseMode::Delimiter`, which would never see a value with more than one part, or
+    /// `max_depth` in a mode without nested keys.
+    ///
+    /// The `try_parse` methods of the parsers, the deserialization functions like
+    /// `from_bytes_with_config` and the serialization functions like `to_string_with_config` call
+    /// it first, and fail with an `InvalidConfig` error. The parsers check the options for their
+    /// own mode, whatever the mode of the config is.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let ParseMode::Delimiter(delimiter) = self.mode {
+            if delimiter == b'=' || self.is_terminator(delimiter) {
+                return Err(ConfigError::DelimiterConflict(delimiter));
+            }
+        }
+
+        if self.fill_seq_gaps.is_some() && self.seq_order == SeqOrder::BySubmission {
+            return Err(ConfigError::SeqGapsWithoutIndexes);
+        }
+
+        if self.max_depth.is_some() && self.mode != ParseMode::Brackets {
+            return Err(ConfigError::DepthWithoutBrackets);
+        }
+
+        Ok(())
+    }
+
+    /// Checks the options like `validate`, for the parser of `mode`
+    ///
+    /// The parsers are picked directly instead of by the mode of the config.
+    pub(crate) fn validate_for(&self, mode: ParseMode) -> Result<(), ConfigError> {
+        self.mode(mode).validate()
+    }
+
+    /// Checks if a percent decoded value is one of the null tokens, or empty with
+    /// `empty_numbers_as_none`
+    pub(crate) fn is_null(&self, value: &[u8]) -> bool {
+        (self.empty_numbers_as_none && value.is_empty())
+            || self
+                .null_tokens
+                .iter()
+                .any(|token| token.as_bytes() == value)
+    }
+
+    /// Returns true if some values may be null, so they're worth checking
+    pub(crate) fn has_null_values(&self) -> bool {
+        self.empty_numbers_as_none || !self.null_tokens.is_empty()
+    }
+
+    /// Checks a parsed pair against the options, the key is the undecoded top level key
+    pub(crate) fn check_pair(&self, key: &[u8], value: Option<&[u8]>) -> Result<(), Error> {
+        match value {
+            Some(value) if self.empty_keys == EmptyKeys::Reject && key.is_empty() => {
+                return Err(Error::new(ErrorKind::InvalidKey)
+                    .message("found a value with an empty key".to_string())
+                    .value(value));
+            }
+            _ => {}
+        }
+
+        match (value, self.max_value_len) {
+            // The key is kept in the error instead of the oversized value
+            (Some(value), Some(limit)) if value.len() > limit => {
+                return Err(Error::new(ErrorKind::InvalidLength)
+                    .message(format!("found a value longer than {} bytes", limit))
+                    .value(key));
+            }
+            _ => {}
+        }
+
+        self.check_escapes(key)?;
+        self.check_utf8(key, key, self.key_decode())?;
+        match value {
+            Some(value) => {
+                self.check_escapes(value)?;
+                self.check_utf8(key, value, self.value_decode())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true for the pairs dropped by `EmptyKeys::Skip`, the key is the undecoded top level key
+    #[inline]
+    pub(crate) fn skips_empty_key(&self, key: &[u8], value: Option<&[u8]>) -> bool {
+        self.empty_keys == EmptyKeys::Skip && key.is_empty() && value.is_some()
+    }
+
+    /// How keys are decoded
+    pub(crate) fn key_decode(&self) -> Decode {
+        Decode {
+            percent: self.percent_decode,
+            plus_as_space: self.plus_as_space_in_keys,
+        }
+    }
+
+    /// How values are decoded
+    pub(crate) fn value_decode(&self) -> Decode {
+        Decode {
+            percent: self.percent_decode,
+            plus_as_space: self.plus_as_space_in_values,
+        }
+    }
+
+    /// Checks the slice to be valid utf8 after decoding it with `decode`, if it's validated when
+    /// parsing
+    pub(crate) fn check_utf8(&self, key: &[u8], slice: &[u8], decode: Decode) -> Result<(), Error> {
+        if !self.validate_utf8 {
+            return Ok(());
+        }
+
+        let mut scratch = Vec::new();
+        match std::str::from_utf8(&decode_if(slice, &mut scratch, decode)) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(Error::new(ErrorKind::InvalidEncoding)
+                .message("invalid utf-8 sequence found in the percent decoded pair".to_string())
+                .value(key)),
+        }
+    }
+
+    /// Checks the undecoded slice for malformed percent escapes, if they are rejected
+    pub(crate) fn check_escapes(&self, slice: &[u8]) -> Result<(), Error> {
+        if !self.reject_malformed_escapes {
+            return Ok(());
+        }
+
+        match malformed_escape_index(slice) {
+            Some(index) => Err(Error::new(ErrorKind::InvalidEncoding)
+                .message("found a `%` not followed by two hex digits".to_string())
+                .value(slice)
+                .index(index)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the number of pairs parsed so far against the limit
+    pub(crate) fn check_params(&self, count: usize) -> Result<(), Error> {
+        match self.max_params {
+            Some(limit) if count > limit => Err(Error::new(ErrorKind::InvalidLength).message(
+                format!("found more than {} pairs in the querystring", limit),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the number of values of a sequence against the limit
+    pub(crate) fn check_seq_len(&self, len: usize) -> Result<(), Error> {
+        match self.max_seq_len {
+            Some(limit) if len > limit => Err(Error::new(ErrorKind::InvalidLength)
+                .message(format!("found a sequence with more than {} values", limit))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the number of values of a key parsed so far against the limit, the key is decoded
+    pub(crate) fn check_values_per_key(&self, key: &[u8], count: usize) -> Result<(), Error> {
+        match self.max_values_per_key {
+            Some(limit) if count > limit => Err(Error::new(ErrorKind::InvalidLength)
+                .message(format!("found more than {} values for a key", limit))
+                .value(key)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the length of a key, the key is the undecoded top level key and `len` is the
+    /// length of the whole key
+    pub(crate) fn check_key_len(&self, key: &[u8], len: usize) -> Result<(), Error> {
+        match self.max_key_len {
+            Some(limit) if len > limit => Err(Error::new(ErrorKind::InvalidLength)
+                .message(format!("found a key longer than {} bytes", limit))
+                .value(key)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the nesting depth of a key against the limit, the key is the undecoded top level key
+    pub(crate) fn check_depth(&self, key: &[u8], depth: usize) -> Result<(), Error> {
+        match self.max_depth {
+            Some(limit) if depth > limit => Err(Error::new(ErrorKind::InvalidKey)
+                .message(format!("found a key nested deeper than {} levels", limit))
+                .value(key)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns true if the byte ends a pair
+    #[inline]
+    pub(crate) fn is_terminator(&self, c: u8) -> bool {
+        c == b'&'
+            || (self.newline_terminators && (c == b'\n' || c == b'\r'))
+            || (self.question_mark_terminators && c == b'?')
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use _serde::de::{self, value, Deserialize, Deserializer};
+
+/// The newtype struct name used by `raw_str` to ask for the undecoded value
+pub(crate) const RAW_STR: &str = "$serde_querystring::private::RawStr";
+
+/// Deserialize a field from its raw value, without any percent decoding or `+` replacement
+///
+/// Meant to be used with `#[serde(deserialize_with = "serde_querystring::raw_str")]`, for
+/// values like timestamps with a `+HH:MM` offset which should keep their `+` as is.
+/// The raw value should still be valid utf-8, and the field type is deserialized from it as a string.
+///
+/// # Example
+/// ```rust
+///# use _serde as serde;
+/// use serde::Deserialize;
+/// use serde_querystring::{from_str, ParseMode};
+///
+/// #[derive(Deserialize)]
+///# #[serde(crate = "_serde")]
+/// struct Query {
+///     #[serde(deserialize_with = "serde_querystring::raw_str")]
+///     since: String,
+/// }
+///
+/// let query: Query = from_str("since=2023-01-01T10:00:00+03:30", ParseMode::Duplicate).unwrap();
+/// assert_eq!(query.since, "2023-01-01T10:00:00+03:30");
+/// ```
+pub fn raw_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    deserializer.deserialize_newtype_struct(RAW_STR, RawStrVisitor(PhantomData))
+}
+
+/// Deserialize a unit enum from the index of its variant, ex. `status=2` for the third variant
+///
+/// Meant to be used with `#[serde(deserialize_with = "serde_querystring::enum_index")]`, for APIs
+/// sending enums by their position. Variants are counted in declaration order from 0,
+/// explicit discriminants (`Variant = 5`) are not taken into account.
+///
+/// # Example
+/// ```rust
+///# use _serde as serde;
+/// use serde::Deserialize;
+/// use serde_querystring::{from_str, ParseMode};
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+///# #[serde(crate = "_serde")]
+/// enum Status {
+///     Pending,
+///     Active,
+///     Closed,
+/// }
+///
+/// #[derive(Deserialize)]
+///# #[serde(crate = "_serde")]
+/// struct Query {
+///     #[serde(deserialize_with = "serde_querystring::enum_index")]
+///     status: Status,
+/// }
+///
+/// let query: Query = from_str("status=2", ParseMode::Duplicate).unwrap();
+/// assert_eq!(query.status, Status::Closed);
+/// ```
+pub fn enum_index<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let index = u32::deserialize(deserializer)?;
+    T::deserialize(value::U32Deserializer::<D::Error>::new(index))
+}
+
+/// Deserialize a `SystemTime` from a unix timestamp in seconds, ex. `since=1700000000`
+///
+/// Meant to be used with `#[serde(deserialize_with = "serde_querystring::unix_seconds")]`.
+/// Negative values are before the unix epoch, and timestamps out of the range of `i64` or
+/// `SystemTime` fail with an error.
+///
+/// # Example
+/// ```rust
+///# use _serde as serde;
+/// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+///
+/// use serde::Deserialize;
+/// use serde_querystring::{from_str, ParseMode};
+///
+/// #[derive(Deserialize)]
+///# #[serde(crate = "_serde")]
+/// struct Query {
+///     #[serde(deserialize_with = "serde_querystring::unix_seconds")]
+///     since: SystemTime,
+/// }
+///
+/// let query: Query = from_str("since=1700000000", ParseMode::Duplicate).unwrap();
+/// assert_eq!(query.since, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+/// ```
+pub fn unix_seconds<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let seconds = i64::deserialize(deserializer)?;
+    let duration = Duration::from_secs(seconds.unsigned_abs());
+    let time = if seconds >= 0 {
+        UNIX_EPOCH.checked_add(duration)
+    } else {
+        UNIX_EPOCH.checked_sub(duration)
+    };
+
+    time.ok_or_else(|| {
+        de::Error::custom(format_args!(
+            "the unix timestamp {} is out of the range of `SystemTime`",
+            seconds
+        ))
+    })
+}
+
+/// The raw bytes of a value, without any percent decoding or `+` replacement
+///
+/// It borrows the value from the input, to decode it later in the application code. Like
+/// [`raw_str`], the raw value should be valid utf-8, which is always the case for percent encoded
+/// values.
+///
+/// # Example
+/// ```rust
+///# use _serde as serde;
+/// use serde::Deserialize;
+/// use serde_querystring::{from_str, ParseMode, RawQuery};
+///
+/// #[derive(Deserialize)]
+///# #[serde(crate = "_serde")]
+/// struct Query<'a> {
+///     #[serde(borrow)]
+///     next: RawQuery<'a>,
+/// }
+///
+/// let query: Query = from_str("next=%2Fhome%3Fa%3D1+2", ParseMode::Duplicate).unwrap();
+/// assert_eq!(query.next, RawQuery(b"%2Fhome%3Fa%3D1+2"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawQuery<'a>(pub &'a [u8]);
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawQuery<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(RAW_STR, RawQueryVisitor)
+    }
+}
+
+struct RawQueryVisitor;
+
+impl<'de> de::Visitor<'de> for RawQueryVisitor {
+    type Value = RawQuery<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a raw value borrowed from the input")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(RawQuery(v.as_bytes()))
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(RawQuery(v))
+    }
+
+    /// Other deserializers don't know about raw values, so the value is used as they provide it
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(self)
+    }
+}
+
+struct RawStrVisitor<T>(PhantomData<T>);
+
+impl<'de, T> de::Visitor<'de> for RawStrVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a raw string")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        T::deserialize(value::BorrowedStrDeserializer::new(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        T::deserialize(value::StrDeserializer::new(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        T::deserialize(value::StringDeserializer::new(v))
+    }
+
+    /// Other deserializers don't know about raw values, so the value is used as they provide it
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer)
+    }
+}
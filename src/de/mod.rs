@@ -1,39 +1,49 @@
-mod error;
+mod helpers;
 mod slices;
 mod traits;
 
+use std::borrow::Cow;
+use std::iter::Peekable;
+
 use _serde::{de, forward_to_deserialize_any};
 
-pub use error::{Error, ErrorKind};
+pub use crate::config::ParseMode;
+pub use crate::error::{Error, ErrorKind};
+pub use helpers::{enum_index, raw_str, unix_seconds, RawQuery};
 
 pub(crate) mod __implementors {
-    pub(crate) use super::slices::{DecodedSlice, RawSlice};
-    pub(crate) use super::traits::{IntoDeserializer, IntoRawSlices};
+    pub(crate) use super::helpers::RAW_STR;
+    pub(crate) use super::slices::{DecodedSlice, RawSlice, Value};
+    pub(crate) use super::traits::{IntoDeserializer, IntoRawSlices, Scratch};
 }
 
+use crate::config::Config;
 use crate::parsers::{BracketsQS, DelimiterQS, DuplicateQS, UrlEncodedQS};
+use __implementors::{RawSlice, Scratch};
 
-pub(crate) struct QSDeserializer<I, T> {
-    iter: I,
+pub(crate) struct QSDeserializer<'s, I: Iterator, T> {
+    iter: Peekable<I>,
     value: Option<T>,
-    scratch: Vec<u8>,
+    scratch: &'s mut Scratch,
+    config: Config,
 }
 
-impl<I, T> QSDeserializer<I, T> {
-    pub fn new(iter: I) -> Self {
+impl<'s, I: Iterator, T> QSDeserializer<'s, I, T> {
+    pub fn new(iter: I, config: Config, scratch: &'s mut Scratch) -> Self {
         Self {
-            iter,
+            iter: iter.peekable(),
             value: None,
-            scratch: Vec::new(),
+            scratch,
+            config,
         }
     }
 }
 
-impl<'de, I, E, A> de::Deserializer<'de> for QSDeserializer<I, A>
+impl<'de, 's, I, E, A> de::Deserializer<'de> for QSDeserializer<'s, I, A>
 where
     I: Iterator<Item = (E, A)>,
-    for<'s> E: __implementors::IntoDeserializer<'de, 's>,
-    for<'s> A: __implementors::IntoDeserializer<'de, 's>,
+    for<'a> E: __implementors::IntoDeserializer<'de, 'a>,
+    for<'a> A: __implementors::IntoDeserializer<'de, 'a>,
 {
     type Error = Error;
 
@@ -44,18 +54,62 @@ where
         visitor.visit_map(self)
     }
 
+    /// An empty querystring is `None`, anything else is deserialized as `Some`
+    fn deserialize_option<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.iter.peek().is_none() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    /// Only an empty querystring is a unit, ex. for `()`
+    fn deserialize_unit<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.iter.peek().is_none() {
+            visitor.visit_unit()
+        } else {
+            Err(Error::new(ErrorKind::InvalidType)
+                .message("expected an empty querystring for a unit".to_string()))
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    /// Skips the whole querystring without visiting its pairs
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
     forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf option unit unit_struct newtype_struct seq tuple
-        tuple_struct map struct enum identifier ignored_any
+        bytes byte_buf newtype_struct seq tuple
+        tuple_struct map struct enum identifier
     }
 }
 
-impl<'de, I, E, A> de::MapAccess<'de> for QSDeserializer<I, A>
+impl<'de, 's, I, E, A> de::MapAccess<'de> for QSDeserializer<'s, I, A>
 where
     I: Iterator<Item = (E, A)>,
-    for<'s> E: __implementors::IntoDeserializer<'de, 's>,
-    for<'s> A: __implementors::IntoDeserializer<'de, 's>,
+    for<'a> E: __implementors::IntoDeserializer<'de, 'a>,
+    for<'a> A: __implementors::IntoDeserializer<'de, 'a>,
 {
     type Error = Error;
 
@@ -63,11 +117,9 @@ where
     where
         K: de::DeserializeSeed<'de>,
     {
-        let mut scratch = Vec::new();
-
         if let Some((k, v)) = self.iter.next() {
             self.value = Some(v);
-            seed.deserialize(k.into_deserializer(&mut scratch))
+            seed.deserialize(k.into_deserializer(self.scratch, &self.config))
                 .map(Some)
         } else {
             Ok(None)
@@ -82,7 +134,7 @@ where
             .value
             .take()
             .expect("Method next_value called before next_key");
-        seed.deserialize(value.into_deserializer(&mut self.scratch))
+        seed.deserialize(value.into_deserializer(self.scratch, &self.config))
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -90,60 +142,344 @@ where
     }
 }
 
-/// An enum used to choose the parsing method for deserialization
-#[derive(Clone, Copy)]
-pub enum ParseMode {
-    /// The simplest parser for querystring.
-    /// It parses the whole querystring, and overwrites each repeated key’s value.
-    /// It does not support vectors, maps nor tuples, but provides the best performance.
-    ///
-    /// More description at ([UrlEncodedQs](crate::UrlEncodedQS))
-    UrlEncoded,
+/// Parses the input with the parser of the mode when it's used as a map or struct
+macro_rules! with_parser {
+    ($self:ident.$method:ident($visitor:ident)) => {{
+        let (input, config, scratch) = ($self.input, &$self.config, $self.scratch);
+        match config.mode {
+            ParseMode::UrlEncoded => {
+                // A simple key=value parser
+                QSDeserializer::new(
+                    UrlEncodedQS::try_parse(input, config)?.into_iter(),
+                    *config,
+                    scratch,
+                )
+                .$method($visitor)
+            }
+            ParseMode::Duplicate => {
+                // A parser with duplicated keys interpreted as sequence
+                QSDeserializer::new(
+                    DuplicateQS::try_parse(input, config)?.into_iter(),
+                    *config,
+                    scratch,
+                )
+                .$method($visitor)
+            }
+            ParseMode::Delimiter(s) => {
+                // A parser with sequences of values seperated by one character
+                QSDeserializer::new(
+                    DelimiterQS::try_parse(input, s, config)?.into_iter(),
+                    *config,
+                    scratch,
+                )
+                .$method($visitor)
+            }
+            ParseMode::Brackets => {
+                // A PHP like interpretation of querystrings
+                QSDeserializer::new(
+                    BracketsQS::try_parse(input, config)?.into_iter(),
+                    *config,
+                    scratch,
+                )
+                .$method($visitor)
+            }
+        }
+    }};
+}
 
-    /// A querystring parser with support for vectors/lists of values by repeating keys.
-    /// (ex. `"key=value1&key=value2"`)
-    ///
-    /// More description at ([DuplicateQs](crate::DuplicateQS))
-    Duplicate,
+/// A pair whose key is not a field of the root struct, as it was in the querystring
+pub(crate) struct ExtraPair<'a> {
+    pub(crate) position: usize,
+    /// The percent decoded key
+    pub(crate) key: Cow<'a, [u8]>,
+    /// The raw key, split after the first bracket in brackets mode
+    pub(crate) raw_key: (&'a [u8], Option<&'a [u8]>),
+    pub(crate) raw_value: Option<&'a [u8]>,
+}
 
-    /// A querystring parser with support for vectors/lists of values by the use of a delimiter byte.
-    /// (ex. `"key=value1|value2|value3"`). Holds the delimiter as a single byte `Delimiter(b'|')`
-    ///
-    /// More description at ([DelimiterQs](crate::DelimiterQS))
-    Delimiter(u8),
+impl ExtraPair<'_> {
+    fn write_raw(&self, output: &mut String) {
+        output.push_str(&String::from_utf8_lossy(self.raw_key.0));
+        if let Some(remains) = self.raw_key.1 {
+            output.push('[');
+            output.push_str(&String::from_utf8_lossy(remains));
+        }
+        if let Some(value) = self.raw_value {
+            output.push('=');
+            output.push_str(&String::from_utf8_lossy(value));
+        }
+    }
+}
+
+/// What happens to the pairs whose keys are not fields of the root struct
+enum ExtraKeys<'s> {
+    /// They are ignored, like serde does
+    Ignore,
+    /// The first one fails the deserialization, like `#[serde(deny_unknown_fields)]`
+    Deny,
+    /// They are written into the string in their original order
+    Collect(&'s mut String),
+}
+
+impl ExtraKeys<'_> {
+    fn handle(
+        &mut self,
+        mut pairs: Vec<ExtraPair<'_>>,
+        fields: &'static [&'static str],
+    ) -> Result<(), Error> {
+        pairs.sort_by_key(|pair| pair.position);
+
+        match self {
+            ExtraKeys::Ignore => {}
+            ExtraKeys::Deny => {
+                if let Some(pair) = pairs.first() {
+                    let name = String::from_utf8_lossy(&pair.key);
+                    return Err(<Error as de::Error>::unknown_field(&name, fields).value(&pair.key));
+                }
+            }
+            ExtraKeys::Collect(output) => {
+                // Empty segments, like in `a=1&&b=2`, are not pairs
+                for pair in pairs
+                    .iter()
+                    .filter(|pair| !pair.raw_key.0.is_empty() || pair.raw_value.is_some())
+                {
+                    if !output.is_empty() {
+                        output.push('&');
+                    }
+                    pair.write_raw(output);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Deserializes the whole querystring, as pairs or as a single unnamed sequence
+struct RootDeserializer<'de, 's> {
+    input: &'de [u8],
+    config: Config,
+    scratch: &'s mut Scratch,
+    extra: ExtraKeys<'s>,
+}
+
+impl<'de, 's> RootDeserializer<'de, 's> {
+    fn values(&self) -> Result<RootValues<'de>, Error> {
+        let config = self.config;
+        let values: Vec<_> = match config.mode {
+            ParseMode::Duplicate => self
+                .input
+                .split(|c| config.is_terminator(*c))
+                .filter(|segment| !segment.is_empty())
+                .map(RawSlice)
+                .collect(),
+            ParseMode::Delimiter(s) => self.input.split(|c| *c == s).map(RawSlice).collect(),
+            _ => {
+                return Err(Error::new(ErrorKind::InvalidType).message(format!(
+                    "sequences at the root are only supported in duplicate and delimiter modes, not in {:?} mode",
+                    config.mode
+                )))
+            }
+        };
+
+        config.check_params(values.len())?;
+        for value in &values {
+            config.check_escapes(value.0)?;
+        }
+
+        Ok(RootValues(values))
+    }
+
+    fn deserialize_values<V>(self, len: Option<usize>, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let config = self.config;
+        let values = self.values()?;
+        let deserializer =
+            __implementors::IntoDeserializer::into_deserializer(values, self.scratch, &config);
+
+        match len {
+            Some(len) => de::Deserializer::deserialize_tuple(deserializer, len, visitor),
+            None => de::Deserializer::deserialize_seq(deserializer, visitor),
+        }
+    }
+}
+
+impl<'de, 's> de::Deserializer<'de> for RootDeserializer<'de, 's> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        with_parser!(self.deserialize_any(visitor))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        with_parser!(self.deserialize_option(visitor))
+    }
+
+    /// Skips the whole querystring without parsing it
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        with_parser!(self.deserialize_unit(visitor))
+    }
 
-    /// A querystring parser with support for vectors/lists, maps and enums
-    /// by the use of brackets(like qs or PHP).(ex. `key[2]=value2&key[1]=value1"`)
+    fn deserialize_unit_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        with_parser!(self.deserialize_unit(visitor))
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_values(None, visitor)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_values(Some(len), visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_values(Some(len), visitor)
+    }
+
+    /// In brackets mode, the fields are visited first and in their declared order
     ///
-    /// More description at ([BracketsQs](crate::BracketsQS))
-    Brackets,
+    /// The pairs which are not fields are taken out of the parser first, unless they are ignored.
+    fn deserialize_struct<V>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let RootDeserializer {
+            input,
+            config,
+            scratch,
+            mut extra,
+        } = self;
+
+        macro_rules! take_extra {
+            ($parser:expr) => {{
+                let mut parser = $parser;
+                if !matches!(extra, ExtraKeys::Ignore) {
+                    extra.handle(
+                        parser.take_extra(|key| fields.iter().any(|field| field.as_bytes() == key)),
+                        fields,
+                    )?;
+                }
+                parser
+            }};
+        }
+
+        match config.mode {
+            ParseMode::UrlEncoded => QSDeserializer::new(
+                take_extra!(UrlEncodedQS::try_parse(input, &config)?).into_iter(),
+                config,
+                scratch,
+            )
+            .deserialize_any(visitor),
+            ParseMode::Duplicate => QSDeserializer::new(
+                take_extra!(DuplicateQS::try_parse(input, &config)?).into_iter(),
+                config,
+                scratch,
+            )
+            .deserialize_any(visitor),
+            ParseMode::Delimiter(s) => QSDeserializer::new(
+                take_extra!(DelimiterQS::try_parse(input, s, &config)?).into_iter(),
+                config,
+                scratch,
+            )
+            .deserialize_any(visitor),
+            ParseMode::Brackets => QSDeserializer::new(
+                take_extra!(BracketsQS::try_parse(input, &config)?).into_struct_iter(fields),
+                config,
+                scratch,
+            )
+            .deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf newtype_struct map enum identifier
+    }
+}
+
+/// The values of a sequence at the root
+struct RootValues<'de>(Vec<RawSlice<'de>>);
+
+impl<'de> __implementors::IntoRawSlices<'de> for RootValues<'de> {
+    type SizedIterator = std::vec::IntoIter<RawSlice<'de>>;
+    type UnSizedIterator = std::vec::IntoIter<RawSlice<'de>>;
+
+    fn into_sized_iterator(self, size: usize) -> Result<Self::SizedIterator, Error> {
+        if self.0.len() == size {
+            Ok(self.0.into_iter())
+        } else {
+            Err(Error::new(ErrorKind::InvalidLength).message(format!(
+                "expected {} values, found {}",
+                size,
+                self.0.len()
+            )))
+        }
+    }
+
+    fn into_unsized_iterator(self) -> Self::UnSizedIterator {
+        self.0.into_iter()
+    }
+
+    fn into_single_slice(mut self) -> RawSlice<'de> {
+        self.0.pop().unwrap_or(RawSlice(b""))
+    }
 }
 
 /// Deserialize an instance of type `T` from bytes of query string.
+///
+/// Querystrings are not self-describing, so by default `deserialize_any` on a value(ex. in a
+/// custom `deserialize_with` function) visits the percent decoded value as a string, even if it
+/// looks like a number or a boolean. `Config::detect_types` visits those values as numbers and
+/// booleans instead. In duplicate mode, repeated keys visit a sequence of those values.
 pub fn from_bytes<'de, T>(input: &'de [u8], config: ParseMode) -> Result<T, Error>
 where
     T: de::Deserialize<'de>,
 {
-    match config {
-        ParseMode::UrlEncoded => {
-            // A simple key=value parser
-            T::deserialize(QSDeserializer::new(UrlEncodedQS::parse(input).into_iter()))
-        }
-        ParseMode::Duplicate => {
-            // A parser with duplicated keys interpreted as sequence
-            T::deserialize(QSDeserializer::new(DuplicateQS::parse(input).into_iter()))
-        }
-        ParseMode::Delimiter(s) => {
-            // A parser with sequences of values seperated by one character
-            T::deserialize(QSDeserializer::new(
-                DelimiterQS::parse(input, s).into_iter(),
-            ))
-        }
-        ParseMode::Brackets => {
-            // A PHP like interpretation of querystrings
-            T::deserialize(QSDeserializer::new(BracketsQS::parse(input).into_iter()))
-        }
-    }
+    from_bytes_with_config(input, &Config::new(config))
 }
 
 /// Deserialize an instance of type `T` from a query string.
@@ -153,3 +489,237 @@ where
 {
     from_bytes(input.as_bytes(), config)
 }
+
+/// Deserialize an instance of type `T` from bytes of query string, using the provided `Config`.
+///
+/// Besides structs and maps, sequences and tuples are supported at the root in duplicate mode,
+/// where each segment is a value(ex. `1&2&3`), and in delimiter mode, where the whole input
+/// is split by the delimiter(ex. `1,2,3`).
+pub fn from_bytes_with_config<'de, T>(input: &'de [u8], config: &Config) -> Result<T, Error>
+where
+    T: de::Deserialize<'de>,
+{
+    config.validate()?;
+    T::deserialize(RootDeserializer {
+        input,
+        config: *config,
+        scratch: &mut Scratch::default(),
+        extra: ExtraKeys::Ignore,
+    })
+}
+
+/// Deserialize an instance of type `T` from a query string, using the provided `Config`.
+pub fn from_str_with_config<'de, T>(input: &'de str, config: &Config) -> Result<T, Error>
+where
+    T: de::Deserialize<'de>,
+{
+    from_bytes_with_config(input.as_bytes(), config)
+}
+
+/// Deserialize an instance of type `T` in loose mode, along with the failures it recovered from.
+///
+/// Numbers and booleans that fail to parse are replaced by their default, and their errors are
+/// returned next to the value. Inside an option the default is kept as well, ex. `Some(0)` for an
+/// `Option<u32>`, as the option is picked before its value is parsed. Errors that can't be recovered, like
+/// a missing field or an unknown enum variant, still fail the deserialization.
+///
+/// # Example
+/// ```rust
+///# use _serde as serde;
+/// use serde::Deserialize;
+/// use serde_querystring::{from_bytes_loose, Config};
+///
+/// #[derive(Deserialize)]
+///# #[serde(crate = "_serde")]
+/// struct Page {
+///     page: u32,
+///     limit: u32,
+/// }
+///
+/// let (page, failures): (Page, _) = from_bytes_loose(b"page=2&limit=ten", &Config::default())
+///     .unwrap();
+/// assert_eq!((page.page, page.limit), (2, 0));
+/// assert_eq!(failures.len(), 1);
+/// ```
+pub fn from_bytes_loose<'de, T>(input: &'de [u8], config: &Config) -> Result<(T, Vec<Error>), Error>
+where
+    T: de::Deserialize<'de>,
+{
+    config.validate()?;
+    let mut scratch = Scratch::loose();
+    let value = T::deserialize(RootDeserializer {
+        input,
+        config: *config,
+        scratch: &mut scratch,
+        extra: ExtraKeys::Ignore,
+    })?;
+    Ok((value, scratch.failures))
+}
+
+/// Deserialize an instance of type `T` from pairs which are already split and percent decoded,
+/// using the provided `Config`.
+///
+/// It's meant for frameworks handing out the pairs of a query, ex. `form_urlencoded::parse`,
+/// without joining them into a string again. The keys and values are not decoded again, and
+/// the limits checked while parsing, like `Config::max_params`, are not applied.
+///
+/// # Example
+/// ```rust
+///# use _serde as serde;
+/// use std::borrow::Cow;
+///
+/// use serde::Deserialize;
+/// use serde_querystring::{from_pairs_iter, Config, ParseMode};
+///
+/// #[derive(Deserialize)]
+///# #[serde(crate = "_serde")]
+/// struct Query {
+///     q: String,
+///     tags: Vec<u32>,
+/// }
+///
+/// let pairs = vec![
+///     (Cow::Borrowed("q"), Cow::Borrowed("a+b %")),
+///     (Cow::Borrowed("tags"), Cow::Borrowed("1")),
+///     (Cow::Borrowed("tags"), Cow::Owned("2".to_string())),
+/// ];
+/// let query: Query = from_pairs_iter(pairs, &Config::new(ParseMode::Duplicate)).unwrap();
+/// assert_eq!(query.q, "a+b %");
+/// assert_eq!(query.tags, vec![1, 2]);
+/// ```
+pub fn from_pairs_iter<'p, I, T>(iter: I, config: &Config) -> Result<T, Error>
+where
+    I: IntoIterator<Item = (Cow<'p, str>, Cow<'p, str>)>,
+    T: de::DeserializeOwned,
+{
+    config.validate()?;
+
+    // The pairs are laid out in a single buffer, so they can all borrow from it
+    let mut buffer = Vec::new();
+    let mut ranges = Vec::new();
+    for (key, value) in iter {
+        let key_start = buffer.len();
+        buffer.extend_from_slice(key.as_bytes());
+        let value_start = buffer.len();
+        buffer.extend_from_slice(value.as_bytes());
+        ranges.push((key_start..value_start, value_start..buffer.len()));
+    }
+
+    let slices = ranges
+        .into_iter()
+        .map(|(key, value)| (&buffer[key], &buffer[value]));
+    let config = config.percent_decode(false);
+
+    match config.mode {
+        ParseMode::UrlEncoded => T::deserialize(QSDeserializer::new(
+            UrlEncodedQS::from_decoded_pairs(slices).into_iter(),
+            config,
+            &mut Scratch::default(),
+        )),
+        ParseMode::Duplicate => T::deserialize(QSDeserializer::new(
+            DuplicateQS::from_decoded_pairs(slices, &config).into_iter(),
+            config,
+            &mut Scratch::default(),
+        )),
+        ParseMode::Delimiter(s) => T::deserialize(QSDeserializer::new(
+            DelimiterQS::from_decoded_pairs(slices, s).into_iter(),
+            config,
+            &mut Scratch::default(),
+        )),
+        ParseMode::Brackets => T::deserialize(QSDeserializer::new(
+            BracketsQS::from_decoded_pairs(slices, &config).into_iter(),
+            config,
+            &mut Scratch::default(),
+        )),
+    }
+}
+
+/// Deserialize a struct from bytes of query string, and return the pairs it didn't use
+///
+/// The pairs whose keys are not fields of the struct are returned as a query string, for example
+/// to forward them to another service. They are kept as they were in the input, in their
+/// original order and without being decoded. In urlencoded and delimiter modes, only the last
+/// pair of a repeated key is kept, like for the fields.
+///
+/// # Note
+/// Only the fields of the root struct are tracked. Other types, like maps or structs with a
+/// `#[serde(flatten)]` field, use all the pairs, so nothing is returned. In brackets mode, a
+/// percent encoded bracket opening the subkeys is returned as `[`.
+///
+/// # Example
+/// ```rust
+///# use _serde as serde;
+/// use serde::Deserialize;
+/// use serde_querystring::{from_bytes_partial, Config, ParseMode};
+///
+/// #[derive(Deserialize)]
+///# #[serde(crate = "_serde")]
+/// struct Page {
+///     page: u32,
+///     limit: u32,
+/// }
+///
+/// let (page, rest): (Page, _) = from_bytes_partial(
+///     b"q=rust&page=2&limit=10&sort=asc",
+///     &Config::new(ParseMode::Duplicate),
+/// )
+/// .unwrap();
+/// assert_eq!(page.page, 2);
+/// assert_eq!(rest, "q=rust&sort=asc");
+/// ```
+pub fn from_bytes_partial<'de, T>(input: &'de [u8], config: &Config) -> Result<(T, String), Error>
+where
+    T: de::Deserialize<'de>,
+{
+    config.validate()?;
+    let mut rest = String::new();
+    let value = T::deserialize(RootDeserializer {
+        input,
+        config: *config,
+        scratch: &mut Scratch::default(),
+        extra: ExtraKeys::Collect(&mut rest),
+    })?;
+    Ok((value, rest))
+}
+
+/// Deserialize a struct from bytes of query string, failing on the keys which are not its fields
+///
+/// It works like `#[serde(deny_unknown_fields)]` on the root struct, without adding the attribute
+/// to the type, ex. for types from other crates. The error is the one of `deny_unknown_fields`,
+/// with the first unknown key of the querystring as its `value`. The keys are checked before
+/// the values are deserialized, so it's returned even if a value is invalid too.
+///
+/// # Note
+/// Like `from_bytes_partial`, only the fields of the root struct are tracked. Other types, like
+/// maps or structs with a `#[serde(flatten)]` field, accept all the keys.
+///
+/// # Example
+/// ```rust
+///# use _serde as serde;
+/// use serde::Deserialize;
+/// use serde_querystring::{from_bytes_deny_extra, Config, ParseMode};
+///
+/// #[derive(Debug, Deserialize)]
+///# #[serde(crate = "_serde")]
+/// struct Page {
+///     page: u32,
+/// }
+///
+/// let config = Config::new(ParseMode::Duplicate);
+/// assert!(from_bytes_deny_extra::<Page>(b"page=2", &config).is_ok());
+///
+/// let error = from_bytes_deny_extra::<Page>(b"page=2&debug=1", &config).unwrap_err();
+/// assert_eq!(error.value, "debug");
+/// ```
+pub fn from_bytes_deny_extra<'de, T>(input: &'de [u8], config: &Config) -> Result<T, Error>
+where
+    T: de::Deserialize<'de>,
+{
+    config.validate()?;
+    T::deserialize(RootDeserializer {
+        input,
+        config: *config,
+        scratch: &mut Scratch::default(),
+        extra: ExtraKeys::Deny,
+    })
+}
@@ -4,34 +4,170 @@ use std::str;
 
 use lexical::FromLexical;
 
-use crate::decode::parse_bytes;
+use crate::config::{BoolFormat, Config, FloatFormat};
+use crate::decode::decode_if;
 use crate::decode::Reference;
 
 use super::{Error, ErrorKind};
 
 pub trait Value<'de> {
-    fn parse_number<'s, T>(&self, scratch: &'s mut Vec<u8>) -> Result<T, Error>
+    fn parse_number<T>(&self, scratch: &mut Vec<u8>, config: &Config) -> Result<T, Error>
     where
-        T: FromLexical;
+        T: Number;
 
-    fn parse_bool<'s>(&self, scratch: &'s mut Vec<u8>) -> Result<bool, Error>;
+    fn parse_bool(&self, scratch: &mut Vec<u8>, config: &Config) -> Result<bool, Error>;
 
-    fn parse_bytes<'s>(self, scratch: &'s mut Vec<u8>) -> Reference<'de, 's, [u8]>;
-    fn parse_str<'s>(self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>, Error>;
+    fn parse_bytes<'s>(self, scratch: &'s mut Vec<u8>, config: &Config)
+        -> Reference<'de, 's, [u8]>;
+    fn parse_str<'s>(
+        self,
+        scratch: &'s mut Vec<u8>,
+        config: &Config,
+    ) -> Result<Reference<'de, 's, str>, Error>;
+
+    /// Returns the value without percent decoding it, if it wasn't decoded already
+    fn parse_raw_str<'s>(self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>, Error>;
 
     fn is_none(&self) -> bool;
+
+    /// Checks the percent decoded value against `Config::null_tokens`
+    fn is_null(&self, scratch: &mut Vec<u8>, config: &Config) -> bool;
+}
+
+/// The numbers parsed from values, floats are checked against the float format of the config
+pub trait Number: FromLexical + Default {
+    const IS_FLOAT: bool;
+
+    /// Parses the hex digits of an integer, floats are never parsed from hex
+    fn from_hex(digits: &str, negative: bool) -> Option<Self>;
+}
+
+macro_rules! impl_number {
+    (integers => $($ty:ty)*) => {
+        $(
+            impl Number for $ty {
+                const IS_FLOAT: bool = false;
+
+                fn from_hex(digits: &str, negative: bool) -> Option<Self> {
+                    // `from_str_radix` accepts a sign of its own
+                    if digits.starts_with(|c| c == '+' || c == '-') {
+                        return None;
+                    }
+
+                    if negative {
+                        <$ty>::from_str_radix(&format!("-{}", digits), 16).ok()
+                    } else {
+                        <$ty>::from_str_radix(digits, 16).ok()
+                    }
+                }
+            }
+        )*
+    };
+    (floats => $($ty:ty)*) => {
+        $(
+            impl Number for $ty {
+                const IS_FLOAT: bool = true;
+
+                fn from_hex(_: &str, _: bool) -> Option<Self> {
+                    None
+                }
+            }
+        )*
+    };
+}
+
+impl_number!(integers => i8 i16 i32 i64 i128 u8 u16 u32 u64 u128);
+impl_number!(floats => f32 f64);
+
+/// Parses a `0x` prefixed integer, returns `None` if the slice is not prefixed
+fn parse_hex<T: Number>(slice: &[u8]) -> Option<Result<T, Error>> {
+    let (negative, rest) = match slice.first() {
+        Some(b'-') => (true, &slice[1..]),
+        _ => (false, slice),
+    };
+    let digits = match rest {
+        [b'0', b'x' | b'X', digits @ ..] => digits,
+        _ => return None,
+    };
+
+    let number = str::from_utf8(digits)
+        .ok()
+        .and_then(|digits| T::from_hex(digits, negative));
+    Some(number.ok_or_else(|| {
+        Error::new(ErrorKind::InvalidNumber)
+            .value(slice)
+            .message("invalid hex integer".to_string())
+    }))
+}
+
+/// Parses a number from a slice, the leniency options of the config are applied here
+#[inline]
+fn parse_number<T>(slice: &[u8], scratch: &mut Vec<u8>, config: &Config) -> Result<T, Error>
+where
+    T: Number,
+{
+    let number = if !config.lenient_numbers {
+        if slice.first() == Some(&b'+') {
+            return Err(Error::new(ErrorKind::InvalidNumber)
+                .value(slice)
+                .message("a leading `+` is only accepted with lenient numbers".to_string()));
+        }
+        slice
+    } else if slice.contains(&b'_') {
+        scratch.clear();
+        scratch.extend(slice.iter().filter(|c| **c != b'_'));
+        scratch
+    } else {
+        slice
+    };
+
+    if T::IS_FLOAT
+        && config.float_format == FloatFormat::NoExponent
+        && number.iter().any(|c| *c == b'e' || *c == b'E')
+    {
+        return Err(Error::new(ErrorKind::InvalidNumber)
+            .value(slice)
+            .message("floats in scientific notation are not accepted".to_string()));
+    }
+
+    if !T::IS_FLOAT && config.hex_integers {
+        if let Some(number) = parse_hex(number) {
+            return number;
+        }
+    }
+
+    lexical::parse(number).map_err(|e| {
+        Error::new(ErrorKind::InvalidNumber)
+            .value(slice)
+            .message(e.to_string())
+    })
 }
 
+/// Parses a boolean from a slice, using the bool format of the config
 #[inline]
-fn invalid_boolean_error(slice: &[u8]) -> Error {
-    Error::new(ErrorKind::InvalidBoolean).value(slice).message(
-        "invalid boolean {}, supported values are 1, on and true for true \
-        and 0, off and false for false"
-            .to_string(),
-    )
+fn parse_bool(slice: &[u8], config: &Config) -> Result<bool, Error> {
+    match config.bool_format {
+        BoolFormat::Default => match slice {
+            b"" | b"1" | b"on" | b"true" => Ok(true),
+            b"0" | b"off" | b"false" => Ok(false),
+            _ => Err(Error::new(ErrorKind::InvalidBoolean).value(slice).message(
+                "invalid boolean, supported values are 1, on and true for true \
+                and 0, off and false for false"
+                    .to_string(),
+            )),
+        },
+        BoolFormat::YesNo if slice.eq_ignore_ascii_case(b"yes") => Ok(true),
+        BoolFormat::YesNo if slice.eq_ignore_ascii_case(b"no") => Ok(false),
+        BoolFormat::YesNo => Err(Error::new(ErrorKind::InvalidBoolean)
+            .value(slice)
+            .message("invalid boolean, supported values are yes and no".to_string())),
+    }
 }
 
 /// Holds a slice of bytes that is already percent decoded
+///
+/// Slices without any percent encoding stay borrowed from the input, so map keys can be
+/// deserialized into `&str`.
 #[derive(Debug)]
 pub struct DecodedSlice<'de>(pub Cow<'de, [u8]>);
 
@@ -42,41 +178,29 @@ impl<'de> fmt::Display for DecodedSlice<'de> {
 }
 
 impl<'de> Value<'de> for DecodedSlice<'de> {
-    fn parse_number<'s, T>(&self, _: &'s mut Vec<u8>) -> Result<T, Error>
+    fn parse_number<T>(&self, scratch: &mut Vec<u8>, config: &Config) -> Result<T, Error>
     where
-        T: FromLexical,
+        T: Number,
     {
-        lexical::parse(&self.0).map_err(|e| {
-            Error::new(ErrorKind::InvalidNumber)
-                .value(&self.0)
-                .message(e.to_string())
-        })
+        parse_number(&self.0, scratch, config)
     }
 
-    fn parse_bool<'s>(&self, _: &'s mut Vec<u8>) -> Result<bool, Error> {
-        match self.0.len() {
-            0 => Ok(true),
-            1 => match self.0[0] {
-                b'1' => Ok(true),
-                b'0' => Ok(false),
-                _ => Err(invalid_boolean_error(&self.0)),
-            },
-            2 if self.0.as_ref() == b"on" => Ok(true),
-            3 if self.0.as_ref() == b"off" => Ok(false),
-            4 if self.0.as_ref() == b"true" => Ok(true),
-            5 if self.0.as_ref() == b"false" => Ok(false),
-            _ => Err(invalid_boolean_error(&self.0)),
-        }
+    fn parse_bool(&self, _: &mut Vec<u8>, config: &Config) -> Result<bool, Error> {
+        parse_bool(&self.0, config)
     }
 
-    fn parse_bytes<'s>(self, _: &'s mut Vec<u8>) -> Reference<'de, 's, [u8]> {
+    fn parse_bytes<'s>(self, _: &'s mut Vec<u8>, _: &Config) -> Reference<'de, 's, [u8]> {
         match self.0 {
             Cow::Borrowed(b) => Reference::Borrowed(b),
             Cow::Owned(o) => Reference::Owned(o),
         }
     }
 
-    fn parse_str<'s>(self, _: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>, Error> {
+    fn parse_str<'s>(
+        self,
+        _: &'s mut Vec<u8>,
+        _: &Config,
+    ) -> Result<Reference<'de, 's, str>, Error> {
         let res = match self.0 {
             Cow::Borrowed(b) => str::from_utf8(b)
                 .map(Reference::Borrowed)
@@ -94,9 +218,17 @@ impl<'de> Value<'de> for DecodedSlice<'de> {
         })
     }
 
+    fn parse_raw_str<'s>(self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>, Error> {
+        self.parse_str(scratch, &Config::default())
+    }
+
     fn is_none(&self) -> bool {
         self.0.is_empty()
     }
+
+    fn is_null(&self, _: &mut Vec<u8>, config: &Config) -> bool {
+        config.is_null(&self.0)
+    }
 }
 
 /// Holds a slice of bytes that is not percent decoded yet
@@ -110,41 +242,33 @@ impl<'de> fmt::Display for RawSlice<'de> {
 }
 
 impl<'de> Value<'de> for RawSlice<'de> {
-    fn parse_number<'s, T>(&self, _: &'s mut Vec<u8>) -> Result<T, Error>
+    fn parse_number<T>(&self, scratch: &mut Vec<u8>, config: &Config) -> Result<T, Error>
     where
-        T: FromLexical,
+        T: Number,
     {
-        lexical::parse(self.0).map_err(|e| {
-            Error::new(ErrorKind::InvalidNumber)
-                .value(self.0)
-                .message(e.to_string())
-        })
+        parse_number(self.0, scratch, config)
     }
 
-    fn parse_bool<'s>(&self, _: &'s mut Vec<u8>) -> Result<bool, Error> {
-        match self.0.len() {
-            0 => Ok(true),
-            1 => match self.0[0] {
-                b'1' => Ok(true),
-                b'0' => Ok(false),
-                _ => Err(invalid_boolean_error(self.0)),
-            },
-            2 if self.0 == b"on" => Ok(true),
-            3 if self.0 == b"off" => Ok(false),
-            4 if self.0 == b"true" => Ok(true),
-            5 if self.0 == b"false" => Ok(false),
-            _ => Err(invalid_boolean_error(self.0)),
-        }
+    fn parse_bool(&self, _: &mut Vec<u8>, config: &Config) -> Result<bool, Error> {
+        parse_bool(self.0, config)
     }
 
-    fn parse_bytes<'s>(self, scratch: &'s mut Vec<u8>) -> Reference<'de, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+    fn parse_bytes<'s>(
+        self,
+        scratch: &'s mut Vec<u8>,
+        config: &Config,
+    ) -> Reference<'de, 's, [u8]> {
+        decode_if(self.0, scratch, config.value_decode())
     }
 
-    fn parse_str<'s>(self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>, Error> {
+    fn parse_str<'s>(
+        self,
+        scratch: &'s mut Vec<u8>,
+        config: &Config,
+    ) -> Result<Reference<'de, 's, str>, Error> {
         let slice = self.0;
 
-        parse_bytes(slice, scratch)
+        decode_if(slice, scratch, config.value_decode())
             .try_map(str::from_utf8)
             .map_err(|error| {
                 Error::new(ErrorKind::InvalidEncoding)
@@ -156,32 +280,64 @@ impl<'de> Value<'de> for RawSlice<'de> {
             })
     }
 
+    fn parse_raw_str<'s>(self, _: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>, Error> {
+        str::from_utf8(self.0)
+            .map(Reference::Borrowed)
+            .map_err(|error| {
+                Error::new(ErrorKind::InvalidEncoding)
+                    .message("invalid utf-8 sequence found in the raw value".to_string())
+                    .value(self.0)
+                    .index(error.valid_up_to())
+            })
+    }
+
     fn is_none(&self) -> bool {
         self.0.is_empty()
     }
+
+    fn is_null(&self, scratch: &mut Vec<u8>, config: &Config) -> bool {
+        config.has_null_values()
+            && config.is_null(&decode_if(self.0, scratch, config.value_decode()))
+    }
 }
 
 impl<'de> Value<'de> for Option<RawSlice<'de>> {
-    fn parse_number<'s, T>(&self, scratch: &'s mut Vec<u8>) -> Result<T, Error>
+    fn parse_number<T>(&self, scratch: &mut Vec<u8>, config: &Config) -> Result<T, Error>
     where
-        T: FromLexical,
+        T: Number,
     {
-        self.unwrap_or_default().parse_number(scratch)
+        self.unwrap_or_default().parse_number(scratch, config)
+    }
+
+    fn parse_bool(&self, scratch: &mut Vec<u8>, config: &Config) -> Result<bool, Error> {
+        self.unwrap_or_default().parse_bool(scratch, config)
     }
 
-    fn parse_bool<'s>(&self, scratch: &'s mut Vec<u8>) -> Result<bool, Error> {
-        self.unwrap_or_default().parse_bool(scratch)
+    fn parse_bytes<'s>(
+        self,
+        scratch: &'s mut Vec<u8>,
+        config: &Config,
+    ) -> Reference<'de, 's, [u8]> {
+        self.unwrap_or_default().parse_bytes(scratch, config)
     }
 
-    fn parse_bytes<'s>(self, scratch: &'s mut Vec<u8>) -> Reference<'de, 's, [u8]> {
-        self.unwrap_or_default().parse_bytes(scratch)
+    fn parse_str<'s>(
+        self,
+        scratch: &'s mut Vec<u8>,
+        config: &Config,
+    ) -> Result<Reference<'de, 's, str>, Error> {
+        self.unwrap_or_default().parse_str(scratch, config)
     }
 
-    fn parse_str<'s>(self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>, Error> {
-        self.unwrap_or_default().parse_str(scratch)
+    fn parse_raw_str<'s>(self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>, Error> {
+        self.unwrap_or_default().parse_raw_str(scratch)
     }
 
     fn is_none(&self) -> bool {
         self.is_none()
     }
+
+    fn is_null(&self, scratch: &mut Vec<u8>, config: &Config) -> bool {
+        self.unwrap_or_default().is_null(scratch, config)
+    }
 }
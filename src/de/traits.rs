@@ -1,21 +1,69 @@
+use std::ops::{Deref, DerefMut};
 use std::str;
 
 use _serde::{de, forward_to_deserialize_any};
-use lexical::{self, FromLexical};
 
+use crate::config::Config;
 use crate::decode::Reference;
+use crate::error::{Error, ErrorKind};
+
+use super::helpers::RAW_STR;
+use super::slices::{DecodedSlice, Number, RawSlice, Value};
+
+/// The buffer used to decode the values, along with the failures recovered in loose mode
+#[derive(Default)]
+pub struct Scratch {
+    buffer: Vec<u8>,
+    /// Set by `from_bytes_loose`, to replace the values failing to parse by their defaults
+    loose: bool,
+    /// The failures replaced by defaults in loose mode, returned by `from_bytes_loose`
+    pub(crate) failures: Vec<Error>,
+}
 
-use super::{
-    error::{Error, ErrorKind},
-    slices::{DecodedSlice, RawSlice, Value},
-};
+impl Scratch {
+    pub(crate) fn loose() -> Self {
+        Self {
+            loose: true,
+            ..Self::default()
+        }
+    }
+
+    /// In loose mode, a value that failed to parse is recorded and replaced by its default
+    #[inline]
+    fn or_loose_default<T>(&mut self, result: Result<T, Error>) -> Result<T, Error>
+    where
+        T: Default,
+    {
+        match result {
+            Err(error) if self.loose => {
+                self.failures.push(error);
+                Ok(T::default())
+            }
+            result => result,
+        }
+    }
+}
+
+impl Deref for Scratch {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for Scratch {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
 
 pub trait IntoDeserializer<'de, 's> {
     /// The type of the deserializer being converted into.
     type Deserializer: de::Deserializer<'de, Error = Error>;
 
     /// Convert this value into a deserializer.
-    fn into_deserializer(self, scratch: &'s mut Vec<u8>) -> Self::Deserializer;
+    fn into_deserializer(self, scratch: &'s mut Scratch, config: &'s Config) -> Self::Deserializer;
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
@@ -23,30 +71,39 @@ pub trait IntoDeserializer<'de, 's> {
 impl<'de, 's> IntoDeserializer<'de, 's> for DecodedSlice<'de> {
     type Deserializer = ValueDeserializer<'s, Self>;
 
-    fn into_deserializer(self, scratch: &'s mut Vec<u8>) -> Self::Deserializer {
-        ValueDeserializer(self, scratch)
+    fn into_deserializer(self, scratch: &'s mut Scratch, config: &'s Config) -> Self::Deserializer {
+        ValueDeserializer(self, scratch, config)
     }
 }
 
 impl<'de, 's> IntoDeserializer<'de, 's> for RawSlice<'de> {
     type Deserializer = ValueDeserializer<'s, Self>;
 
-    fn into_deserializer(self, scratch: &'s mut Vec<u8>) -> Self::Deserializer {
-        ValueDeserializer(self, scratch)
+    fn into_deserializer(self, scratch: &'s mut Scratch, config: &'s Config) -> Self::Deserializer {
+        ValueDeserializer(self, scratch, config)
     }
 }
 
 impl<'de, 's> IntoDeserializer<'de, 's> for Option<RawSlice<'de>> {
     type Deserializer = ValueDeserializer<'s, Self>;
 
-    fn into_deserializer(self, scratch: &'s mut Vec<u8>) -> Self::Deserializer {
-        ValueDeserializer(self, scratch)
+    fn into_deserializer(self, scratch: &'s mut Scratch, config: &'s Config) -> Self::Deserializer {
+        ValueDeserializer(self, scratch, config)
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 
-pub struct ValueDeserializer<'s, T>(T, &'s mut Vec<u8>);
+/// Values in flat modes can't hold maps or structs, only the brackets mode can nest them
+#[cold]
+fn nested_value_error() -> Error {
+    Error::new(ErrorKind::InvalidType).message(String::from(
+        "nested maps and structs can't be deserialized from a single value, \
+        use the brackets mode (`ParseMode::Brackets`) for them, ex. `key[field]=value`",
+    ))
+}
+
+pub struct ValueDeserializer<'s, T>(T, &'s mut Scratch, &'s Config);
 
 macro_rules! deserialize_number {
     ($($method:ident => $visit:ident) *) => {
@@ -56,7 +113,8 @@ macro_rules! deserialize_number {
             where
                 V: de::Visitor<'de>,
             {
-                visitor.$visit(self.0.parse_number(self.1)?)
+                let number = self.0.parse_number(self.1, self.2);
+                visitor.$visit(self.1.or_loose_default(number)?)
             }
         )*
     };
@@ -68,24 +126,57 @@ where
 {
     type Error = Error;
 
+    /// Leaf values are visited as strings, unless `Config::detect_types` is set and they look
+    /// like numbers or booleans
     #[inline]
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
     where
         V: de::Visitor<'de>,
     {
-        match self.0.parse_str(self.1)? {
+        if self.2.detect_types {
+            if let Ok(number) = self.0.parse_number::<i64>(self.1, self.2) {
+                return visitor.visit_i64(number);
+            }
+            if let Ok(number) = self.0.parse_number::<u64>(self.1, self.2) {
+                return visitor.visit_u64(number);
+            }
+            match self.0.parse_number::<f64>(self.1, self.2) {
+                Ok(number) if number.is_finite() => return visitor.visit_f64(number),
+                _ => {}
+            }
+        }
+
+        let value = self.0.parse_str(self.1, self.2)?;
+        if self.2.detect_types {
+            match &*value {
+                "true" => return visitor.visit_bool(true),
+                "false" => return visitor.visit_bool(false),
+                _ => {}
+            }
+        }
+
+        match value {
             Reference::Borrowed(b) => visitor.visit_borrowed_str(b),
             Reference::Copied(o) => visitor.visit_str(o),
             Reference::Owned(o) => visitor.visit_string(o),
         }
     }
 
+    /// `raw_str` asks for the raw value by its special name
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _: &str, visitor: V) -> Result<V::Value, Error>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value, Error>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        if name != RAW_STR {
+            return visitor.visit_newtype_struct(self);
+        }
+
+        match self.0.parse_raw_str(self.1)? {
+            Reference::Borrowed(b) => visitor.visit_borrowed_str(b),
+            Reference::Copied(o) => visitor.visit_str(o),
+            Reference::Owned(o) => visitor.visit_string(o),
+        }
     }
 
     #[inline]
@@ -93,20 +184,37 @@ where
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_bool(self.0.parse_bool(self.1)?)
+        let value = self.0.parse_bool(self.1, self.2);
+        visitor.visit_bool(self.1.or_loose_default(value)?)
     }
 
+    /// With `Config::case_insensitive_enums`, the value is matched against the variant names
+    /// ignoring the ascii case, and the matching name is given to the visitor
     #[inline]
     fn deserialize_enum<V>(
         self,
         _: &'static str,
-        _: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Error>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_enum(self)
+        if !self.2.case_insensitive_enums {
+            return visitor.visit_enum(self);
+        }
+
+        let value = self.0.parse_bytes(self.1, self.2);
+        match variants
+            .iter()
+            .find(|variant| variant.as_bytes().eq_ignore_ascii_case(&value))
+        {
+            Some(variant) => visitor.visit_enum(de::value::StrDeserializer::new(variant)),
+            None => Err(de::Error::unknown_variant(
+                &String::from_utf8_lossy(&value),
+                variants,
+            )),
+        }
     }
 
     #[inline]
@@ -114,7 +222,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        if self.0.is_none() {
+        if self.0.is_none() || self.0.is_null(self.1, self.2) {
             visitor.visit_none()
         } else {
             visitor.visit_some(self)
@@ -130,12 +238,16 @@ where
     }
 
     /// We don't check the bytes to be valid utf8
+    ///
+    /// Values without any encoded byte are borrowed from the input, so `&'de [u8]` targets work
+    /// for them, percent decoded values live in the scratch and can only be copied out
+    /// (`Cow<'de, [u8]>` with `#[serde(borrow)]` accepts both)
     #[inline]
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Error>
     where
         V: de::Visitor<'de>,
     {
-        match self.0.parse_bytes(self.1) {
+        match self.0.parse_bytes(self.1, self.2) {
             Reference::Borrowed(b) => visitor.visit_borrowed_bytes(b),
             Reference::Copied(c) => visitor.visit_bytes(c),
             Reference::Owned(o) => visitor.visit_byte_buf(o),
@@ -157,10 +269,38 @@ where
         self.deserialize_bytes(visitor)
     }
 
+    /// A single value is a sequence of its percent decoded bytes, ex. for `Vec<u8>`
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let bytes = self.0.parse_bytes(self.1, self.2);
+        visitor.visit_seq(BytesDeserializer(bytes.iter()))
+    }
+
+    fn deserialize_map<V>(self, _: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(nested_value_error())
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(nested_value_error())
+    }
+
     forward_to_deserialize_any! {
         <W: Visitor<'de>>
-        char str string unit unit_struct map struct
-        tuple seq tuple_struct
+        char str string unit unit_struct
+        tuple tuple_struct
     }
 
     deserialize_number!(
@@ -194,6 +334,57 @@ where
     }
 }
 
+struct BytesDeserializer<I>(I);
+
+impl<'de, 'a, I> de::SeqAccess<'de> for BytesDeserializer<I>
+where
+    I: Iterator<Item = &'a u8>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        self.0
+            .next()
+            .map(|b| seed.deserialize(ByteDeserializer(*b)))
+            .transpose()
+    }
+}
+
+/// Only `u8` elements are accepted, so other types don't get the bytes of the value by mistake
+struct ByteDeserializer(u8);
+
+impl<'de> de::Deserializer<'de> for ByteDeserializer {
+    type Error = Error;
+
+    #[cold]
+    fn deserialize_any<V>(self, _: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::new(ErrorKind::InvalidType).message(String::from(
+            "Sequences of a single value are only supported for bytes (u8)",
+        )))
+    }
+
+    #[inline]
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_u8(self.0)
+    }
+
+    forward_to_deserialize_any! {
+        <W: Visitor<'de>>
+        bool i8 i16 i32 i64 i128 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 
 pub trait IntoRawSlices<'de> {
@@ -203,6 +394,17 @@ pub trait IntoRawSlices<'de> {
     fn into_sized_iterator(self, size: usize) -> Result<Self::SizedIterator, Error>;
     fn into_unsized_iterator(self) -> Self::UnSizedIterator;
     fn into_single_slice(self) -> RawSlice<'de>;
+
+    /// If true, `deserialize_any` visits all the values as a sequence instead of a single value,
+    /// ex. for repeated keys in duplicate mode
+    fn is_multi_valued(&self) -> bool {
+        false
+    }
+
+    /// If true, `deserialize_option` visits `None`, see `Config::null_tokens`
+    fn is_null(&self, _: &mut Vec<u8>, _: &Config) -> bool {
+        false
+    }
 }
 
 impl<'de, 's, I> IntoDeserializer<'de, 's> for I
@@ -211,12 +413,12 @@ where
 {
     type Deserializer = IterDeserializer<'s, I>;
 
-    fn into_deserializer(self, scratch: &'s mut Vec<u8>) -> Self::Deserializer {
-        IterDeserializer(self, scratch)
+    fn into_deserializer(self, scratch: &'s mut Scratch, config: &'s Config) -> Self::Deserializer {
+        IterDeserializer(self, scratch, config)
     }
 }
 
-pub struct IterDeserializer<'s, I>(I, &'s mut Vec<u8>);
+pub struct IterDeserializer<'s, I>(I, &'s mut Scratch, &'s Config);
 
 impl<'de, 's, I> IterDeserializer<'s, I>
 where
@@ -224,14 +426,15 @@ where
 {
     fn parse_number<T>(self) -> Result<T, Error>
     where
-        T: FromLexical,
+        T: Number,
     {
-        self.0.into_single_slice().parse_number(self.1)
+        let number = self.0.into_single_slice().parse_number(self.1, self.2);
+        self.1.or_loose_default(number)
     }
 
     #[inline]
     fn into_slice_deserializer(self) -> ValueDeserializer<'s, RawSlice<'de>> {
-        ValueDeserializer(self.0.into_single_slice(), self.1)
+        ValueDeserializer(self.0.into_single_slice(), self.1, self.2)
     }
 }
 
@@ -249,6 +452,20 @@ macro_rules! deserialize_number {
     };
 }
 
+macro_rules! forward_to_slice_deserializer {
+    ($($method:ident ,)*) => {
+        $(
+            #[inline]
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                self.into_slice_deserializer().$method(visitor)
+            }
+        )*
+    };
+}
+
 impl<'de, 's, I> de::Deserializer<'de> for IterDeserializer<'s, I>
 where
     I: 'de + IntoRawSlices<'de>,
@@ -260,15 +477,24 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.into_slice_deserializer().deserialize_any(visitor)
+        if self.0.is_multi_valued() {
+            self.deserialize_seq(visitor)
+        } else {
+            self.into_slice_deserializer().deserialize_any(visitor)
+        }
     }
 
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _: &str, visitor: V) -> Result<V::Value, Error>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value, Error>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        if name == RAW_STR {
+            self.into_slice_deserializer()
+                .deserialize_newtype_struct(RAW_STR, visitor)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
     }
 
     #[inline]
@@ -298,7 +524,11 @@ where
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_some(self)
+        if self.0.is_null(self.1, self.2) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
     }
 
     #[inline]
@@ -326,14 +556,18 @@ where
         self.deserialize_bytes(visitor)
     }
 
+    /// A single empty value, ex. `key=` or `key`, is an empty sequence and not a sequence
+    /// of one empty value, so `Option<Vec<T>>` can tell an empty list from a missing key
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_seq(SizedIterDeserializer(
-            self.0.into_unsized_iterator(),
-            self.1,
-        ))
+        let mut iter = self.0.into_unsized_iterator().peekable();
+        if iter.size_hint() == (1, Some(1)) && iter.peek().map_or(false, |v| v.0.is_empty()) {
+            iter.next();
+        }
+
+        visitor.visit_seq(SizedIterDeserializer(iter, 0, self.1, self.2))
     }
 
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
@@ -342,7 +576,9 @@ where
     {
         visitor.visit_seq(SizedIterDeserializer(
             self.0.into_sized_iterator(len)?,
+            0,
             self.1,
+            self.2,
         ))
     }
 
@@ -357,13 +593,39 @@ where
     {
         visitor.visit_seq(SizedIterDeserializer(
             self.0.into_sized_iterator(len)?,
+            0,
             self.1,
+            self.2,
         ))
     }
 
+    forward_to_slice_deserializer! {
+        deserialize_char, deserialize_str, deserialize_string, deserialize_identifier,
+        deserialize_unit,
+    }
+
+    fn deserialize_map<V>(self, _: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(nested_value_error())
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(nested_value_error())
+    }
+
     forward_to_deserialize_any! {
         <W: Visitor<'de>>
-        char str string unit unit_struct map struct identifier
+        unit_struct
     }
 
     deserialize_number!(
@@ -382,7 +644,8 @@ where
     );
 }
 
-struct SizedIterDeserializer<'s, I>(I, &'s mut Vec<u8>);
+/// Visits the values of the iterator, counting them to check the `max_seq_len` of the config
+struct SizedIterDeserializer<'s, I>(I, usize, &'s mut Scratch, &'s Config);
 
 impl<'de, 's, I> de::SeqAccess<'de> for SizedIterDeserializer<'s, I>
 where
@@ -394,10 +657,24 @@ where
     where
         T: de::DeserializeSeed<'de>,
     {
-        self.0
-            .next()
-            .map(|v| seed.deserialize(v.into_deserializer(self.1)))
-            .transpose()
+        let value = match self.0.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        self.1 += 1;
+        self.3.check_seq_len(self.1)?;
+
+        seed.deserialize(value.into_deserializer(self.2, self.3))
+            .map(Some)
+    }
+
+    /// Only exact hints are given, as collections like `SmallVec` reserve based on them
+    fn size_hint(&self) -> Option<usize> {
+        match self.0.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        }
     }
 }
 
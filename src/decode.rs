@@ -1,4 +1,5 @@
 use std::borrow::{Borrow, Cow};
+use std::fmt;
 
 /// Parses a single percent encoded char
 #[inline]
@@ -6,10 +7,42 @@ pub fn parse_char(h: u8, l: u8) -> Option<u8> {
     Some(char::from(h).to_digit(16)? as u8 * 0x10 + char::from(l).to_digit(16)? as u8)
 }
 
-/// Decodes a slice and return a Reference pointer
+/// Returns the index of the first `%` not followed by two hex digits
+pub fn malformed_escape_index(slice: &[u8]) -> Option<usize> {
+    let mut index = 0;
+    while index < slice.len() {
+        if slice[index] == b'%' {
+            match (slice.get(index + 1), slice.get(index + 2)) {
+                (Some(h), Some(l)) if parse_char(*h, *l).is_some() => index += 3,
+                _ => return Some(index),
+            }
+        } else {
+            index += 1;
+        }
+    }
+    None
+}
+
+/// How a key or a value is decoded
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decode {
+    pub percent: bool,
+    pub plus_as_space: bool,
+}
+
+impl Decode {
+    /// Keeps the slice as it is
+    pub const NONE: Self = Self {
+        percent: false,
+        plus_as_space: false,
+    };
+}
+
+/// Decodes a slice and return a Reference pointer, keeping `+` as it is unless `plus_as_space`
 pub fn parse_bytes<'de, 's>(
     slice: &'de [u8],
     scratch: &'s mut Vec<u8>,
+    plus_as_space: bool,
 ) -> Reference<'de, 's, [u8]> {
     scratch.clear();
 
@@ -21,31 +54,27 @@ pub fn parse_bytes<'de, 's>(
 
     while let Some(v) = slice.get(cursor) {
         match v {
-            b'+' => {
+            b'+' if plus_as_space => {
                 scratch.extend_from_slice(&slice[index..cursor]);
                 scratch.push(b' ');
 
                 cursor += 1;
                 index = cursor;
             }
-            b'%' => {
-                // we saw percentage
-                if slice.len() > cursor + 2 {
-                    match parse_char(slice[cursor + 1], slice[cursor + 2]) {
-                        Some(b) => {
-                            scratch.extend_from_slice(&slice[index..cursor]);
-                            scratch.push(b);
-
-                            cursor += 3;
-                            index = cursor;
-                        }
-                        None => {
-                            // If it wasn't valid, go to the next byte
-                            cursor += 1;
-                        }
+            // we saw percentage
+            b'%' if slice.len() > cursor + 2 => {
+                match parse_char(slice[cursor + 1], slice[cursor + 2]) {
+                    Some(b) => {
+                        scratch.extend_from_slice(&slice[index..cursor]);
+                        scratch.push(b);
+
+                        cursor += 3;
+                        index = cursor;
+                    }
+                    None => {
+                        // If it wasn't valid, go to the next byte
+                        cursor += 1;
                     }
-                } else {
-                    cursor += 1;
                 }
             }
             _ => {
@@ -62,6 +91,19 @@ pub fn parse_bytes<'de, 's>(
     }
 }
 
+/// Decodes a slice like `parse_bytes` if percent decoding is enabled, otherwise returns it as it is
+pub fn decode_if<'de, 's>(
+    slice: &'de [u8],
+    scratch: &'s mut Vec<u8>,
+    decode: Decode,
+) -> Reference<'de, 's, [u8]> {
+    if decode.percent {
+        parse_bytes(slice, scratch, decode.plus_as_space)
+    } else {
+        Reference::Borrowed(slice)
+    }
+}
+
 /// A struct that can hold an owned or borrowed value
 ///
 /// The difference between `Reference` and `Cow` is that it can contain a reference
@@ -114,3 +156,12 @@ where
         }
     }
 }
+
+/// Formats a decoded slice as a lossy utf-8 string, used by the parsers' `Debug` implementations
+pub struct DebugSlice<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Debug for DebugSlice<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&String::from_utf8_lossy(self.0), f)
+    }
+}
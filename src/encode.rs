@@ -0,0 +1,144 @@
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Bits of the ascii alphanumerics, `0-9`, `A-Z` and `a-z`
+const ALPHANUMERIC: u128 =
+    ((1 << 10) - 1) << b'0' | ((1 << 26) - 1) << b'A' | ((1 << 26) - 1) << b'a';
+
+/// The set of bytes written as they are when percent encoding, every other byte is written as `%XX`
+///
+/// Alphanumerics are always kept, and spaces are written as `+` or `%20`. Bytes with a meaning in
+/// querystrings(`&`, `=`, `+`, `%`, `#` and brackets) should not be kept, or the output
+/// won't parse back to the same values.
+///
+/// # Example
+/// ```rust
+/// use serde_querystring::PercentEncodeSet;
+///
+/// let set = PercentEncodeSet::STRICT.keep(b"-_").space_as_plus(true);
+/// assert_eq!(set.encode(b"a-b c/d"), "a-b+c%2Fd");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PercentEncodeSet {
+    // A bit for each ascii byte, set if the byte is kept
+    safe: u128,
+    space_as_plus: bool,
+}
+
+impl PercentEncodeSet {
+    /// The `application/x-www-form-urlencoded` style, keeps `-._~` and writes spaces as `+`
+    pub const FORM: Self = Self::STRICT.keep(b"-._~").space_as_plus(true);
+
+    /// For the query part of URLs, keeps `-._~!$'()*,;:@/?` and writes spaces as `%20`
+    pub const QUERY: Self = Self::STRICT.keep(b"-._~!$'()*,;:@/?");
+
+    /// Only keeps alphanumerics, every other byte including spaces is percent encoded
+    pub const STRICT: Self = Self {
+        safe: ALPHANUMERIC,
+        space_as_plus: false,
+    };
+
+    /// Keep the provided bytes as they are, non ascii bytes are always encoded
+    pub const fn keep(mut self, bytes: &[u8]) -> Self {
+        let mut index = 0;
+        while index < bytes.len() {
+            if bytes[index] < 128 {
+                self.safe |= 1 << bytes[index];
+            }
+            index += 1;
+        }
+        self
+    }
+
+    /// Percent encode the provided byte, even if it is kept by the set
+    pub(crate) const fn encode_byte(mut self, byte: u8) -> Self {
+        if byte < 128 {
+            self.safe &= !(1 << byte);
+        }
+        self
+    }
+
+    /// The set used for the names of keys, brackets are always encoded so they aren't
+    /// mistaken for nesting
+    pub(crate) const fn for_keys(self) -> Self {
+        self.encode_byte(b'[').encode_byte(b']')
+    }
+
+    /// Write spaces as `+` instead of `%20`
+    pub const fn space_as_plus(mut self, enabled: bool) -> Self {
+        self.space_as_plus = enabled;
+        self
+    }
+
+    #[inline]
+    fn is_safe(&self, b: u8) -> bool {
+        b < 128 && self.safe & (1 << b) != 0
+    }
+
+    /// Percent encodes a slice and appends it to the output
+    pub fn encode_into(&self, slice: &[u8], output: &mut String) {
+        for &b in slice {
+            match b {
+                b if self.is_safe(b) => output.push(char::from(b)),
+                b' ' if self.space_as_plus => output.push('+'),
+                _ => {
+                    output.push('%');
+                    output.push(char::from(HEX[usize::from(b >> 4)]));
+                    output.push(char::from(HEX[usize::from(b & 0x0F)]));
+                }
+            }
+        }
+    }
+
+    /// Percent encodes a slice into a new string
+    pub fn encode(&self, slice: &[u8]) -> String {
+        let mut output = String::with_capacity(slice.len());
+        self.encode_into(slice, &mut output);
+        output
+    }
+
+    /// Appends a pair to the output, the key should be already encoded while the value is encoded here
+    pub(crate) fn write_pair(&self, output: &mut String, key: &str, value: Option<&[u8]>) {
+        if !output.is_empty() {
+            output.push('&');
+        }
+
+        output.push_str(key);
+
+        if let Some(value) = value {
+            output.push('=');
+            self.encode_into(value, output);
+        }
+    }
+
+    /// Appends a pair to the output, both the key and the value should be already encoded
+    pub(crate) fn write_encoded_pair(&self, output: &mut String, key: &str, value: &str) {
+        if !output.is_empty() {
+            output.push('&');
+        }
+
+        output.push_str(key);
+        output.push('=');
+        output.push_str(value);
+    }
+}
+
+impl Default for PercentEncodeSet {
+    fn default() -> Self {
+        Self::FORM
+    }
+}
+
+/// Percent encodes a slice and appends it to the output, using the `FORM` set
+pub fn encode_into(slice: &[u8], output: &mut String) {
+    PercentEncodeSet::FORM.encode_into(slice, output)
+}
+
+/// Percent encodes a slice into a new string, using the `FORM` set
+pub fn encode(slice: &[u8]) -> String {
+    PercentEncodeSet::FORM.encode(slice)
+}
+
+/// Appends a pair to the output using the `FORM` set, the key should be already encoded
+pub fn write_pair(output: &mut String, key: &str, value: Option<&[u8]>) {
+    PercentEncodeSet::FORM.write_pair(output, key, value)
+}
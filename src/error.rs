@@ -0,0 +1,153 @@
+use std::fmt;
+
+#[derive(Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    InvalidType,
+    InvalidLength,
+    InvalidEncoding,
+    InvalidNumber,
+    InvalidBoolean,
+    InvalidKey,
+    /// Contradictory options in a `Config`, see `Config::validate`
+    InvalidConfig,
+    Other,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+
+    // The slice causing the error
+    pub value: String,
+    // Index of the byte in the value slice, causing the error
+    pub index: Option<usize>,
+}
+
+impl Error {
+    pub(crate) fn new(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            message: String::new(),
+            value: String::new(),
+            index: None,
+        }
+    }
+
+    pub(crate) fn message(mut self, message: String) -> Self {
+        self.message = message;
+        self
+    }
+
+    pub(crate) fn value(mut self, slice: &[u8]) -> Self {
+        self.value = String::from_utf8_lossy(slice).to_string();
+        self
+    }
+
+    pub(crate) fn index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Returns true if the querystring itself is malformed, ex. invalid percent escapes or utf8,
+    /// empty keys or keys nested too deep (`InvalidEncoding` and `InvalidKey`)
+    pub fn is_syntax(&self) -> bool {
+        matches!(
+            self.kind,
+            ErrorKind::InvalidEncoding | ErrorKind::InvalidKey
+        )
+    }
+
+    /// Returns true if a value doesn't fit its type, ex. `abc` for a number
+    /// (`InvalidType`, `InvalidNumber` and `InvalidBoolean`)
+    pub fn is_type(&self) -> bool {
+        matches!(
+            self.kind,
+            ErrorKind::InvalidType | ErrorKind::InvalidNumber | ErrorKind::InvalidBoolean
+        )
+    }
+
+    /// Returns true if a limit of the config is exceeded, or a tuple or array got the wrong
+    /// number of values (`InvalidLength`)
+    pub fn is_length(&self) -> bool {
+        self.kind == ErrorKind::InvalidLength
+    }
+}
+
+#[cfg(feature = "serde")]
+impl _serde::de::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        Error::new(ErrorKind::Other).message(msg.to_string())
+    }
+
+    fn invalid_type(unexp: _serde::de::Unexpected, exp: &dyn _serde::de::Expected) -> Self {
+        Error::new(ErrorKind::InvalidType)
+            .message(format_args!("invalid type: {}, expected {}", unexp, exp).to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl _serde::ser::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        Error::new(ErrorKind::Other).message(msg.to_string())
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A contradictory setting of a `Config`, found by `Config::validate`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The delimiter of `ParseMode::Delimiter` also separates pairs or keys from values, ex. `&`
+    DelimiterConflict(u8),
+    /// `Config::fill_seq_gaps` is set while `SeqOrder::BySubmission` ignores the indexes
+    SeqGapsWithoutIndexes,
+    /// `Config::max_depth` is set while only `ParseMode::Brackets` has nested keys
+    DepthWithoutBrackets,
+}
+
+impl std::error::Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::DelimiterConflict(_) => {
+                f.write_str("the delimiter also separates pairs or keys from values")
+            }
+            ConfigError::SeqGapsWithoutIndexes => {
+                f.write_str("sequence gaps can't be filled when the indexes are ignored")
+            }
+            ConfigError::DepthWithoutBrackets => {
+                f.write_str("the nesting depth can only be limited in brackets mode")
+            }
+        }
+    }
+}
+
+/// Config errors are `InvalidConfig` errors, with the conflicting delimiter as their value
+impl From<ConfigError> for Error {
+    fn from(error: ConfigError) -> Self {
+        let converted = Error::new(ErrorKind::InvalidConfig).message(error.to_string());
+        match error {
+            ConfigError::DelimiterConflict(delimiter) => converted.value(&[delimiter]),
+            ConfigError::SeqGapsWithoutIndexes | ConfigError::DepthWithoutBrackets => converted,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "Error {:?}: {} in `{}`",
+            self.kind, self.message, self.value
+        ))
+    }
+}
@@ -1,6 +1,9 @@
 #![doc = include_str!("../README.md")]
 
+mod config;
 mod decode;
+mod encode;
+mod error;
 
 #[doc(hidden)]
 pub mod parsers;
@@ -9,8 +12,26 @@ pub mod parsers;
 #[doc(hidden)]
 pub mod de;
 
-pub use parsers::{BracketsQS, DelimiterQS, DuplicateQS, UrlEncodedQS};
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub mod ser;
+
+pub use config::{BoolFormat, Config, EmptyKeys, FloatFormat, KeyOrder, ParseMode, SeqOrder};
+pub use encode::PercentEncodeSet;
+pub use error::{ConfigError, Error, ErrorKind};
+pub use parsers::{
+    parse_lossy, parse_pairs_ordered, BracketsQS, DelimiterQS, DuplicateQS, LazyDuplicateQS,
+    LossyQS, OwnedDuplicateQS, PairCursor, UrlEncodedQS,
+};
+
+#[cfg(feature = "serde")]
+#[doc(inline)]
+pub use de::{
+    enum_index, from_bytes, from_bytes_deny_extra, from_bytes_loose, from_bytes_partial,
+    from_bytes_with_config, from_pairs_iter, from_str, from_str_with_config, raw_str, unix_seconds,
+    RawQuery,
+};
 
 #[cfg(feature = "serde")]
 #[doc(inline)]
-pub use de::{from_bytes, from_str, Error, ErrorKind, ParseMode};
+pub use ser::{to_string, to_string_merged, to_string_with_config, to_writer};
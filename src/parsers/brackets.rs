@@ -1,6 +1,9 @@
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{borrow::Cow, collections::BTreeMap, fmt, ops::Range};
 
-use crate::decode::{parse_bytes, parse_char, Reference};
+use crate::config::{Config, ParseMode};
+use crate::decode::{decode_if, parse_char, DebugSlice, Decode, Reference};
+use crate::encode::{encode, write_pair};
+use crate::error::Error;
 
 /// A `Key` in brackets mode represents some state of a parsed key
 ///
@@ -13,46 +16,69 @@ use crate::decode::{parse_bytes, parse_char, Reference};
 /// For this pair `key[key1][key2]=value`, the first Key would be (`key`, `key1][key2]`).
 /// the first time we call the `sub_key` method we get (`key1`, `key2]`).
 /// and by calling `sub_key` again on the result we get (`key2`, None)
+///
+/// The third field tells if the key should be percent decoded, and the last one if percent
+/// encoded brackets are kept as literals, both are passed down to the subkeys.
 #[derive(Clone, Copy)]
-struct Key<'a>(&'a [u8], Option<&'a [u8]>);
+struct Key<'a>(&'a [u8], Option<&'a [u8]>, Decode, bool);
 
 impl<'a> Key<'a> {
-    fn parse(slice: &'a [u8]) -> (Self, usize) {
+    fn parse(slice: &'a [u8], config: &Config) -> (Self, usize) {
         let mut index = 0;
         while index < slice.len() {
             match slice[index] {
                 b'[' => {
-                    let res = Key::parse_remains(&slice[..index], &slice[(index + 1)..]);
+                    let res = Key::parse_remains(&slice[..index], &slice[(index + 1)..], config);
                     return (res.0, res.1 + index + 1);
                 }
                 b'%' => {
                     // Percent encoded opening bracket
-                    if index + 2 < slice.len()
+                    if !config.literal_encoded_brackets
+                        && index + 2 < slice.len()
                         && parse_char(slice[index + 1], slice[index + 2]) == Some(b'[')
                     {
-                        let res = Key::parse_remains(&slice[..index], &slice[(index + 3)..]);
+                        let res =
+                            Key::parse_remains(&slice[..index], &slice[(index + 3)..], config);
                         return (res.0, res.1 + index + 3);
                     };
                     index += 1;
                 }
-                b'&' | b'=' => break,
+                b'=' => break,
+                c if config.is_terminator(c) => break,
                 _ => index += 1,
             }
         }
 
-        (Self(&slice[..index], None), index)
+        (
+            Self(
+                &slice[..index],
+                None,
+                config.key_decode(),
+                config.literal_encoded_brackets,
+            ),
+            index,
+        )
     }
 
-    fn parse_remains(key: &'a [u8], slice: &'a [u8]) -> (Self, usize) {
+    fn parse_remains(key: &'a [u8], slice: &'a [u8], config: &Config) -> (Self, usize) {
         let mut index = 0;
         while index < slice.len() {
             match slice[index] {
-                b'&' | b'=' => break,
+                b'=' => break,
+                c if config.is_terminator(c) => break,
                 _ => index += 1,
             }
         }
 
-        (Self(key, Some(&slice[..index])), index)
+        (
+            Self(
+                key,
+                Some(&slice[..index]),
+                config.key_decode(),
+                config.literal_encoded_brackets,
+            ),
+            index,
+        )
     }
 
     fn subkey(self) -> Option<Self> {
@@ -67,8 +93,9 @@ impl<'a> Key<'a> {
                     break;
                 }
                 b'%' => {
-                    // Percent encoded opening bracket
-                    if index + 2 < remains.len()
+                    // Percent encoded closing bracket
+                    if !self.3
+                        && index + 2 < remains.len()
                         && parse_char(remains[index + 1], remains[index + 2]) == Some(b']')
                     {
                         key_end_index = index;
@@ -83,14 +110,25 @@ impl<'a> Key<'a> {
         }
 
         if index + 1 < remains.len() && remains[index + 1] == b'[' {
-            Some(Self(&remains[..key_end_index], Some(&remains[index + 2..])))
-        } else if index + 3 < remains.len()
+            Some(Self(
+                &remains[..key_end_index],
+                Some(&remains[index + 2..]),
+                self.2,
+                self.3,
+            ))
+        } else if !self.3
+            && index + 3 < remains.len()
             && remains[index + 1] == b'%'
             && parse_char(remains[index + 2], remains[index + 3]) == Some(b'[')
         {
-            Some(Self(&remains[..key_end_index], Some(&remains[index + 4..])))
+            Some(Self(
+                &remains[..key_end_index],
+                Some(&remains[index + 4..]),
+                self.2,
+                self.3,
+            ))
         } else {
-            Some(Self(&remains[..key_end_index], None))
+            Some(Self(&remains[..key_end_index], None, self.2, self.3))
         }
     }
 
@@ -102,8 +140,9 @@ impl<'a> Key<'a> {
                     match remains[index] {
                         b']' => return true,
                         b'%' => {
-                            // Percent encoded opening bracket
-                            if index + 2 < remains.len()
+                            // Percent encoded closing bracket
+                            if !self.3
+                                && index + 2 < remains.len()
                                 && parse_char(remains[index + 1], remains[index + 2]) == Some(b']')
                             {
                                 return true;
@@ -113,12 +152,31 @@ impl<'a> Key<'a> {
                         _ => index += 1,
                     }
                 }
-                return false;
+                false
             }
             None => false,
         }
     }
 
+    /// Returns the number of subkeys, ex. 2 for `key[key1][key2]`
+    fn depth(self) -> usize {
+        let mut depth = 0;
+        let mut key = self;
+        while let Some(subkey) = key.subkey() {
+            depth += 1;
+            key = subkey;
+        }
+        depth
+    }
+
+    /// Returns the length of the whole key in the parsed slice, ex. 7 for `key[a]`
+    fn len(&self) -> usize {
+        match self.1 {
+            Some(remains) => remains.as_ptr() as usize + remains.len() - self.0.as_ptr() as usize,
+            None => self.0.len(),
+        }
+    }
+
     fn is_empty(&self) -> bool {
         match self.1 {
             Some(r) => self.0.is_empty() && r.is_empty(),
@@ -127,35 +185,39 @@ impl<'a> Key<'a> {
     }
 
     fn decode<'s>(&self, scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+        decode_if(self.0, scratch, self.2)
+    }
+
+    fn slice(&self) -> &'a [u8] {
+        self.0
     }
 }
 
 #[derive(Default, Clone, Copy)]
-struct Value<'a>(&'a [u8]);
+struct Value<'a>(&'a [u8], Decode);
 
 impl<'a> Value<'a> {
-    fn parse(slice: &'a [u8]) -> (Option<Self>, usize) {
-        match slice.get(0) {
-            Some(b'&') | None => {
+    fn parse(slice: &'a [u8], config: &Config) -> (Option<Self>, usize) {
+        match slice.first() {
+            Some(c) if !config.is_terminator(*c) => {}
+            _ => {
                 return (None, 0);
             }
-            _ => {}
         }
 
         let mut index = 1;
         while index < slice.len() {
             match slice[index] {
-                b'&' => break,
+                c if config.is_terminator(c) => break,
                 _ => index += 1,
             }
         }
 
-        (Some(Self(&slice[1..index])), index)
+        (Some(Self(&slice[1..index], config.value_decode())), index)
     }
 
     fn decode<'s>(&self, scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+        decode_if(self.0, scratch, self.1)
     }
 
     fn slice(&self) -> &'a [u8] {
@@ -163,8 +225,9 @@ impl<'a> Value<'a> {
     }
 }
 
+/// A pair with its index in the querystring, which keeps the submission order of the keys
 #[derive(Clone, Copy)]
-struct Pair<'a>(Key<'a>, Option<Value<'a>>);
+struct Pair<'a>(Key<'a>, Option<Value<'a>>, usize);
 
 impl<'a> Pair<'a> {
     /// Parses a pair of key-value and return a `Pair` and a skip len
@@ -172,15 +235,15 @@ impl<'a> Pair<'a> {
     /// Unlike other parser methods, we directly return the skip_len here
     /// since there are many exceptions to take into account in this method
     /// and it helps avoid some recalculations.
-    fn parse(slice: &'a [u8]) -> (Self, usize) {
-        let (key, key_len) = Key::parse(slice);
-        let (value, value_len) = Value::parse(&slice[key_len..]);
+    fn parse(slice: &'a [u8], config: &Config, position: usize) -> (Self, usize) {
+        let (key, key_len) = Key::parse(slice, config);
+        let (value, value_len) = Value::parse(&slice[key_len..], config);
 
-        (Self(key, value), key_len + value_len + 1)
+        (Self(key, value, position), key_len + value_len + 1)
     }
 
-    fn new(k: Key<'a>, v: Option<Value<'a>>) -> Pair<'a> {
-        Self(k, v)
+    fn new(k: Key<'a>, v: Option<Value<'a>>, position: usize) -> Pair<'a> {
+        Self(k, v, position)
     }
 }
 
@@ -193,6 +256,10 @@ impl<'a> Pair<'a> {
 /// Sub keys/Sub values(The part of the key after bracket opening) is visited when calling the `sub_values`
 /// method, to limit unnecessary allocations and parsing(and stack overflows from too many levels).
 ///
+/// A key can have both direct values and subkeys, ex. `foo=1&foo[bar]=2`. When deserializing,
+/// the shape of the target decides: scalars like numbers and strings get the last direct
+/// value(`1`), while maps and structs only see the subkeys(`{bar: 2}`).
+///
 /// # Example
 /// ```rust
 ///# use std::borrow::Cow;
@@ -227,44 +294,82 @@ impl<'a> Pair<'a> {
 /// ```
 pub struct BracketsQS<'a> {
     pairs: BTreeMap<Cow<'a, [u8]>, Vec<Pair<'a>>>,
+    // The config it was parsed with, to deserialize the values with the same options
+    config: Config,
 }
 
 impl<'a> BracketsQS<'a> {
     /// Parse a slice of bytes into a `BracketsQS`
     pub fn parse(slice: &'a [u8]) -> Self {
+        Self::try_parse(slice, &Config::default())
+            .expect("Parsing with the default config doesn't fail")
+    }
+
+    /// Parse a slice of bytes into a `BracketsQS`, using the provided `Config`
+    ///
+    /// It returns an error if the slice violates any of the config's restrictions.
+    pub fn try_parse(slice: &'a [u8], config: &Config) -> Result<Self, Error> {
+        config.validate_for(ParseMode::Brackets)?;
         let mut pairs: BTreeMap<_, Vec<Pair<'a>>> = BTreeMap::new();
         let mut scratch = Vec::new();
 
         let mut index = 0;
+        let mut params = 0;
+        let mut position = 0;
 
         while index < slice.len() {
-            let (pair, pair_len) = Pair::parse(&slice[index..]);
+            let (pair, pair_len) = Pair::parse(&slice[index..], config, position);
             index += pair_len;
+            position += 1;
+
+            config.check_pair(pair.0.slice(), pair.1.map(|v| v.slice()))?;
+            config.check_key_len(pair.0.slice(), pair.0.len())?;
+            if let Some(remains) = pair.0 .1 {
+                config.check_escapes(remains)?;
+                config.check_utf8(pair.0.slice(), remains, config.key_decode())?;
+            }
+            if config.skips_empty_key(pair.0.slice(), pair.1.map(|v| v.slice())) {
+                continue;
+            }
+            if !pair.0.is_empty() || pair.1.is_some() {
+                params += 1;
+                config.check_params(params)?;
+            } else if config.skip_empty_segments {
+                continue;
+            }
+            if config.max_depth.is_some() {
+                config.check_depth(pair.0.slice(), pair.0.depth())?;
+            }
 
             let decoded_key = pair.0.decode(&mut scratch);
 
             if let Some(values) = pairs.get_mut(decoded_key.as_ref()) {
+                config.check_values_per_key(&decoded_key, values.len() + 1)?;
                 values.push(pair);
             } else {
+                config.check_values_per_key(&decoded_key, 1)?;
                 pairs.insert(decoded_key.into_cow(), vec![pair]);
             }
         }
 
-        Self { pairs }
+        Ok(Self {
+            pairs,
+            config: *config,
+        })
     }
 
-    fn from_pairs<I>(iter: I) -> Self
+    fn from_pairs<I>(iter: I, config: &Config) -> Self
     where
         I: Iterator<Item = Pair<'a>>,
     {
         let mut pairs: BTreeMap<_, Vec<Pair<'a>>> = BTreeMap::new();
 
         let mut scratch = Vec::new();
-        let subpairs = iter.filter_map(|p| Some((p.0.subkey()?, p.1)));
+        let subpairs = iter.filter_map(|p| Some((p.0.subkey()?, p.1, p.2)));
 
-        for (k, v) in subpairs {
+        for (k, v, position) in subpairs {
             let decoded_key = k.decode(&mut scratch);
-            let pair = Pair::new(k, v);
+            let pair = Pair::new(k, v, position);
 
             if let Some(values) = pairs.get_mut(decoded_key.as_ref()) {
                 values.push(pair);
@@ -273,7 +378,10 @@ impl<'a> BracketsQS<'a> {
             }
         }
 
-        Self { pairs }
+        Self {
+            pairs,
+            config: *config,
+        }
     }
 
     /// Returns a vector containing all the keys in querystring.
@@ -282,8 +390,20 @@ impl<'a> BracketsQS<'a> {
     }
 
     /// Parses all the subkeys for this key and optionally returns a new 'BracketsQS' if the key exists
-    pub fn sub_values(&self, key: &'a [u8]) -> Option<BracketsQS> {
-        Some(Self::from_pairs(self.pairs.get(key)?.iter().copied()))
+    pub fn sub_values(&self, key: &'a [u8]) -> Option<BracketsQS<'_>> {
+        Some(Self::from_pairs(
+            self.pairs.get(key)?.iter().copied(),
+            &self.config,
+        ))
+    }
+
+    /// Returns a vector containing the decoded immediate subkeys of a key, ex. `bar` for `foo[bar]=baz`
+    ///
+    /// It returns None if the **key doesn't exist** in the querystring, and an empty vector if
+    /// the key doesn't have any subkeys.
+    pub fn subkeys(&self, key: &[u8]) -> Option<Vec<Cow<'a, [u8]>>> {
+        let pairs = Self::from_pairs(self.pairs.get(key)?.iter().copied(), &self.config).pairs;
+        Some(pairs.into_keys().collect())
     }
 
     /// Returns a vector containing all the values assigned to a key.
@@ -306,6 +426,92 @@ impl<'a> BracketsQS<'a> {
         )
     }
 
+    /// Returns the last direct value assigned to a path of keys, ex. `[b"a", b"b"]` for `a[b]=value`.
+    ///
+    /// It is the same as calling `sub_values` for all but the last key of the path and then `value`
+    /// for the last one, without parsing the intermediate levels into new `BracketsQS`s.
+    /// It returns `None` if the **path doesn't exist** or is empty.
+    ///
+    /// # Note
+    /// Percent decoding the value is done on-the-fly **every time** this function is called.
+    pub fn get_nested(&self, path: &[&[u8]]) -> Option<Option<Cow<'a, [u8]>>> {
+        let (first, rest) = path.split_first()?;
+        let mut scratch = Vec::new();
+
+        self.pairs
+            .get(*first)?
+            .iter()
+            .rfind(|p| {
+                let mut key = p.0;
+                for subkey in rest {
+                    match key.subkey() {
+                        Some(k) if k.decode(&mut scratch).as_ref() == *subkey => key = k,
+                        _ => return false,
+                    }
+                }
+                !key.has_subkey()
+            })
+            .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
+    }
+
+    /// Returns the last direct values of the numeric subkeys of a key within `range`, ex.
+    /// `items[0]=a&items[1]=b`, ordered by their index.
+    ///
+    /// Subkeys which are not numbers or have more subkeys, ex. `items[0][name]=a`, are
+    /// skipped. It returns `None` if the **key doesn't exist** in the querystring.
+    ///
+    /// # Note
+    /// Percent decoding the value is done on-the-fly **every time** this function is called.
+    #[allow(clippy::type_complexity)]
+    pub fn index_range(
+        &self,
+        key: &[u8],
+        range: Range<usize>,
+    ) -> Option<Vec<(usize, Option<Cow<'a, [u8]>>)>> {
+        let mut scratch = Vec::new();
+        let mut indexes = BTreeMap::new();
+
+        for pair in self.pairs.get(key)? {
+            let subkey = match pair.0.subkey() {
+                Some(subkey) if !subkey.has_subkey() => subkey,
+                _ => continue,
+            };
+            let index = std::str::from_utf8(&subkey.decode(&mut scratch))
+                .ok()
+                .and_then(|index| index.parse::<usize>().ok());
+
+            match index {
+                Some(index) if range.contains(&index) => {
+                    indexes.insert(index, pair.1);
+                }
+                _ => {}
+            }
+        }
+
+        Some(
+            indexes
+                .into_iter()
+                .map(|(index, value)| (index, value.map(|v| v.decode(&mut scratch).into_cow())))
+                .collect(),
+        )
+    }
+
+    /// Returns the number of assignments to a key, including the ones to its subkeys.
+    ///
+    /// It returns 0 if the **key doesn't exist** in the querystring, and it doesn't decode
+    /// or allocate anything.
+    pub fn values_count(&self, key: &[u8]) -> usize {
+        self.pairs.get(key).map_or(0, |pairs| pairs.len())
+    }
+
+    /// Returns the number of direct values assigned to a key, the same as the length of `values`
+    /// but without decoding or allocating anything.
+    pub fn leaf_values_count(&self, key: &[u8]) -> usize {
+        self.pairs.get(key).map_or(0, |pairs| {
+            pairs.iter().filter(|p| !p.0.has_subkey()).count()
+        })
+    }
+
     /// Returns the last direct value assigned to a key.
     ///
     /// It returns `None` if the **key doesn't exist** in the querystring,
@@ -319,71 +525,458 @@ impl<'a> BracketsQS<'a> {
         self.pairs
             .get(key)?
             .iter()
-            .filter(|p| !p.0.has_subkey())
-            .last()
+            .rfind(|p| !p.0.has_subkey())
             .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
     }
+
+    /// Returns the last value assigned to a key, or `default` if the **key doesn't exist** or
+    /// its last assignment **doesn't have a value**, ex `"&key&"`
+    pub fn value_or(&self, key: &'a [u8], default: &'a [u8]) -> Cow<'a, [u8]> {
+        self.value(key).flatten().unwrap_or(Cow::Borrowed(default))
+    }
+
+    /// Returns the last direct value assigned to a key, without percent decoding it.
+    ///
+    /// It returns `None` and `Some(None)` in the same cases as `value`, but doesn't decode
+    /// or allocate anything.
+    pub fn raw_value(&self, key: &[u8]) -> Option<Option<&'a [u8]>> {
+        self.pairs
+            .get(key)?
+            .iter()
+            .rfind(|p| !p.0.has_subkey())
+            .map(|p| p.1.map(|v| v.slice()))
+    }
+
+    /// Returns whether the last direct value of a key needs percent decoding, ex. `true` for `a%20b`.
+    ///
+    /// `value` borrows the values which don't need decoding from the input(`Cow::Borrowed`) and
+    /// copies the others(`Cow::Owned`), so it tells how a value can be stored before calling
+    /// `value`. It returns `None` if the **key doesn't exist**, and `Some(false)` for an assignment
+    /// without a value, ex `"&key&"`.
+    pub fn needs_decoding(&self, key: &[u8]) -> Option<bool> {
+        let mut scratch = Vec::new();
+
+        self.pairs
+            .get(key)?
+            .iter()
+            .rfind(|p| !p.0.has_subkey())
+            .map(|p| {
+                p.1.map_or(false, |v| {
+                    matches!(v.decode(&mut scratch), Reference::Copied(_))
+                })
+            })
+    }
+
+    /// Percent decodes the last direct value of a key into `buf`, replacing its content.
+    ///
+    /// Unlike `value`, it never allocates once `buf` is large enough, so a single buffer can be
+    /// reused for many keys. It returns `None` if the **key doesn't exist**, and `Some(false)`
+    /// leaving `buf` empty if the last assignment to a **key doesn't have a value**, ex `"&key&"`.
+    pub fn decode_into(&self, key: &[u8], buf: &mut Vec<u8>) -> Option<bool> {
+        let pair = self.pairs.get(key)?.iter().rfind(|p| !p.0.has_subkey())?;
+
+        buf.clear();
+        match &pair.1 {
+            Some(value) => {
+                if let Reference::Borrowed(slice) = value.decode(buf) {
+                    buf.extend_from_slice(slice);
+                }
+                Some(true)
+            }
+            None => Some(false),
+        }
+    }
+
+    /// Re-encodes the querystring in a canonical form, ex. for cache keys or signatures.
+    ///
+    /// The direct values of a key are written first, followed by its subkeys wrapped in
+    /// (unencoded) brackets, ex. `foo=1&foo[bar]=2`. Subkeys are sorted the same way as keys and
+    /// the other rules are the same as
+    /// [`DuplicateQS::to_canonical_string`](crate::DuplicateQS::to_canonical_string).
+    pub fn to_canonical_string(&self) -> String {
+        let mut output = String::new();
+        self.write_canonical(None, &mut output);
+        output
+    }
+
+    fn write_canonical(&self, prefix: Option<&str>, output: &mut String) {
+        let mut scratch = Vec::new();
+
+        for (key, pairs) in &self.pairs {
+            let key = match prefix {
+                Some(prefix) => format!("{}[{}]", prefix, encode(key)),
+                None => encode(key),
+            };
+
+            for pair in pairs.iter().filter(|p| !p.0.has_subkey()) {
+                let value = pair.1.map(|v| v.decode(&mut scratch));
+                write_pair(output, &key, value.as_deref());
+            }
+
+            if pairs.iter().any(|p| p.0.has_subkey()) {
+                Self::from_pairs(pairs.iter().copied(), &self.config)
+                    .write_canonical(Some(&key), output);
+            }
+        }
+    }
+
+    /// Removes the keys not matching the predicate, along with all their values.
+    ///
+    /// The predicate gets the percent decoded keys, ex. to drop the keys starting with `_`
+    /// before deserializing.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        self.pairs.retain(|key, _| f(key));
+    }
+
+    /// Removes a key along with its subkeys, and returns its direct values like `values`.
+    ///
+    /// It returns `None` if the **key doesn't exist**, ex. `token` is removed from
+    /// `token=a&token[exp]=1` with `Some(vec![Some("a")])`.
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<Option<Cow<'a, [u8]>>>> {
+        let mut scratch = Vec::new();
+
+        Some(
+            self.pairs
+                .remove(key)?
+                .iter()
+                .filter(|p| !p.0.has_subkey())
+                .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
+                .collect(),
+        )
+    }
+
+    /// Returns the keys starting with `prefix`, with the prefix removed from them.
+    ///
+    /// The prefix is matched against the percent decoded keys, ex. `t1.` in `t1.user[name]=a&t2.user[name]=b` gives
+    /// a querystring with `user[name]` assigned to `a`.
+    pub fn with_prefix(&self, prefix: &[u8]) -> Self {
+        let pairs = self
+            .pairs
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, pairs)| {
+                let key = match key {
+                    Cow::Borrowed(key) => Cow::Borrowed(&key[prefix.len()..]),
+                    Cow::Owned(key) => Cow::Owned(key[prefix.len()..].to_vec()),
+                };
+                (key, pairs.clone())
+            })
+            .collect();
+
+        Self {
+            pairs,
+            config: self.config,
+        }
+    }
+
+    /// Combines two querystrings, the values of `other` are appended after the values of
+    /// the same key in `self`, ex. `a[]=1` merged with `a[]=2` gives `a[]=1&a[]=2`.
+    ///
+    /// Values are not deduplicated, a value present in both querystrings is kept twice.
+    pub fn merge(mut self, other: Self) -> Self {
+        // The pairs of `other` come after all the pairs of `self`
+        let offset = self
+            .pairs
+            .values()
+            .flatten()
+            .map(|p| p.2 + 1)
+            .max()
+            .unwrap_or(0);
+
+        for (key, pairs) in other.pairs {
+            let pairs = pairs.into_iter().map(|p| Pair(p.0, p.1, p.2 + offset));
+            self.pairs.entry(key).or_default().extend(pairs);
+        }
+        self
+    }
+}
+
+/// Prints the decoded keys with their direct values, followed by their subkeys if there are any,
+/// ex. `{"foo": [Some("bar"), {"baz": [Some("qux")]}]}`
+impl<'a> fmt::Debug for BracketsQS<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.pairs
+                    .iter()
+                    .map(|(key, pairs)| (DebugSlice(key), DebugPairs(pairs, &self.config))),
+            )
+            .finish()
+    }
+}
+
+/// Formats the pairs of a single key for `BracketsQS`'s `Debug` implementation
+struct DebugPairs<'a, 'b>(&'b [Pair<'a>], &'b Config);
+
+impl<'a, 'b> fmt::Debug for DebugPairs<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut scratch = Vec::new();
+        let mut list = f.debug_list();
+
+        for pair in self.0.iter().filter(|p| !p.0.has_subkey()) {
+            let value = pair.1.map(|v| v.decode(&mut scratch).into_cow());
+            list.entry(&value.as_deref().map(DebugSlice));
+        }
+
+        if self.0.iter().any(|p| p.0.has_subkey()) {
+            let subpairs = self.0.iter().copied().filter(|p| p.0.has_subkey());
+            list.entry(&BracketsQS::from_pairs(subpairs, self.1));
+        }
+
+        list.finish()
+    }
 }
 
 #[cfg(feature = "serde")]
 mod de {
-    use _serde::{de, forward_to_deserialize_any, Deserialize, Deserializer};
+    use std::{borrow::Cow, collections::BTreeMap};
 
+    use _serde::{de, de::DeserializeOwned, forward_to_deserialize_any, Deserialize, Deserializer};
+
+    use crate::config::{Config, SeqOrder};
     use crate::de::{
-        Error, ErrorKind, QSDeserializer,
-        __implementors::{DecodedSlice, IntoDeserializer, RawSlice},
+        __implementors::{DecodedSlice, IntoDeserializer, RawSlice, Scratch, Value as _, RAW_STR},
+        Error, ErrorKind, ExtraPair, QSDeserializer,
     };
+    use crate::decode::Decode;
 
-    use super::{BracketsQS, Pair};
+    use super::{BracketsQS, Key, Pair, Value};
 
     pub struct Pairs<'a>(Vec<Pair<'a>>);
 
     impl<'a> BracketsQS<'a> {
-        /// Deserialize the parsed slice into T
+        /// Creates the parser from already percent decoded pairs, which are never decoded again
+        ///
+        /// The brackets of the keys are always subkeys, as there are no encoded brackets left.
+        pub(crate) fn from_decoded_pairs<I>(iter: I, config: &Config) -> Self
+        where
+            I: Iterator<Item = (&'a [u8], &'a [u8])>,
+        {
+            let mut pairs: BTreeMap<Cow<'a, [u8]>, Vec<Pair<'a>>> = BTreeMap::new();
+
+            for (position, (key, value)) in iter.enumerate() {
+                let key = match key.iter().position(|c| *c == b'[') {
+                    Some(index) => {
+                        Key(&key[..index], Some(&key[(index + 1)..]), Decode::NONE, true)
+                    }
+                    None => Key(key, None, Decode::NONE, true),
+                };
+
+                pairs.entry(Cow::Borrowed(key.0)).or_default().push(Pair(
+                    key,
+                    Some(Value(value, Decode::NONE)),
+                    position,
+                ));
+            }
+
+            Self {
+                pairs,
+                config: *config,
+            }
+        }
+
+        /// Deserialize the parsed slice into T, with the config it was parsed with
         pub fn deserialize<T: Deserialize<'a>>(self) -> Result<T, Error> {
-            T::deserialize(QSDeserializer::new(self.into_iter()))
+            let config = self.config;
+            T::deserialize(QSDeserializer::new(
+                self.into_iter(),
+                config,
+                &mut Scratch::default(),
+            ))
+        }
+
+        /// Deserialize the last direct value assigned to a key into T, without a struct for
+        /// the whole querystring
+        ///
+        /// It returns `None` in the same cases as `raw_value`, a key without a value is
+        /// deserialized like an empty value.
+        pub fn get_as<T: DeserializeOwned>(&self, key: &[u8]) -> Option<Result<T, Error>> {
+            let value = self.raw_value(key)?.map(RawSlice);
+            let mut scratch = Scratch::default();
+            Some(T::deserialize(
+                value.into_deserializer(&mut scratch, &self.config),
+            ))
         }
 
+        /// The keys are visited in the order of their first pair in the querystring, so maps
+        /// like `IndexMap` keep the submission order
         pub(crate) fn into_iter(self) -> impl Iterator<Item = (DecodedSlice<'a>, Pairs<'a>)> {
-            self.pairs
+            let mut pairs: Vec<_> = self.pairs.into_iter().collect();
+            pairs.sort_by_key(|(_, pairs)| pairs[0].2);
+
+            pairs
+                .into_iter()
+                .map(|(key, pairs)| (DecodedSlice(key), Pairs(pairs)))
+        }
+
+        /// Like `into_iter`, but the fields of a struct come first in their declared order
+        ///
+        /// Adjacently tagged enums need their tag before their content, or the content is
+        /// buffered and loses its nesting and types.
+        pub(crate) fn into_struct_iter(
+            mut self,
+            fields: &[&str],
+        ) -> impl Iterator<Item = (DecodedSlice<'a>, Pairs<'a>)> {
+            let ordered: Vec<_> = fields
+                .iter()
+                .filter_map(|field| self.pairs.remove_entry(field.as_bytes()))
+                .collect();
+
+            ordered
                 .into_iter()
+                .chain(self.pairs)
                 .map(|(key, pairs)| (DecodedSlice(key), Pairs(pairs)))
         }
+
+        /// Removes the pairs whose keys are not fields, and returns them as they were parsed
+        pub(crate) fn take_extra<F>(&mut self, mut is_field: F) -> Vec<ExtraPair<'a>>
+        where
+            F: FnMut(&[u8]) -> bool,
+        {
+            let (pairs, extra): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(&mut self.pairs)
+                .into_iter()
+                .partition(|(key, _)| is_field(key));
+            self.pairs = pairs;
+
+            extra
+                .into_iter()
+                .flat_map(|(key, pairs)| {
+                    pairs.into_iter().map(move |pair| ExtraPair {
+                        position: pair.2,
+                        key: key.clone(),
+                        raw_key: (pair.0.slice(), pair.0 .1),
+                        raw_value: pair.1.map(|v| v.slice()),
+                    })
+                })
+                .collect()
+        }
     }
 
     impl<'a, 's> IntoDeserializer<'a, 's> for Pairs<'a> {
         type Deserializer = PairsDeserializer<'a, 's>;
 
-        fn into_deserializer(self, scratch: &'s mut Vec<u8>) -> Self::Deserializer {
-            PairsDeserializer(self.0, scratch)
+        fn into_deserializer(
+            self,
+            scratch: &'s mut Scratch,
+            config: &'s Config,
+        ) -> Self::Deserializer {
+            PairsDeserializer(self.0, scratch, config)
         }
     }
 
-    pub struct PairsDeserializer<'a, 's>(Vec<Pair<'a>>, &'s mut Vec<u8>);
+    pub struct PairsDeserializer<'a, 's>(Vec<Pair<'a>>, &'s mut Scratch, &'s Config);
+
+    /// An element of a sequence, either a direct value(`key[0]=value`), the pairs
+    /// sharing the same index and having more subkeys(`key[0][field]=value`), or a missing
+    /// index filled when `Config::fill_seq_gaps` is set
+    enum SeqElement<'a> {
+        Value(RawSlice<'a>),
+        Pairs(Vec<Pair<'a>>),
+        Missing,
+    }
 
     impl<'a, 's> PairsDeserializer<'a, 's> {
+        /// The last direct value of the key, ex. `1` for `key=1&key[sub]=2`, as the subkeys
+        /// are only used by maps and structs. Without direct values, the last value is used.
+        fn last_value(&self) -> &'a [u8] {
+            self.0
+                .iter()
+                .rfind(|pair| !pair.0.has_subkey())
+                .or_else(|| self.0.last())
+                .and_then(|pair| pair.1)
+                .unwrap_or_default()
+                .slice()
+        }
+
         #[inline]
-        fn to_seq_values(&mut self) -> Result<Vec<(usize, RawSlice<'a>)>, Error> {
-            let mut values = std::mem::take(&mut self.0)
-                .into_iter()
-                .map(|pair| {
-                    let index = match pair.0.subkey() {
-                        Some(subkey) if !subkey.is_empty() => lexical::parse::<usize, _>(subkey.0)
-                            .map_err(|e| {
-                                Error::new(ErrorKind::InvalidNumber)
-                                    .message(format!("invalid index: {}", e))
-                            })?,
-                        _ => 0,
-                    };
-                    Ok((index, RawSlice(pair.1.unwrap_or_default().slice())))
-                })
-                .collect::<Result<Vec<(usize, RawSlice)>, Error>>()?;
+        fn take_seq_values(&mut self) -> Result<Vec<(usize, SeqElement<'a>)>, Error> {
+            let mut values: Vec<(usize, SeqElement<'a>)> = Vec::new();
+            // Position of the nested elements in `values` by their index
+            let mut nested: BTreeMap<usize, usize> = BTreeMap::new();
+
+            // The pairs are kept, so a failed sequence can be visited as entries
+            for pair in self.0.iter().copied() {
+                let subkey = pair.0.subkey();
+                let index = match subkey {
+                    // The index might be percent encoded, ex. `key[%30]`
+                    Some(subkey) if !subkey.is_empty() => {
+                        let decoded = subkey.decode(self.1);
+                        lexical::parse::<usize, _>(&*decoded)
+                            .map_err(|_| non_numeric_index(&decoded))?
+                    }
+                    _ => 0,
+                };
+
+                match subkey {
+                    Some(subkey) if subkey.has_subkey() => {
+                        let pair = Pair::new(subkey, pair.1, pair.2);
+                        match nested.get(&index) {
+                            Some(&position) => match &mut values[position].1 {
+                                SeqElement::Pairs(pairs) => pairs.push(pair),
+                                _ => unreachable!(),
+                            },
+                            None => {
+                                nested.insert(index, values.len());
+                                values.push((index, SeqElement::Pairs(vec![pair])));
+                            }
+                        }
+                    }
+                    _ => values.push((
+                        index,
+                        SeqElement::Value(RawSlice(pair.1.unwrap_or_default().slice())),
+                    )),
+                }
+            }
+
+            if self.2.seq_order == SeqOrder::ByIndex {
+                values.sort_by_key(|item| item.0);
 
-            values.sort_by_key(|item| item.0);
+                if let Some(limit) = self.2.fill_seq_gaps {
+                    return fill_gaps(values, limit);
+                }
+            }
             Ok(values)
         }
     }
 
+    fn non_numeric_index(index: &[u8]) -> Error {
+        Error::new(ErrorKind::InvalidNumber)
+            .message(format!(
+                "expected numeric index, found '{}'",
+                String::from_utf8_lossy(index)
+            ))
+            .value(index)
+    }
+
+    /// Fills the missing indexes of the sorted values, ex. `1` and `2` for `key[0]=a&key[3]=b`
+    fn fill_gaps(
+        values: Vec<(usize, SeqElement<'_>)>,
+        limit: usize,
+    ) -> Result<Vec<(usize, SeqElement<'_>)>, Error> {
+        match values.last() {
+            Some((index, _)) if *index >= limit => {
+                return Err(Error::new(ErrorKind::InvalidLength).message(format!(
+                    "found the index {} in a sequence filled up to {} values",
+                    index, limit
+                )))
+            }
+            None => return Ok(values),
+            _ => {}
+        }
+
+        let mut filled = Vec::with_capacity(values.last().map_or(0, |v| v.0 + 1));
+        for (index, value) in values {
+            while filled.len() < index {
+                filled.push((filled.len(), SeqElement::Missing));
+            }
+            filled.push((index, value));
+        }
+        Ok(filled)
+    }
+
     macro_rules! forware_to_slice_deserializer {
         ($($method:ident ,)*) => {
             $(
@@ -392,9 +985,8 @@ mod de {
                 where
                     V: de::Visitor<'de>,
                 {
-                    let scratch = self.1;
-                    let value = self.0.last().unwrap().1.unwrap_or_default().slice();
-                    RawSlice(value).into_deserializer(scratch).$method(visitor)
+                    let value = self.last_value();
+                    RawSlice(value).into_deserializer(self.1, self.2).$method(visitor)
                 }
             )*
         };
@@ -403,13 +995,35 @@ mod de {
     impl<'de, 's> de::Deserializer<'de> for PairsDeserializer<'de, 's> {
         type Error = crate::de::Error;
 
+        /// Subkeys which are not indexes, ex. `key[x]=1&key[y]=2`, are visited as a sequence of
+        /// `(subkey, value)` tuples in their order in the querystring
         fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: de::Visitor<'de>,
         {
+            let values = match self.take_seq_values() {
+                Ok(values) => values,
+                Err(error) if error.kind == ErrorKind::InvalidNumber => {
+                    let entries: Vec<_> = BracketsQS::from_pairs(self.0.into_iter(), self.2)
+                        .into_iter()
+                        .collect();
+                    self.2.check_seq_len(entries.len())?;
+
+                    return visitor.visit_seq(PairsEntriesDeserializer {
+                        iter: entries.into_iter(),
+                        scratch: self.1,
+                        config: self.2,
+                        index: error.value,
+                    });
+                }
+                Err(error) => return Err(error),
+            };
+            self.2.check_seq_len(values.len())?;
+
             visitor.visit_seq(PairsSeqDeserializer(
-                self.to_seq_values()?.into_iter().map(|v| v.1),
+                values.into_iter().map(|v| v.1),
                 self.1,
+                self.2,
             ))
         }
 
@@ -417,12 +1031,13 @@ mod de {
         where
             V: de::Visitor<'de>,
         {
-            let values = self.to_seq_values()?;
+            let values = self.take_seq_values()?;
 
             if values.len() == len {
                 visitor.visit_seq(PairsSeqDeserializer(
                     values.into_iter().map(|v| v.1),
                     self.1,
+                    self.2,
                 ))
             } else {
                 Err(Error::new(ErrorKind::InvalidLength))
@@ -443,13 +1058,20 @@ mod de {
 
         fn deserialize_newtype_struct<V>(
             self,
-            _: &'static str,
+            name: &'static str,
             visitor: V,
         ) -> Result<V::Value, Self::Error>
         where
             V: de::Visitor<'de>,
         {
-            visitor.visit_newtype_struct(self)
+            if name == RAW_STR {
+                let value = self.last_value();
+                RawSlice(value)
+                    .into_deserializer(self.1, self.2)
+                    .deserialize_newtype_struct(name, visitor)
+            } else {
+                visitor.visit_newtype_struct(self)
+            }
         }
 
         fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -457,8 +1079,9 @@ mod de {
             V: de::Visitor<'de>,
         {
             visitor.visit_map(PairsMapDeserializer {
-                iter: BracketsQS::from_pairs(self.0.into_iter()).into_iter(),
+                iter: BracketsQS::from_pairs(self.0.into_iter(), self.2).into_iter(),
                 scratch: self.1,
+                config: self.2,
                 value: None,
             })
         }
@@ -466,47 +1089,88 @@ mod de {
         fn deserialize_struct<V>(
             self,
             _: &'static str,
-            _: &'static [&'static str],
+            fields: &'static [&'static str],
             visitor: V,
         ) -> Result<V::Value, Self::Error>
         where
             V: de::Visitor<'de>,
         {
-            self.deserialize_map(visitor)
+            visitor.visit_map(PairsMapDeserializer {
+                iter: BracketsQS::from_pairs(self.0.into_iter(), self.2).into_struct_iter(fields),
+                scratch: self.1,
+                config: self.2,
+                value: None,
+            })
         }
 
         fn deserialize_enum<V>(
             self,
-            _: &'static str,
-            _: &'static [&'static str],
+            name: &'static str,
+            variants: &'static [&'static str],
             visitor: V,
         ) -> Result<V::Value, Self::Error>
         where
             V: de::Visitor<'de>,
         {
-            visitor.visit_enum(self)
+            match self.0.last() {
+                // Unit variants are matched by the value deserializer, ignoring the case
+                Some(pair) if self.2.case_insensitive_enums && !pair.0.has_subkey() => {
+                    RawSlice(pair.1.unwrap_or_default().slice())
+                        .into_deserializer(self.1, self.2)
+                        .deserialize_enum(name, variants, visitor)
+                }
+                _ => visitor.visit_enum(self),
+            }
         }
 
         fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: de::Visitor<'de>,
         {
-            if self.0.is_empty() {
-                visitor.visit_none()
-            } else if self.0.len() == 1 && !self.0[0].0.has_subkey() && self.0[0].1.is_none() {
+            let single_value = match self.0.as_slice() {
+                [pair] if !pair.0.has_subkey() => Some(pair.1.map(|v| RawSlice(v.slice()))),
+                _ => None,
+            };
+
+            if self.0.is_empty()
+                || matches!(single_value, Some(value) if value.is_none() || value.is_null(self.1, self.2))
+            {
                 visitor.visit_none()
             } else {
                 visitor.visit_some(self)
             }
         }
 
+        /// Keys with subkeys are visited as maps, ex. the content of adjacently tagged enums,
+        /// otherwise the last value is visited as a string
+        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            if self.0.iter().any(|pair| pair.0.has_subkey()) {
+                self.deserialize_map(visitor)
+            } else {
+                let value = self.last_value();
+                RawSlice(value)
+                    .into_deserializer(self.1, self.2)
+                    .deserialize_any(visitor)
+            }
+        }
+
+        /// Ignored values and their whole subtree are skipped without looking at them
+        fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            visitor.visit_unit()
+        }
+
         forware_to_slice_deserializer! {
             deserialize_i8, deserialize_i16, deserialize_i32, deserialize_i64, deserialize_i128,
             deserialize_u8, deserialize_u16, deserialize_u32, deserialize_u64, deserialize_u128,
             deserialize_f32, deserialize_f64,
             deserialize_char, deserialize_str, deserialize_string, deserialize_identifier,
             deserialize_bool, deserialize_bytes, deserialize_byte_buf, deserialize_unit,
-            deserialize_any, deserialize_ignored_any,
         }
 
         forward_to_deserialize_any! {
@@ -526,20 +1190,21 @@ mod de {
             let last_pair = self.0.last().expect("Values iterator can't be empty");
             match last_pair.0.subkey() {
                 Some(subkey) => {
-                    let mut scratch = self.1;
-                    let pairs = BracketsQS::from_pairs(self.0.into_iter())
+                    let (scratch, config) = (self.1, self.2);
+                    let pairs = BracketsQS::from_pairs(self.0.into_iter(), config)
                         .pairs
                         .remove(subkey.0)
                         .unwrap();
-                    seed.deserialize(RawSlice(subkey.0).into_deserializer(&mut scratch))
-                        .map(move |v| (v, Self(pairs, scratch)))
+                    seed.deserialize(RawSlice(subkey.0).into_deserializer(scratch, config))
+                        .map(move |v| (v, Self(pairs, scratch, config)))
                 }
                 None => {
-                    let mut scratch = self.1;
+                    let (scratch, config) = (self.1, self.2);
                     seed.deserialize(
-                        RawSlice(last_pair.1.unwrap_or_default().0).into_deserializer(&mut scratch),
+                        RawSlice(last_pair.1.unwrap_or_default().0)
+                            .into_deserializer(scratch, config),
                     )
-                    .map(move |v| (v, PairsDeserializer(Vec::new(), scratch)))
+                    .map(move |v| (v, PairsDeserializer(Vec::new(), scratch, config)))
                 }
             }
         }
@@ -549,7 +1214,7 @@ mod de {
         type Error = Error;
 
         fn unit_variant(self) -> Result<(), Self::Error> {
-            if self.0.len() == 0 {
+            if self.0.is_empty() {
                 Ok(())
             } else {
                 Err(Error::new(ErrorKind::Other)
@@ -583,11 +1248,48 @@ mod de {
         }
     }
 
-    struct PairsSeqDeserializer<'s, I>(I, &'s mut Vec<u8>);
+    struct PairsSeqDeserializer<'s, I>(I, &'s mut Scratch, &'s Config);
 
     impl<'de, 's, I> de::SeqAccess<'de> for PairsSeqDeserializer<'s, I>
     where
-        I: Iterator<Item = RawSlice<'de>>,
+        I: Iterator<Item = SeqElement<'de>>,
+    {
+        type Error = Error;
+
+        fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+        where
+            T: de::DeserializeSeed<'de>,
+        {
+            match self.0.next() {
+                Some(SeqElement::Value(v)) => seed
+                    .deserialize(v.into_deserializer(self.1, self.2))
+                    .map(Some),
+                Some(SeqElement::Pairs(pairs)) => seed
+                    .deserialize(PairsDeserializer(pairs, self.1, self.2))
+                    .map(Some),
+                Some(SeqElement::Missing) => seed
+                    .deserialize(None::<RawSlice>.into_deserializer(self.1, self.2))
+                    .map(Some),
+                None => Ok(None),
+            }
+        }
+    }
+
+    /// The subkeys of a key visited as a sequence of `(subkey, value)` tuples, `index` is the
+    /// first subkey which is not an index, reported when an entry is not deserialized as a tuple
+    struct PairsEntriesDeserializer<'de, 's, I>
+    where
+        I: Iterator<Item = (DecodedSlice<'de>, Pairs<'de>)>,
+    {
+        iter: I,
+        scratch: &'s mut Scratch,
+        config: &'s Config,
+        index: String,
+    }
+
+    impl<'de, 's, I> de::SeqAccess<'de> for PairsEntriesDeserializer<'de, 's, I>
+    where
+        I: Iterator<Item = (DecodedSlice<'de>, Pairs<'de>)>,
     {
         type Error = Error;
 
@@ -595,12 +1297,111 @@ mod de {
         where
             T: de::DeserializeSeed<'de>,
         {
-            if let Some(v) = self.0.next() {
-                seed.deserialize(v.into_deserializer(self.1)).map(Some)
+            match self.iter.next() {
+                Some((key, value)) => seed
+                    .deserialize(EntryDeserializer {
+                        key: Some(key),
+                        value: Some(value),
+                        scratch: self.scratch,
+                        config: self.config,
+                        index: &self.index,
+                    })
+                    .map(Some),
+                None => Ok(None),
+            }
+        }
+
+        fn size_hint(&self) -> Option<usize> {
+            self.iter.size_hint().1
+        }
+    }
+
+    /// A `(subkey, value)` tuple of `PairsEntriesDeserializer`
+    struct EntryDeserializer<'de, 's, 'i> {
+        key: Option<DecodedSlice<'de>>,
+        value: Option<Pairs<'de>>,
+        scratch: &'s mut Scratch,
+        config: &'s Config,
+        index: &'i str,
+    }
+
+    impl<'de, 's, 'i> de::Deserializer<'de> for EntryDeserializer<'de, 's, 'i> {
+        type Error = Error;
+
+        /// Only tuples and sequences are entries, other types expected a numeric index
+        fn deserialize_any<V>(self, _: V) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            Err(non_numeric_index(self.index.as_bytes()))
+        }
+
+        fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            visitor.visit_seq(self)
+        }
+
+        fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            if len == 2 {
+                visitor.visit_seq(self)
+            } else {
+                Err(Error::new(ErrorKind::InvalidLength)
+                    .message(format!("expected a tuple of 2 values, found {}", len)))
+            }
+        }
+
+        fn deserialize_tuple_struct<V>(
+            self,
+            _: &'static str,
+            len: usize,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            self.deserialize_tuple(len, visitor)
+        }
+
+        fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            visitor.visit_unit()
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct map struct enum
+            identifier
+        }
+    }
+
+    impl<'de, 's, 'i> de::SeqAccess<'de> for EntryDeserializer<'de, 's, 'i> {
+        type Error = Error;
+
+        fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+        where
+            T: de::DeserializeSeed<'de>,
+        {
+            if let Some(key) = self.key.take() {
+                seed.deserialize(key.into_deserializer(self.scratch, self.config))
+                    .map(Some)
+            } else if let Some(value) = self.value.take() {
+                seed.deserialize(value.into_deserializer(self.scratch, self.config))
+                    .map(Some)
             } else {
                 Ok(None)
             }
         }
+
+        fn size_hint(&self) -> Option<usize> {
+            Some(self.key.is_some() as usize + self.value.is_some() as usize)
+        }
     }
 
     struct PairsMapDeserializer<'de, 's, I>
@@ -608,7 +1409,8 @@ mod de {
         I: Iterator<Item = (DecodedSlice<'de>, Pairs<'de>)>,
     {
         iter: I,
-        scratch: &'s mut Vec<u8>,
+        scratch: &'s mut Scratch,
+        config: &'s Config,
         value: Option<Pairs<'de>>,
     }
 
@@ -625,7 +1427,7 @@ mod de {
             if let Some((k, v)) = self.iter.next() {
                 self.value = Some(v);
 
-                seed.deserialize(k.into_deserializer(self.scratch))
+                seed.deserialize(k.into_deserializer(self.scratch, self.config))
                     .map(Some)
             } else {
                 Ok(None)
@@ -640,7 +1442,7 @@ mod de {
                 self.value
                     .take()
                     .expect("next_value is called before next_key")
-                    .into_deserializer(self.scratch),
+                    .into_deserializer(self.scratch, self.config),
             )
         }
 
@@ -655,6 +1457,8 @@ mod tests {
     use std::borrow::Cow;
 
     use super::BracketsQS;
+    use crate::config::Config;
+    use crate::error::ErrorKind;
 
     #[test]
     fn parse_pair() {
@@ -755,7 +1559,26 @@ mod tests {
         assert_eq!(
             foo_values.values(b"foobar"),
             Some(vec![Some("qux".as_bytes().into())])
-        )
+        );
+
+        assert_eq!(
+            parser.subkeys(b"foo"),
+            Some(vec![
+                Cow::Borrowed("bar".as_bytes()),
+                Cow::Borrowed("foobar".as_bytes())
+            ])
+        );
+        assert_eq!(parser.subkeys(b"baz"), None);
+
+        let parser = BracketsQS::parse(b"foo[b%61r][baz]=1&foo[qux]=2&foo[qux]=3&bar=4");
+        assert_eq!(
+            parser.subkeys(b"foo"),
+            Some(vec![
+                Cow::Borrowed("bar".as_bytes()),
+                Cow::Borrowed("qux".as_bytes())
+            ])
+        );
+        assert_eq!(parser.subkeys(b"bar"), Some(vec![]));
     }
 
     #[test]
@@ -789,4 +1612,301 @@ mod tests {
             Some(vec![Some("qux".as_bytes().into())])
         )
     }
+
+    #[test]
+    fn parse_nested_path() {
+        let parser = BracketsQS::parse(b"a[b][c]=v&a[b][c]=w&a[b]=x&a[%62][d][]=y&a[b][e]&f=z");
+
+        assert_eq!(
+            parser.get_nested(&[b"a", b"b", b"c"]),
+            Some(Some("w".as_bytes().into()))
+        );
+        assert_eq!(
+            parser.get_nested(&[b"a", b"b"]),
+            Some(Some("x".as_bytes().into()))
+        );
+        assert_eq!(
+            parser.get_nested(&[b"a", b"b", b"d", b""]),
+            Some(Some("y".as_bytes().into()))
+        );
+        assert_eq!(parser.get_nested(&[b"a", b"b", b"e"]), Some(None));
+        assert_eq!(parser.get_nested(&[b"f"]), parser.value(b"f"));
+
+        assert_eq!(parser.get_nested(&[b"a"]), None);
+        assert_eq!(parser.get_nested(&[b"a", b"c"]), None);
+        assert_eq!(parser.get_nested(&[b"f", b"b"]), None);
+        assert_eq!(parser.get_nested(&[]), None);
+    }
+
+    #[test]
+    fn values_count() {
+        let parser = BracketsQS::parse(b"foo=1&foo[bar]=2&foo[]=3&foo&baz=4");
+
+        assert_eq!(parser.values_count(b"foo"), 4);
+        assert_eq!(parser.leaf_values_count(b"foo"), 2);
+        assert_eq!(parser.values_count(b"baz"), 1);
+        assert_eq!(parser.leaf_values_count(b"baz"), 1);
+        assert_eq!(parser.values_count(b"qux"), 0);
+        assert_eq!(parser.leaf_values_count(b"qux"), 0);
+    }
+
+    #[test]
+    fn raw_value() {
+        let parser = BracketsQS::parse(b"foo=b%20r&foo[sub]=baz&foo+bar=1&qux");
+
+        assert_eq!(parser.raw_value(b"foo"), Some(Some("b%20r".as_bytes())));
+        assert_eq!(parser.raw_value(b"foo bar"), Some(Some("1".as_bytes())));
+        assert_eq!(parser.raw_value(b"qux"), Some(None));
+        assert_eq!(parser.raw_value(b"baz"), None);
+
+        // Only subkeys
+        let parser = BracketsQS::parse(b"foo[sub]=baz");
+        assert_eq!(parser.raw_value(b"foo"), None);
+    }
+
+    #[test]
+    fn needs_decoding() {
+        let parser = BracketsQS::parse(b"foo=b%20r&foo[sub]=b%20z&bar=baz&qux");
+
+        assert_eq!(parser.needs_decoding(b"foo"), Some(true));
+        assert_eq!(
+            parser.value(b"foo"),
+            Some(Some(Cow::Owned(b"b r".to_vec())))
+        );
+        assert_eq!(parser.needs_decoding(b"bar"), Some(false));
+        assert_eq!(parser.value(b"bar"), Some(Some(Cow::Borrowed(&b"baz"[..]))));
+        assert_eq!(parser.needs_decoding(b"qux"), Some(false));
+        assert_eq!(parser.needs_decoding(b"baz"), None);
+    }
+
+    #[test]
+    fn decode_into() {
+        let parser = BracketsQS::parse(b"foo=b%20r&foo[sub]=b%20z&bar=baz&qux");
+        let mut buf = Vec::with_capacity(16);
+        let capacity = buf.capacity();
+
+        assert_eq!(parser.decode_into(b"foo", &mut buf), Some(true));
+        assert_eq!(buf, b"b r");
+        assert_eq!(parser.decode_into(b"bar", &mut buf), Some(true));
+        assert_eq!(buf, b"baz");
+        assert_eq!(parser.decode_into(b"qux", &mut buf), Some(false));
+        assert!(buf.is_empty());
+        assert_eq!(parser.decode_into(b"baz", &mut buf), None);
+
+        // The buffer is reused
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn parse_max_key_len() {
+        let config = Config::default().max_key_len(7);
+
+        // The subkeys and encoded brackets are part of the key
+        let parser = BracketsQS::try_parse(b"a[b][c]=1&abcdefg=2&a%5Bb]=3", &config).unwrap();
+        assert_eq!(parser.values_count(b"a"), 2);
+
+        let error = BracketsQS::try_parse(b"a=1&a[b][cd]=2", &config).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidLength);
+        assert_eq!(error.value, "a");
+        assert!(BracketsQS::try_parse(b"a%5Bb%5D=1", &config).is_err());
+    }
+
+    #[test]
+    fn parse_strict() {
+        let config = Config::default().max_depth(2);
+        assert!(BracketsQS::try_parse(b"a=1&b[0]=1&c[d][e]=1", &config).is_ok());
+        assert_eq!(
+            BracketsQS::try_parse(b"a[b][c][d]=1", &config)
+                .err()
+                .map(|e| e.kind),
+            Some(ErrorKind::InvalidKey)
+        );
+        assert!(BracketsQS::try_parse(b"a%5Bb%5D%5Bc%5D%5Bd%5D=1", &config).is_err());
+
+        let config = Config::default().max_params(1);
+        assert!(BracketsQS::try_parse(b"a[b]=1&", &config).is_ok());
+        assert_eq!(
+            BracketsQS::try_parse(b"a[b]=1&a[c]", &config)
+                .err()
+                .map(|e| e.kind),
+            Some(ErrorKind::InvalidLength)
+        );
+
+        let config = Config::default().max_values_per_key(2);
+        assert!(BracketsQS::try_parse(b"a[0]=1&b=1&a[1]=2", &config).is_ok());
+        assert_eq!(
+            BracketsQS::try_parse(b"a[0]=1&a[1]=2&a[2]=3", &config)
+                .err()
+                .map(|e| e.kind),
+            Some(ErrorKind::InvalidLength)
+        );
+
+        // Subkeys are checked as well
+        let config = Config::default().reject_malformed_escapes(true);
+        assert!(BracketsQS::try_parse(b"a%5Bb%5D=%20", &config).is_ok());
+        assert_eq!(
+            BracketsQS::try_parse(b"a[b%]=1", &config)
+                .err()
+                .map(|e| e.kind),
+            Some(ErrorKind::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn debug_format() {
+        let parser = BracketsQS::parse(b"foo[bar]=b%20z&foo=bar&foo[qux][]=1&baz&%E2%9C%93=%FF");
+
+        assert_eq!(
+            format!("{:?}", parser),
+            r#"{"baz": [None], "foo": [Some("bar"), {"bar": [Some("b z")], "qux": [{"": [Some("1")]}]}], "✓": [Some("�")]}"#
+        );
+    }
+
+    #[test]
+    fn parse_empty_segments() {
+        let config = Config::default().skip_empty_segments(true);
+        let parser = BracketsQS::try_parse(b"a[0]=1&&&a[1]=2&", &config).unwrap();
+        assert_eq!(parser.keys(), vec![&Cow::Borrowed(b"a")]);
+        assert_eq!(parser.values_count(b"a"), 2);
+    }
+
+    #[test]
+    fn encoded_brackets_parity() {
+        // The same path written with literal, encoded and mixed brackets, in both hex cases
+        let parser = BracketsQS::parse(
+            b"foo=0&foo%5Bbar%5D[baz]=1&foo[bar]%5Bqux%5D=2&foo[bar][baz]=3&foo%5bbar%5d%5bbaz%5d=4",
+        );
+
+        assert_eq!(
+            parser.values(b"foo"),
+            Some(vec![Some("0".as_bytes().into())])
+        );
+        assert_eq!(parser.raw_value(b"foo"), Some(Some(&b"0"[..])));
+        assert_eq!(parser.values_count(b"foo"), 5);
+        assert_eq!(parser.leaf_values_count(b"foo"), 1);
+        assert_eq!(
+            parser.subkeys(b"foo"),
+            Some(vec![Cow::Borrowed(&b"bar"[..])])
+        );
+        assert_eq!(
+            parser.get_nested(&[b"foo", b"bar", b"qux"]),
+            Some(Some("2".as_bytes().into()))
+        );
+
+        let foo = parser.sub_values(b"foo").unwrap();
+        let bar = foo.sub_values(b"bar").unwrap();
+        assert_eq!(
+            bar.values(b"baz"),
+            Some(vec![
+                Some("1".as_bytes().into()),
+                Some("3".as_bytes().into()),
+                Some("4".as_bytes().into())
+            ])
+        );
+        assert_eq!(
+            parser.to_canonical_string(),
+            "foo=0&foo[bar][baz]=1&foo[bar][baz]=3&foo[bar][baz]=4&foo[bar][qux]=2"
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let base = BracketsQS::parse(b"a[]=1&b[x]=2");
+        let overrides = BracketsQS::parse(b"a[]=3&b[y]=4");
+
+        let merged = base.merge(overrides);
+        assert_eq!(merged.values_count(b"a"), 2);
+        assert_eq!(
+            merged.get_nested(&[b"b", b"y"]),
+            Some(Some("4".as_bytes().into()))
+        );
+        assert_eq!(
+            merged.get_nested(&[b"b", b"x"]),
+            Some(Some("2".as_bytes().into()))
+        );
+    }
+
+    #[test]
+    fn with_prefix() {
+        let parser = BracketsQS::parse(b"t1.user[name]=a&t2.user[name]=b&t1.tags[]=x");
+
+        let scoped = parser.with_prefix(b"t1.");
+        assert_eq!(
+            scoped.keys(),
+            vec![&Cow::Borrowed(b"tags"), &Cow::Borrowed(b"user")]
+        );
+        assert_eq!(
+            scoped.get_nested(&[b"user", b"name"]),
+            Some(Some("a".as_bytes().into()))
+        );
+        assert_eq!(scoped.values_count(b"tags"), 1);
+    }
+
+    #[test]
+    fn retain() {
+        let mut parser = BracketsQS::parse(b"_internal[id]=1&foo[bar]=baz&_internal%5Fsecret=2");
+
+        parser.retain(|key| !key.starts_with(b"_internal"));
+        assert_eq!(parser.keys(), vec![&Cow::Borrowed(b"foo")]);
+        assert!(parser.sub_values(b"foo").is_some());
+    }
+
+    #[test]
+    fn remove() {
+        let mut parser = BracketsQS::parse(b"token=a&token[exp]=1&foo[bar]=baz");
+
+        assert_eq!(
+            parser.remove(b"token"),
+            Some(vec![Some("a".as_bytes().into())])
+        );
+        assert_eq!(parser.values(b"token"), None);
+        assert!(parser.sub_values(b"token").is_none());
+        assert_eq!(parser.keys(), vec![&Cow::Borrowed(b"foo")]);
+    }
+
+    #[test]
+    fn canonical_string() {
+        let first = BracketsQS::parse(b"foo[b%20r]=%7e&foo=1&foo[baz][]=2&foo[baz][]=3&a");
+        let second = BracketsQS::parse(b"a&foo%5Bbaz%5D[]=2&foo=%31&foo[b+r]=~&foo[baz][]=3");
+
+        assert_eq!(
+            first.to_canonical_string(),
+            "a&foo=1&foo[b+r]=~&foo[baz][]=2&foo[baz][]=3"
+        );
+        assert_eq!(first.to_canonical_string(), second.to_canonical_string());
+    }
+
+    #[test]
+    fn value_or() {
+        let parser = BracketsQS::parse(b"foo=bar&foo=b%20z&qux&empty=");
+
+        assert_eq!(parser.value_or(b"foo", b"default"), Cow::Borrowed(b"b z"));
+        assert_eq!(parser.value_or(b"empty", b"default"), Cow::Borrowed(b""));
+        assert_eq!(
+            parser.value_or(b"qux", b"default"),
+            Cow::Borrowed(b"default")
+        );
+        assert_eq!(
+            parser.value_or(b"baz", b"default"),
+            Cow::Borrowed(b"default")
+        );
+    }
+
+    #[test]
+    fn index_range() {
+        let parser = BracketsQS::parse(
+            b"items[0]=a&items[3]=d&items[1]=b&items[2]=c&items[1]=B\
+            &items[x]=y&items[2][name]=z&items=direct",
+        );
+
+        assert_eq!(
+            parser.index_range(b"items", 1..3),
+            Some(vec![
+                (1, Some(Cow::Borrowed("B".as_bytes()))),
+                (2, Some(Cow::Borrowed("c".as_bytes())))
+            ])
+        );
+        assert_eq!(parser.index_range(b"items", 10..20), Some(vec![]));
+        assert_eq!(parser.index_range(b"missing", 0..10), None);
+    }
 }
@@ -1,20 +1,24 @@
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{borrow::Cow, collections::BTreeMap, fmt};
 
-use crate::decode::{parse_bytes, Reference};
+use crate::config::{Config, ParseMode};
+use crate::decode::{decode_if, DebugSlice, Decode, Reference};
+use crate::encode::{encode, encode_into, write_pair};
+use crate::error::Error;
 
-struct Key<'a>(&'a [u8]);
+struct Key<'a>(&'a [u8], Decode);
 
 impl<'a> Key<'a> {
-    fn parse(slice: &'a [u8]) -> Self {
+    fn parse(slice: &'a [u8], config: &Config) -> Self {
         let mut index = 0;
         while index < slice.len() {
             match slice[index] {
-                b'&' | b'=' => break,
+                b'=' => break,
+                c if config.is_terminator(c) => break,
                 _ => index += 1,
             }
         }
 
-        Self(&slice[..index])
+        Self(&slice[..index], config.key_decode())
     }
 
     fn len(&self) -> usize {
@@ -22,36 +26,40 @@ impl<'a> Key<'a> {
     }
 
     fn decode<'s>(&self, scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+        decode_if(self.0, scratch, self.1)
+    }
+
+    fn slice(&self) -> &'a [u8] {
+        self.0
     }
 }
 
-struct Value<'a>(&'a [u8]);
+struct Value<'a>(&'a [u8], Decode);
 
 impl<'a> Value<'a> {
     fn decode<'s>(&self, scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+        decode_if(self.0, scratch, self.1)
     }
 }
 
 #[derive(Default)]
-struct Values<'a>(&'a [u8]);
+struct Values<'a>(&'a [u8], Decode);
 
 impl<'a> Values<'a> {
-    fn parse(slice: &'a [u8]) -> Option<Self> {
-        if *slice.get(0)? == b'&' {
+    fn parse(slice: &'a [u8], config: &Config) -> Option<Self> {
+        if config.is_terminator(*slice.first()?) {
             return None;
         }
 
         let mut index = 1;
         while index < slice.len() {
             match slice[index] {
-                b'&' => break,
+                c if config.is_terminator(c) => break,
                 _ => index += 1,
             }
         }
 
-        Some(Self(&slice[1..index]))
+        Some(Self(&slice[1..index], config.value_decode()))
     }
 
     fn len(&self) -> usize {
@@ -59,24 +67,35 @@ impl<'a> Values<'a> {
     }
 
     fn values(&self, delimiter: u8) -> impl Iterator<Item = Value<'a>> {
+        let decode = self.1;
         self.0
             .split(move |c| *c == delimiter)
-            .map(|slice| Value(slice))
+            .map(move |v| Value(v, decode))
     }
 
     fn decode_to<'s>(&self, scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+        decode_if(self.0, scratch, self.1)
+    }
+
+    fn slice(&self) -> &'a [u8] {
+        self.0
     }
 }
 
-struct Pair<'a>(Key<'a>, Option<Values<'a>>);
+/// A pair with its index in the querystring, which is only used to find the order of the pairs
+/// a struct didn't use when deserializing
+struct Pair<'a>(
+    Key<'a>,
+    Option<Values<'a>>,
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))] usize,
+);
 
 impl<'a> Pair<'a> {
-    fn parse(slice: &'a [u8]) -> Self {
-        let key = Key::parse(slice);
-        let value = Values::parse(&slice[key.len()..]);
+    fn parse(slice: &'a [u8], config: &Config, position: usize) -> Self {
+        let key = Key::parse(slice, config);
+        let value = Values::parse(&slice[key.len()..], config);
 
-        Self(key, value)
+        Self(key, value, position)
     }
 
     /// It report how many chars we should move forward after this pair, to see a new one.
@@ -126,14 +145,38 @@ pub struct DelimiterQS<'a> {
 impl<'a> DelimiterQS<'a> {
     /// Parse a slice of bytes into a `DelimiterQS`
     pub fn parse(slice: &'a [u8], delimiter: u8) -> Self {
+        Self::try_parse(slice, delimiter, &Config::default())
+            .expect("Parsing with the default config doesn't fail")
+    }
+
+    /// Parse a slice of bytes into a `DelimiterQS`, using the provided `Config`
+    ///
+    /// It returns an error if the slice violates any of the config's restrictions.
+    pub fn try_parse(slice: &'a [u8], delimiter: u8, config: &Config) -> Result<Self, Error> {
+        config.validate_for(ParseMode::Delimiter(delimiter))?;
         let mut pairs: BTreeMap<Cow<'a, [u8]>, Pair<'a>> = BTreeMap::new();
         let mut scratch = Vec::new();
 
         let mut index = 0;
+        let mut params = 0;
+        let mut position = 0;
 
         while index < slice.len() {
-            let pair = Pair::parse(&slice[index..]);
+            let pair = Pair::parse(&slice[index..], config, position);
             index += pair.skip_len();
+            position += 1;
+
+            config.check_pair(pair.0.slice(), pair.1.as_ref().map(|v| v.slice()))?;
+            config.check_key_len(pair.0.slice(), pair.0.slice().len())?;
+            if config.skips_empty_key(pair.0.slice(), pair.1.as_ref().map(|v| v.slice())) {
+                continue;
+            }
+            if !pair.0.slice().is_empty() || pair.1.is_some() {
+                params += 1;
+                config.check_params(params)?;
+            } else if config.skip_empty_segments {
+                continue;
+            }
 
             let decoded_key = pair.0.decode(&mut scratch);
 
@@ -144,7 +187,7 @@ impl<'a> DelimiterQS<'a> {
             }
         }
 
-        Self { pairs, delimiter }
+        Ok(Self { pairs, delimiter })
     }
 
     /// Returns a vector containing all the keys in querystring.
@@ -189,23 +232,134 @@ impl<'a> DelimiterQS<'a> {
                 .map(|values| values.decode_to(&mut scratch).into_cow()),
         )
     }
+
+    /// Returns the last value assigned to a key, or `default` if the **key doesn't exist** or
+    /// its last assignment **doesn't have a value**, ex `"&key&"`
+    pub fn value_or(&self, key: &'a [u8], default: &'a [u8]) -> Cow<'a, [u8]> {
+        self.value(key).flatten().unwrap_or(Cow::Borrowed(default))
+    }
+
+    /// Re-encodes the querystring in a canonical form, ex. for cache keys or signatures.
+    ///
+    /// The values of a key are re-encoded one by one and joined by the (unencoded) delimiter,
+    /// the other rules are the same as
+    /// [`DuplicateQS::to_canonical_string`](crate::DuplicateQS::to_canonical_string).
+    pub fn to_canonical_string(&self) -> String {
+        let mut output = String::new();
+        let mut scratch = Vec::new();
+
+        for (key, pair) in &self.pairs {
+            write_pair(&mut output, &encode(key), None);
+
+            if let Some(values) = &pair.1 {
+                output.push('=');
+                for (index, value) in values.values(self.delimiter).enumerate() {
+                    if index > 0 {
+                        output.push(char::from(self.delimiter));
+                    }
+                    encode_into(&value.decode(&mut scratch), &mut output);
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Removes the keys not matching the predicate, along with all their values.
+    ///
+    /// The predicate gets the percent decoded keys, ex. to drop the keys starting with `_`
+    /// before deserializing.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        self.pairs.retain(|key, _| f(key));
+    }
+
+    /// Removes a key and returns its values split by the delimiter, like `values`.
+    ///
+    /// It returns `None` if the **key doesn't exist**.
+    pub fn remove(&mut self, key: &[u8]) -> Option<Option<Vec<Cow<'a, [u8]>>>> {
+        let delimiter = self.delimiter;
+        let mut scratch = Vec::new();
+
+        Some(self.pairs.remove(key)?.1.map(|values| {
+            values
+                .values(delimiter)
+                .map(|v| v.decode(&mut scratch).into_cow())
+                .collect()
+        }))
+    }
+}
+
+/// Prints the decoded keys and their values split by the delimiter,
+/// ex. `{"foo": Some(["bar", "baz"]), "qux": None}`
+impl<'a> fmt::Debug for DelimiterQS<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut scratch = Vec::new();
+        let mut map = f.debug_map();
+
+        for (key, pair) in &self.pairs {
+            let values: Option<Vec<_>> = pair.1.as_ref().map(|values| {
+                values
+                    .values(self.delimiter)
+                    .map(|v| v.decode(&mut scratch).into_cow())
+                    .collect()
+            });
+            let values = values
+                .as_ref()
+                .map(|values| values.iter().map(|v| DebugSlice(v)).collect::<Vec<_>>());
+
+            map.entry(&DebugSlice(key), &values);
+        }
+
+        map.finish()
+    }
 }
 
 #[cfg(feature = "serde")]
 mod de {
+    use std::{borrow::Cow, collections::BTreeMap};
+
     use _serde::Deserialize;
 
+    use crate::config::Config;
     use crate::de::{
-        Error, QSDeserializer,
-        __implementors::{DecodedSlice, IntoRawSlices, RawSlice},
+        __implementors::{DecodedSlice, IntoRawSlices, RawSlice, Scratch, Value as _},
+        Error, ErrorKind, ExtraPair, QSDeserializer,
     };
+    use crate::decode::Decode;
 
-    use super::DelimiterQS;
+    use super::{DelimiterQS, Key, Pair, Values};
 
     impl<'a> DelimiterQS<'a> {
+        /// Creates the parser from already percent decoded pairs, which are never decoded again
+        pub(crate) fn from_decoded_pairs<I>(iter: I, delimiter: u8) -> Self
+        where
+            I: Iterator<Item = (&'a [u8], &'a [u8])>,
+        {
+            let pairs = iter
+                .enumerate()
+                .map(|(position, (key, value))| {
+                    let pair = Pair(
+                        Key(key, Decode::NONE),
+                        Some(Values(value, Decode::NONE)),
+                        position,
+                    );
+                    (Cow::Borrowed(key), pair)
+                })
+                .collect();
+
+            Self { pairs, delimiter }
+        }
+
         /// Deserialize the parsed slice into T
         pub fn deserialize<T: Deserialize<'a>>(self) -> Result<T, Error> {
-            T::deserialize(QSDeserializer::new(self.into_iter()))
+            T::deserialize(QSDeserializer::new(
+                self.into_iter(),
+                Config::default(),
+                &mut Scratch::default(),
+            ))
         }
 
         pub(crate) fn into_iter(
@@ -219,6 +373,27 @@ mod de {
                 )
             })
         }
+
+        /// Removes the pairs whose keys are not fields, and returns them as they were parsed
+        pub(crate) fn take_extra<F>(&mut self, mut is_field: F) -> Vec<ExtraPair<'a>>
+        where
+            F: FnMut(&[u8]) -> bool,
+        {
+            let (pairs, extra): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(&mut self.pairs)
+                .into_iter()
+                .partition(|(key, _)| is_field(key));
+            self.pairs = pairs;
+
+            extra
+                .into_iter()
+                .map(|(key, pair)| ExtraPair {
+                    position: pair.2,
+                    key,
+                    raw_key: (pair.0 .0, None),
+                    raw_value: pair.1.map(|v| v.0),
+                })
+                .collect()
+        }
     }
 
     pub(crate) struct SeparatorValues<'a> {
@@ -237,8 +412,19 @@ mod de {
 
         type UnSizedIterator = SizedValuesIterator<'a>;
 
-        #[inline]
+        /// The number of values is checked before splitting them, ex. `1,2,3` into `(u32, u32)` fails
         fn into_sized_iterator(self, size: usize) -> Result<Self::SizedIterator, crate::de::Error> {
+            let count = match self.slice.is_empty() {
+                true => 0,
+                false => self.slice.iter().filter(|c| **c == self.delimiter).count() + 1,
+            };
+
+            if count != size {
+                return Err(Error::new(ErrorKind::InvalidLength)
+                    .message(format!("expected {} values, found {}", size, count))
+                    .value(self.slice));
+            }
+
             Ok(SizedValuesIterator::new(
                 self.slice,
                 self.delimiter,
@@ -255,6 +441,12 @@ mod de {
         fn into_single_slice(self) -> RawSlice<'a> {
             RawSlice(self.slice)
         }
+
+        /// The whole value is checked, before splitting it by the delimiter
+        #[inline]
+        fn is_null(&self, scratch: &mut Vec<u8>, config: &Config) -> bool {
+            RawSlice(self.slice).is_null(scratch, config)
+        }
     }
 
     pub struct SizedValuesIterator<'a> {
@@ -416,4 +608,38 @@ mod tests {
             ]))
         );
     }
+
+    #[test]
+    fn retain() {
+        let mut parser =
+            DelimiterQS::parse(b"_internal_id=1&foo=bar|baz&_internal%5Fsecret=2", b'|');
+
+        parser.retain(|key| !key.starts_with(b"_internal"));
+        assert_eq!(parser.keys(), vec![&Cow::Borrowed(b"foo")]);
+    }
+
+    #[test]
+    fn canonical_string() {
+        let first = DelimiterQS::parse(b"b=%7e|a%7Cb|&a=1", b'|');
+        let second = DelimiterQS::parse(b"a=%31&b=~|a%7cb|", b'|');
+
+        assert_eq!(first.to_canonical_string(), "a=1&b=~|a%7Cb|");
+        assert_eq!(first.to_canonical_string(), second.to_canonical_string());
+    }
+
+    #[test]
+    fn value_or() {
+        let parser = DelimiterQS::parse(b"foo=bar&foo=b%20z&qux&empty=", b',');
+
+        assert_eq!(parser.value_or(b"foo", b"default"), Cow::Borrowed(b"b z"));
+        assert_eq!(parser.value_or(b"empty", b"default"), Cow::Borrowed(b""));
+        assert_eq!(
+            parser.value_or(b"qux", b"default"),
+            Cow::Borrowed(b"default")
+        );
+        assert_eq!(
+            parser.value_or(b"baz", b"default"),
+            Cow::Borrowed(b"default")
+        );
+    }
 }
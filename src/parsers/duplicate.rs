@@ -1,20 +1,25 @@
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{borrow::Cow, collections::BTreeMap, fmt, ops::Range};
 
-use crate::decode::{parse_bytes, Reference};
+use crate::config::{Config, ParseMode};
+use crate::decode::{decode_if, DebugSlice, Decode, Reference};
+use crate::encode::{encode, write_pair};
+use crate::error::Error;
 
-struct Key<'a>(&'a [u8]);
+#[derive(Clone, Copy)]
+struct Key<'a>(&'a [u8], Decode);
 
 impl<'a> Key<'a> {
-    fn parse(slice: &'a [u8]) -> Self {
+    fn parse(slice: &'a [u8], config: &Config) -> Self {
         let mut index = 0;
         while index < slice.len() {
             match slice[index] {
-                b'&' | b'=' => break,
+                b'=' => break,
+                c if config.is_terminator(c) => break,
                 _ => index += 1,
             }
         }
 
-        Self(&slice[..index])
+        Self(&slice[..index], config.key_decode())
     }
 
     fn len(&self) -> usize {
@@ -22,27 +27,32 @@ impl<'a> Key<'a> {
     }
 
     fn decode<'s>(&self, scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+        decode_if(self.0, scratch, self.1)
+    }
+
+    fn slice(&self) -> &'a [u8] {
+        self.0
     }
 }
 
-struct Value<'a>(&'a [u8]);
+#[derive(Clone, Copy)]
+struct Value<'a>(&'a [u8], Decode);
 
 impl<'a> Value<'a> {
-    fn parse(slice: &'a [u8]) -> Option<Self> {
-        if *slice.get(0)? == b'&' {
+    fn parse(slice: &'a [u8], config: &Config) -> Option<Self> {
+        if config.is_terminator(*slice.first()?) {
             return None;
         }
 
         let mut index = 1;
         while index < slice.len() {
             match slice[index] {
-                b'&' => break,
+                c if config.is_terminator(c) => break,
                 _ => index += 1,
             }
         }
 
-        Some(Self(&slice[1..index]))
+        Some(Self(&slice[1..index], config.value_decode()))
     }
 
     fn len(&self) -> usize {
@@ -50,7 +60,7 @@ impl<'a> Value<'a> {
     }
 
     fn decode<'s>(&self, scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+        decode_if(self.0, scratch, self.1)
     }
 
     fn slice(&self) -> &'a [u8] {
@@ -58,14 +68,16 @@ impl<'a> Value<'a> {
     }
 }
 
-struct Pair<'a>(Key<'a>, Option<Value<'a>>);
+/// A pair with its index in the querystring, which keeps the order of the pairs across keys
+#[derive(Clone, Copy)]
+struct Pair<'a>(Key<'a>, Option<Value<'a>>, usize);
 
 impl<'a> Pair<'a> {
-    fn parse(slice: &'a [u8]) -> Self {
-        let key = Key::parse(slice);
-        let value = Value::parse(&slice[key.len()..]);
+    fn parse(slice: &'a [u8], config: &Config, position: usize) -> Self {
+        let key = Key::parse(slice, config);
+        let value = Value::parse(&slice[key.len()..], config);
 
-        Self(key, value)
+        Self(key, value, position)
     }
 
     /// It report how many chars we should move forward after this pair, to see a new one.
@@ -79,6 +91,55 @@ impl<'a> Pair<'a> {
     }
 }
 
+/// Checks a pair against the config, and returns false if it should be skipped
+///
+/// `params` counts the pairs checked so far, for `Config::max_params`.
+fn check_pair(pair: &Pair<'_>, params: &mut usize, config: &Config) -> Result<bool, Error> {
+    config.check_pair(pair.0.slice(), pair.1.as_ref().map(|v| v.slice()))?;
+    config.check_key_len(pair.0.slice(), pair.0.slice().len())?;
+    if config.skips_empty_key(pair.0.slice(), pair.1.as_ref().map(|v| v.slice())) {
+        return Ok(false);
+    }
+    if !pair.0.slice().is_empty() || pair.1.is_some() {
+        *params += 1;
+        config.check_params(*params)?;
+    } else if config.skip_empty_segments {
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Parses the pairs of a slice and checks them against the config, calling `f` for the pairs
+/// which are not skipped, along with their start and end offsets in the slice
+fn for_each_pair<'a, F>(slice: &'a [u8], config: &Config, mut f: F) -> Result<(), Error>
+where
+    F: FnMut(Pair<'a>, Range<usize>) -> Result<(), Error>,
+{
+    config.validate_for(ParseMode::Duplicate)?;
+
+    let mut index = 0;
+    let mut params = 0;
+    let mut position = 0;
+
+    while index < slice.len() {
+        let pair = Pair::parse(&slice[index..], config, position);
+        let start = index;
+        let end = match &pair.1 {
+            Some(v) => start + pair.0.len() + v.len() + 1,
+            None => start + pair.0.len(),
+        };
+        index += pair.skip_len();
+        position += 1;
+
+        if check_pair(&pair, &mut params, config)? {
+            f(pair, start..end)?;
+        }
+    }
+
+    Ok(())
+}
+
 /// A querystring parser with support for vectors/lists of values by repeating keys.
 ///
 /// # Note
@@ -110,30 +171,204 @@ impl<'a> Pair<'a> {
 /// ```
 pub struct DuplicateQS<'a> {
     pairs: BTreeMap<Cow<'a, [u8]>, Vec<Pair<'a>>>,
+    // The config it was parsed with, to deserialize the values with the same options
+    config: Config,
 }
 
 impl<'a> DuplicateQS<'a> {
     /// Parse a slice of bytes into a `DuplicateQS`
     pub fn parse(slice: &'a [u8]) -> Self {
+        Self::try_parse(slice, &Config::default())
+            .expect("Parsing with the default config doesn't fail")
+    }
+
+    /// Parse a slice of bytes into a `DuplicateQS`, using the provided `Config`
+    ///
+    /// It returns an error if the slice violates any of the config's restrictions.
+    pub fn try_parse(slice: &'a [u8], config: &Config) -> Result<Self, Error> {
+        Self::parse_with_observer(slice, config, |_, _| {})
+    }
+
+    /// Parse a slice of bytes into a `DuplicateQS` like `try_parse`, calling `observer` with
+    /// the raw key and value of each pair as it's parsed
+    ///
+    /// The observer gets the undecoded slices, ex. to count the params or measure their sizes
+    /// for metrics, and `None` for pairs without a value. Skipped empty segments are not observed.
+    ///
+    /// # Example
+    /// ```rust
+    /// use serde_querystring::{Config, DuplicateQS};
+    ///
+    /// let mut sizes = Vec::new();
+    /// let config = Config::default();
+    /// let parser = DuplicateQS::parse_with_observer(b"a=1&b%20c=22&d", &config, |key, value| {
+    ///     sizes.push((key.len(), value.map(|v| v.len())))
+    /// })
+    /// .unwrap();
+    ///
+    /// assert_eq!(sizes, vec![(1, Some(1)), (5, Some(2)), (1, None)]);
+    /// assert_eq!(parser.keys().len(), 3);
+    /// ```
+    pub fn parse_with_observer<F>(
+        slice: &'a [u8],
+        config: &Config,
+        mut observer: F,
+    ) -> Result<Self, Error>
+    where
+        F: FnMut(&[u8], Option<&[u8]>),
+    {
         let mut pairs: BTreeMap<Cow<'a, [u8]>, Vec<Pair<'a>>> = BTreeMap::new();
         let mut scratch = Vec::new();
 
-        let mut index = 0;
+        for_each_pair(slice, config, |pair, _| {
+            observer(pair.0.slice(), pair.1.as_ref().map(|v| v.slice()));
+            let decoded_key = pair.0.decode(&mut scratch);
 
-        while index < slice.len() {
-            let pair = Pair::parse(&slice[index..]);
-            index += pair.skip_len();
+            if let Some(values) = pairs.get_mut(decoded_key.as_ref()) {
+                config.check_values_per_key(&decoded_key, values.len() + 1)?;
+                values.push(pair);
+            } else {
+                config.check_values_per_key(&decoded_key, 1)?;
+                pairs.insert(decoded_key.into_cow(), vec![pair]);
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Self {
+            pairs,
+            config: *config,
+        })
+    }
+
+    /// Parse a slice of bytes into a `LazyDuplicateQS`, which decodes the keys on lookup
+    pub fn parse_lazy(slice: &'a [u8]) -> LazyDuplicateQS<'a> {
+        Self::try_parse_lazy(slice, &Config::default())
+            .expect("Parsing with the default config doesn't fail")
+    }
+
+    /// Parse a slice of bytes into a `LazyDuplicateQS`, using the provided `Config`
+    ///
+    /// It returns an error if the slice violates any of the config's restrictions.
+    /// `Config::max_values_per_key` needs the decoded keys, so the keys are decoded while
+    /// parsing when it's set.
+    pub fn try_parse_lazy(slice: &'a [u8], config: &Config) -> Result<LazyDuplicateQS<'a>, Error> {
+        let mut pairs = Vec::new();
+        let mut counts: BTreeMap<Cow<'a, [u8]>, usize> = BTreeMap::new();
+        let mut scratch = Vec::new();
+
+        for_each_pair(slice, config, |pair, _| {
+            if config.max_values_per_key.is_some() {
+                let decoded_key = pair.0.decode(&mut scratch);
+
+                if let Some(count) = counts.get_mut(decoded_key.as_ref()) {
+                    *count += 1;
+                    config.check_values_per_key(&decoded_key, *count)?;
+                } else {
+                    config.check_values_per_key(&decoded_key, 1)?;
+                    counts.insert(decoded_key.into_cow(), 1);
+                }
+            }
+
+            pairs.push(pair);
+            Ok(())
+        })?;
+
+        Ok(LazyDuplicateQS { pairs })
+    }
+
+    /// Parse an owned buffer into an `OwnedDuplicateQS`, which keeps the buffer and
+    /// is not bound to the lifetime of the input
+    pub fn parse_owned(buffer: Vec<u8>) -> OwnedDuplicateQS {
+        Self::try_parse_owned(buffer, &Config::default())
+            .expect("Parsing with the default config doesn't fail")
+    }
+
+    /// Parse an owned buffer into an `OwnedDuplicateQS`, using the provided `Config`
+    ///
+    /// It returns an error if the buffer violates any of the config's restrictions.
+    pub fn try_parse_owned(buffer: Vec<u8>, config: &Config) -> Result<OwnedDuplicateQS, Error> {
+        let mut pairs: BTreeMap<Vec<u8>, Vec<PairRange>> = BTreeMap::new();
+        let mut scratch = Vec::new();
+
+        // The pairs are kept as ranges of the buffer, so it can be moved along with them
+        for_each_pair(&buffer, config, |pair, span| {
+            let key = span.start..span.start + pair.0.len();
+            let value = pair.1.as_ref().map(|v| span.end - v.len()..span.end);
+            let decoded_key = pair.0.decode(&mut scratch);
+
+            if let Some(ranges) = pairs.get_mut(decoded_key.as_ref()) {
+                config.check_values_per_key(&decoded_key, ranges.len() + 1)?;
+                ranges.push((key, value, pair.2));
+            } else {
+                config.check_values_per_key(&decoded_key, 1)?;
+                pairs.insert(decoded_key.to_vec(), vec![(key, value, pair.2)]);
+            }
+
+            Ok(())
+        })?;
+
+        Ok(OwnedDuplicateQS {
+            buffer,
+            pairs,
+            config: *config,
+        })
+    }
+
+    /// Create a `DuplicateQS` from already split pairs, ex. `[b"foo=bar", b"foo=baz"]`
+    ///
+    /// Each chunk is parsed as a single pair, the key ends at the first `=` and the rest of
+    /// the chunk is the value, even if it contains a `&`.
+    pub fn from_chunks<I>(chunks: I) -> Self
+    where
+        I: Iterator<Item = &'a [u8]>,
+    {
+        Self::try_from_chunks(chunks, &Config::default())
+            .expect("Parsing with the default config doesn't fail")
+    }
+
+    /// Create a `DuplicateQS` from already split pairs like `from_chunks`, using the provided
+    /// `Config`
+    ///
+    /// It returns an error if the chunks violate any of the config's restrictions.
+    pub fn try_from_chunks<I>(chunks: I, config: &Config) -> Result<Self, Error>
+    where
+        I: Iterator<Item = &'a [u8]>,
+    {
+        config.validate_for(ParseMode::Duplicate)?;
+        let mut pairs: BTreeMap<Cow<'a, [u8]>, Vec<Pair<'a>>> = BTreeMap::new();
+        let mut scratch = Vec::new();
+        let mut params = 0;
+
+        for (position, chunk) in chunks.enumerate() {
+            let pair = match chunk.iter().position(|c| *c == b'=') {
+                Some(index) => Pair(
+                    Key(&chunk[..index], config.key_decode()),
+                    Some(Value(&chunk[index + 1..], config.value_decode())),
+                    position,
+                ),
+                None => Pair(Key(chunk, config.key_decode()), None, position),
+            };
+
+            if !check_pair(&pair, &mut params, config)? {
+                continue;
+            }
 
             let decoded_key = pair.0.decode(&mut scratch);
 
             if let Some(values) = pairs.get_mut(decoded_key.as_ref()) {
+                config.check_values_per_key(&decoded_key, values.len() + 1)?;
                 values.push(pair);
             } else {
+                config.check_values_per_key(&decoded_key, 1)?;
                 pairs.insert(decoded_key.into_cow(), vec![pair]);
             }
         }
 
-        Self { pairs }
+        Ok(Self {
+            pairs,
+            config: *config,
+        })
     }
 
     /// Returns a vector containing all the keys in querystring.
@@ -141,6 +376,26 @@ impl<'a> DuplicateQS<'a> {
         self.pairs.keys().collect()
     }
 
+    /// Returns the decoded key of the first pair in the querystring, ex. `b` for `b=1&a=2`.
+    pub fn first_key(&self) -> Option<&Cow<'a, [u8]>> {
+        self.pairs
+            .iter()
+            .filter_map(|(key, pairs)| Some((key, pairs.first()?.2)))
+            .min_by_key(|(_, position)| *position)
+            .map(|(key, _)| key)
+    }
+
+    /// Returns the decoded key of the last pair in the querystring, ex. `a` for `b=1&a=2&b=3&a`.
+    ///
+    /// After `merge` the pairs of the merged querystring come after the pairs of the base.
+    pub fn last_key(&self) -> Option<&Cow<'a, [u8]>> {
+        self.pairs
+            .iter()
+            .filter_map(|(key, pairs)| Some((key, pairs.last()?.2)))
+            .max_by_key(|(_, position)| *position)
+            .map(|(key, _)| key)
+    }
+
     /// Returns a vector containing all the values assigned to a key.
     ///
     /// It returns None if the **key doesn't exist** in the querystring,
@@ -160,6 +415,35 @@ impl<'a> DuplicateQS<'a> {
         )
     }
 
+    /// Returns all the keys with all their values, percent decoded at once.
+    ///
+    /// It's a snapshot of the whole querystring for reading it without serde, simpler than
+    /// calling `values` for each key, but it allocates the map and decodes every key and value
+    /// even if only a few of them are used.
+    #[allow(clippy::type_complexity)]
+    pub fn as_multimap(&self) -> BTreeMap<Cow<'a, [u8]>, Vec<Option<Cow<'a, [u8]>>>> {
+        let mut scratch = Vec::new();
+
+        self.pairs
+            .iter()
+            .map(|(key, pairs)| {
+                let values = pairs
+                    .iter()
+                    .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
+                    .collect();
+                (key.clone(), values)
+            })
+            .collect()
+    }
+
+    /// Returns the number of values assigned to a key, including the assignments without a value.
+    ///
+    /// It returns 0 if the **key doesn't exist** in the querystring, and it doesn't decode
+    /// or allocate anything.
+    pub fn values_count(&self, key: &[u8]) -> usize {
+        self.pairs.get(key).map_or(0, |pairs| pairs.len())
+    }
+
     /// Returns the last value assigned to a key.
     ///
     /// It returns `None` if the **key doesn't exist** in the querystring,
@@ -176,136 +460,844 @@ impl<'a> DuplicateQS<'a> {
             .last()
             .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
     }
-}
 
-#[cfg(feature = "serde")]
-mod de {
-    use _serde::Deserialize;
-
-    use crate::de::{
-        Error, ErrorKind, QSDeserializer,
-        __implementors::{DecodedSlice, IntoRawSlices, RawSlice},
-    };
+    /// Returns the last value assigned to a key, or `default` if the **key doesn't exist** or
+    /// its last assignment **doesn't have a value**, ex `"&key&"`
+    pub fn value_or(&self, key: &'a [u8], default: &'a [u8]) -> Cow<'a, [u8]> {
+        self.value(key).flatten().unwrap_or(Cow::Borrowed(default))
+    }
 
-    use super::DuplicateQS;
+    /// Returns the last value assigned to a key, without percent decoding it.
+    ///
+    /// It returns `None` and `Some(None)` in the same cases as `value`, but doesn't decode
+    /// or allocate anything.
+    pub fn raw_value(&self, key: &[u8]) -> Option<Option<&'a [u8]>> {
+        self.pairs
+            .get(key)?
+            .last()
+            .map(|p| p.1.as_ref().map(|v| v.slice()))
+    }
 
-    impl<'a> DuplicateQS<'a> {
-        /// Deserialize the parsed slice into T
-        pub fn deserialize<T: Deserialize<'a>>(self) -> Result<T, Error> {
-            T::deserialize(QSDeserializer::new(self.into_iter()))
-        }
+    /// Returns whether the last value of a key needs percent decoding, ex. `true` for `a%20b`.
+    ///
+    /// `value` borrows the values which don't need decoding from the input(`Cow::Borrowed`) and
+    /// copies the others(`Cow::Owned`), so it tells how a value can be stored before calling
+    /// `value`. It returns `None` if the **key doesn't exist**, and `Some(false)` for an assignment
+    /// without a value, ex `"&key&"`.
+    pub fn needs_decoding(&self, key: &[u8]) -> Option<bool> {
+        let mut scratch = Vec::new();
 
-        pub(crate) fn into_iter(
-            self,
-        ) -> impl Iterator<
-            Item = (
-                DecodedSlice<'a>,
-                DuplicateValueIter<impl Iterator<Item = RawSlice<'a>>>,
-            ),
-        > {
-            self.pairs.into_iter().map(|(key, pairs)| {
-                (
-                    DecodedSlice(key),
-                    DuplicateValueIter(
-                        pairs
-                            .into_iter()
-                            .map(|v| RawSlice(v.1.map(|v| v.slice()).unwrap_or_default())),
-                    ),
-                )
+        self.pairs.get(key)?.last().map(|p| {
+            p.1.as_ref().map_or(false, |v| {
+                matches!(v.decode(&mut scratch), Reference::Copied(_))
             })
+        })
+    }
+
+    /// Percent decodes the last value of a key into `buf`, replacing its content.
+    ///
+    /// Unlike `value`, it never allocates once `buf` is large enough, so a single buffer can be
+    /// reused for many keys. It returns `None` if the **key doesn't exist**, and `Some(false)`
+    /// leaving `buf` empty if the last assignment to a **key doesn't have a value**, ex `"&key&"`.
+    pub fn decode_into(&self, key: &[u8], buf: &mut Vec<u8>) -> Option<bool> {
+        let pair = self.pairs.get(key)?.last()?;
+
+        buf.clear();
+        match &pair.1 {
+            Some(value) => {
+                if let Reference::Borrowed(slice) = value.decode(buf) {
+                    buf.extend_from_slice(slice);
+                }
+                Some(true)
+            }
+            None => Some(false),
         }
     }
 
-    pub(crate) struct DuplicateValueIter<I>(I);
+    /// Re-encodes the querystring in a canonical form, ex. for cache keys or signatures.
+    ///
+    /// The rules are:
+    /// - Keys are sorted by their percent decoded bytes, the values of a key keep their order
+    /// - Keys and values are percent decoded, then encoded again keeping alphanumerics and
+    ///   `-._~` as they are, writing spaces as `+` and every other byte as `%XX` in uppercase hex
+    /// - Pairs are written as `key=value`, or `key` if they don't have a value, and joined by `&`
+    ///
+    /// So equivalent querystrings, ex. `b=%7e&a=1` and `a=1&b=~`, give the same output.
+    pub fn to_canonical_string(&self) -> String {
+        let mut output = String::new();
+        let mut scratch = Vec::new();
 
-    impl<'a, I> IntoRawSlices<'a> for DuplicateValueIter<I>
+        for (key, pairs) in &self.pairs {
+            write_pairs(key, pairs, &mut scratch, &mut output);
+        }
+
+        output
+    }
+
+    /// Removes the keys not matching the predicate, along with all their values.
+    ///
+    /// The predicate gets the percent decoded keys, ex. to drop the keys starting with `_`
+    /// before deserializing.
+    pub fn retain<F>(&mut self, mut f: F)
     where
-        I: Iterator<Item = RawSlice<'a>>,
+        F: FnMut(&[u8]) -> bool,
     {
-        type SizedIterator = I;
-        type UnSizedIterator = I;
+        self.pairs.retain(|key, _| f(key));
+    }
 
-        #[inline]
-        fn into_sized_iterator(self, size: usize) -> Result<I, Error> {
-            if self.0.size_hint().0 == size {
-                Ok(self.0)
-            } else {
-                Err(Error::new(ErrorKind::InvalidLength))
-            }
-        }
+    /// Removes a key and returns all its values, percent decoded like `values`.
+    ///
+    /// It returns `None` if the **key doesn't exist**, ex. to strip a sensitive key before logging
+    /// the querystring.
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<Option<Cow<'a, [u8]>>>> {
+        let mut scratch = Vec::new();
 
-        #[inline]
-        fn into_unsized_iterator(self) -> I {
-            self.0
+        Some(
+            self.pairs
+                .remove(key)?
+                .iter()
+                .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
+                .collect(),
+        )
+    }
+
+    /// Returns the keys starting with `prefix`, with the prefix removed from them.
+    ///
+    /// The prefix is matched against the percent decoded keys, ex. `t1.` in `t1.user=a&t2.user=b` gives
+    /// a querystring with `user` assigned to `a`.
+    pub fn with_prefix(&self, prefix: &[u8]) -> Self {
+        let pairs = self
+            .pairs
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, pairs)| {
+                let key = match key {
+                    Cow::Borrowed(key) => Cow::Borrowed(&key[prefix.len()..]),
+                    Cow::Owned(key) => Cow::Owned(key[prefix.len()..].to_vec()),
+                };
+                (key, pairs.clone())
+            })
+            .collect();
+
+        Self {
+            pairs,
+            config: self.config,
         }
+    }
 
-        #[inline]
-        fn into_single_slice(self) -> RawSlice<'a> {
-            self.0
-                .last()
-                .expect("Iterator has at least one value in it")
+    /// Combines two querystrings, the values of `other` are appended after the values of
+    /// the same key in `self`, ex. `a=1` merged with `a=2&b=3` gives `a=1&a=2&b=3`.
+    ///
+    /// Values are not deduplicated, a value present in both querystrings is kept twice.
+    pub fn merge(mut self, other: Self) -> Self {
+        // The pairs of `other` come after all the pairs of `self`
+        let offset = self
+            .pairs
+            .values()
+            .flatten()
+            .map(|p| p.2 + 1)
+            .max()
+            .unwrap_or(0);
+        for (key, pairs) in other.pairs {
+            let pairs = pairs.into_iter().map(|p| Pair(p.0, p.1, p.2 + offset));
+            self.pairs.entry(key).or_default().extend(pairs);
         }
+        self
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::borrow::Cow;
+/// The ranges of the key and the value of a pair in the buffer of an `OwnedDuplicateQS`,
+/// and the index of the pair
+type PairRange = (Range<usize>, Option<Range<usize>>, usize);
 
-    use super::DuplicateQS;
+/// A `DuplicateQS` holding its input, ex. a request body that should outlive the function
+/// reading it
+///
+/// The querystring is parsed once, and `parser` gives a `DuplicateQS` borrowing the buffer
+/// without parsing or decoding the keys again.
+///
+/// # Example
+/// ```rust
+/// use serde_querystring::{DuplicateQS, OwnedDuplicateQS};
+///
+/// fn read_body() -> OwnedDuplicateQS {
+///     let body = b"foo=bar&foo=b%20z".to_vec();
+///     DuplicateQS::parse_owned(body)
+/// }
+///
+/// let owned = read_body();
+/// assert_eq!(owned.parser().value(b"foo"), Some(Some("b z".as_bytes().into())));
+/// ```
+pub struct OwnedDuplicateQS {
+    buffer: Vec<u8>,
+    // The ranges of the undecoded keys and values in the buffer, by their decoded keys
+    pairs: BTreeMap<Vec<u8>, Vec<PairRange>>,
+    config: Config,
+}
 
-    #[test]
-    fn parse_pair() {
-        let slice = b"key=value";
+impl OwnedDuplicateQS {
+    /// Returns a `DuplicateQS` borrowing the buffer
+    pub fn parser(&self) -> DuplicateQS<'_> {
+        let (key_decode, value_decode) = (self.config.key_decode(), self.config.value_decode());
+        let pairs = self
+            .pairs
+            .iter()
+            .map(|(key, ranges)| {
+                let pairs = ranges
+                    .iter()
+                    .map(|(key, value, position)| {
+                        Pair(
+                            Key(&self.buffer[key.clone()], key_decode),
+                            value
+                                .as_ref()
+                                .map(|value| Value(&self.buffer[value.clone()], value_decode)),
+                            *position,
+                        )
+                    })
+                    .collect();
+                (Cow::Borrowed(key.as_slice()), pairs)
+            })
+            .collect();
 
-        let parser = DuplicateQS::parse(slice);
+        DuplicateQS {
+            pairs,
+            config: self.config,
+        }
+    }
 
-        assert_eq!(parser.keys(), vec![&Cow::Borrowed(b"key")]);
-        assert_eq!(
-            parser.values(b"key"),
-            Some(vec![Some(Cow::Borrowed("value".as_bytes()))])
-        );
-        assert_eq!(
-            parser.value(b"key"),
-            Some(Some(Cow::Borrowed("value".as_bytes())))
-        );
+    /// Returns the buffer holding the querystring
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buffer
     }
+}
 
-    #[test]
-    fn parse_multiple_pairs() {
-        let slice = b"foo=bar&foobar=baz&qux=box";
+impl fmt::Debug for OwnedDuplicateQS {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.parser(), f)
+    }
+}
 
-        let parser = DuplicateQS::parse(slice);
+/// A `DuplicateQS` which keeps the keys undecoded, returned by `DuplicateQS::parse_lazy`
+///
+/// `DuplicateQS` decodes every key while parsing, copying the keys with escapes even if they
+/// are never looked up. `LazyDuplicateQS` only splits the pairs, and decodes the keys when
+/// looking up a key, so it's faster for wide querystrings when only a few keys are read.
+/// Each lookup goes through all the pairs, use `DuplicateQS` for reading many keys.
+///
+/// # Example
+/// ```rust
+/// use serde_querystring::DuplicateQS;
+///
+/// let parser = DuplicateQS::parse_lazy(b"foo%5Fbar=1&baz=2&foo_bar=3");
+///
+/// assert_eq!(
+///     parser.values(b"foo_bar"),
+///     Some(vec![Some("1".as_bytes().into()), Some("3".as_bytes().into())])
+/// );
+/// assert_eq!(parser.value(b"qux"), None);
+/// ```
+pub struct LazyDuplicateQS<'a> {
+    pairs: Vec<Pair<'a>>,
+}
 
-        assert_eq!(
-            parser.values(b"foo"),
-            Some(vec![Some("bar".as_bytes().into())])
-        );
-        assert_eq!(
-            parser.values(b"foobar"),
-            Some(vec![Some("baz".as_bytes().into())])
-        );
-        assert_eq!(
-            parser.values(b"qux"),
-            Some(vec![Some("box".as_bytes().into())])
-        );
+impl<'a> LazyDuplicateQS<'a> {
+    /// Returns whether the decoded key of the pair is `key`
+    fn matches(pair: &Pair<'a>, key: &[u8], scratch: &mut Vec<u8>) -> bool {
+        pair.0.decode(scratch).as_ref() == key
     }
 
-    #[test]
-    fn parse_no_value() {
-        let slice = b"foo&foobar=";
+    /// Returns all the values assigned to a key, like `DuplicateQS::values`.
+    ///
+    /// # Note
+    /// The keys of all the pairs are decoded **every time** this function is called.
+    pub fn values(&self, key: &[u8]) -> Option<Vec<Option<Cow<'a, [u8]>>>> {
+        let mut scratch = Vec::new();
+        let mut values = Vec::new();
 
-        let parser = DuplicateQS::parse(slice);
+        for pair in &self.pairs {
+            if Self::matches(pair, key, &mut scratch) {
+                values.push(pair.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()));
+            }
+        }
 
-        assert_eq!(parser.value(b"key"), None);
-        assert_eq!(parser.values(b"key"), None);
-        assert_eq!(parser.value(b"foo"), Some(None));
-        assert_eq!(parser.values(b"foo"), Some(vec![None]));
-        assert_eq!(
-            parser.values(b"foobar"),
-            Some(vec![Some("".as_bytes().into())])
-        );
-        assert_eq!(parser.value(b"foobar"), Some(Some("".as_bytes().into())));
+        match values.is_empty() {
+            true => None,
+            false => Some(values),
+        }
+    }
+
+    /// Returns the last value assigned to a key, like `DuplicateQS::value`.
+    ///
+    /// # Note
+    /// The keys are decoded from the last pair **every time** this function is called.
+    pub fn value(&self, key: &[u8]) -> Option<Option<Cow<'a, [u8]>>> {
+        let mut scratch = Vec::new();
+
+        self.pairs
+            .iter()
+            .rev()
+            .find(|p| Self::matches(p, key, &mut scratch))
+            .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
+    }
+}
+
+/// Parses a querystring into a flat list of its decoded pairs, in the order they appear
+///
+/// Unlike the parsers, the pairs are not grouped by their keys, and empty segments like `"&&"`
+/// are skipped.
+///
+/// # Example
+/// ```rust
+/// use serde_querystring::parse_pairs_ordered;
+///
+/// let pairs = parse_pairs_ordered(b"b=1&a&b=%32");
+/// assert_eq!(
+///     pairs,
+///     vec![
+///         ("b".as_bytes().into(), Some("1".as_bytes().into())),
+///         ("a".as_bytes().into(), None),
+///         ("b".as_bytes().into(), Some("2".as_bytes().into())),
+///     ]
+/// );
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn parse_pairs_ordered(slice: &[u8]) -> Vec<(Cow<'_, [u8]>, Option<Cow<'_, [u8]>>)> {
+    let config = Config::default();
+    let mut scratch = Vec::new();
+    let mut pairs = Vec::new();
+
+    let mut index = 0;
+    while index < slice.len() {
+        let pair = Pair::parse(&slice[index..], &config, pairs.len());
+        index += pair.skip_len();
+
+        if pair.0.slice().is_empty() && pair.1.is_none() {
+            continue;
+        }
+
+        pairs.push((
+            pair.0.decode(&mut scratch).into_cow(),
+            pair.1.map(|v| v.decode(&mut scratch).into_cow()),
+        ));
+    }
+
+    pairs
+}
+
+/// A cursor stepping through the pairs of a querystring in order, ex. for editors or inspectors
+///
+/// Pairs are parsed one at a time like `DuplicateQS::parse` without being stored, and empty
+/// segments like `"&&"` are skipped. Offsets are in bytes from the start of the slice.
+///
+/// # Example
+/// ```rust
+/// use serde_querystring::PairCursor;
+///
+/// let mut cursor = PairCursor::new(b"a=1&&b%20c&a=x+y");
+/// assert_eq!(cursor.offset(), Some(0));
+/// assert_eq!(cursor.value(), Some(Some("1".as_bytes().into())));
+///
+/// assert!(cursor.advance());
+/// assert_eq!(cursor.key(), Some("b c".as_bytes().into()));
+/// assert_eq!(cursor.value(), Some(None));
+/// assert_eq!(cursor.span(), Some(5..10));
+///
+/// assert!(cursor.advance());
+/// assert_eq!(cursor.raw_value(), Some(Some("x+y".as_bytes())));
+/// assert!(!cursor.advance());
+/// assert_eq!(cursor.key(), None);
+/// ```
+pub struct PairCursor<'a> {
+    slice: &'a [u8],
+    config: Config,
+    /// The offset of the current pair and the pair, `None` after the last pair
+    current: Option<(usize, Pair<'a>)>,
+}
+
+impl<'a> PairCursor<'a> {
+    /// Creates a cursor at the first pair of the slice
+    pub fn new(slice: &'a [u8]) -> Self {
+        Self::with_config(slice, &Config::default())
+    }
+
+    /// Creates a cursor at the first pair of the slice, parsing it with the options of `config`
+    ///
+    /// Only the options changing how pairs are split or decoded are used, ex.
+    /// `Config::newline_terminators`, the limits of `try_parse` are not checked.
+    pub fn with_config(slice: &'a [u8], config: &Config) -> Self {
+        let mut cursor = Self {
+            slice,
+            config: *config,
+            current: None,
+        };
+        cursor.seek(0);
+        cursor
+    }
+
+    /// Moves to the first non empty pair at or after `index`
+    fn seek(&mut self, mut index: usize) {
+        while index < self.slice.len() {
+            let pair = Pair::parse(&self.slice[index..], &self.config, index);
+            if !pair.0.slice().is_empty() || pair.1.is_some() {
+                self.current = Some((index, pair));
+                return;
+            }
+            index += pair.skip_len();
+        }
+        self.current = None;
+    }
+
+    /// Moves to the next pair, it returns `false` if there are no more pairs
+    pub fn advance(&mut self) -> bool {
+        if let Some((offset, pair)) = self.current {
+            self.seek(offset + pair.skip_len());
+        }
+        self.current.is_some()
+    }
+
+    /// Returns true once the cursor moved past the last pair
+    pub fn is_done(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /// Returns the offset of the current pair
+    pub fn offset(&self) -> Option<usize> {
+        self.current.map(|(offset, _)| offset)
+    }
+
+    /// Returns the range of the current pair, from the start of its key to the end of its value
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.current.map(|(offset, pair)| {
+            let len = match pair.1 {
+                Some(value) => pair.0.len() + value.len() + 1,
+                None => pair.0.len(),
+            };
+            offset..offset + len
+        })
+    }
+
+    /// Returns the percent decoded key of the current pair
+    pub fn key(&self) -> Option<Cow<'a, [u8]>> {
+        let mut scratch = Vec::new();
+        self.current
+            .map(|(_, pair)| pair.0.decode(&mut scratch).into_cow())
+    }
+
+    /// Returns the percent decoded value of the current pair, `Some(None)` if the pair
+    /// **doesn't have a value**, ex `"&key&"`
+    pub fn value(&self) -> Option<Option<Cow<'a, [u8]>>> {
+        let mut scratch = Vec::new();
+        self.current
+            .map(|(_, pair)| pair.1.map(|v| v.decode(&mut scratch).into_cow()))
+    }
+
+    /// Returns the key of the current pair without decoding it
+    pub fn raw_key(&self) -> Option<&'a [u8]> {
+        self.current.map(|(_, pair)| pair.0.slice())
+    }
+
+    /// Returns the value of the current pair without decoding it
+    pub fn raw_value(&self) -> Option<Option<&'a [u8]>> {
+        self.current.map(|(_, pair)| pair.1.map(|v| v.slice()))
+    }
+}
+
+/// Parses a querystring like `DuplicateQS::parse`, with its keys lossily converted to `String`
+///
+/// Invalid utf-8 in keys is replaced by `U+FFFD`, and the keys which become equal after the
+/// conversion share their values, in the order they appear.
+///
+/// # Example
+/// ```rust
+/// use serde_querystring::parse_lossy;
+///
+/// let parser = parse_lossy(b"name%FF=a&name=b");
+/// assert_eq!(parser.keys(), vec!["name", "name\u{FFFD}"]);
+/// assert_eq!(parser.value("name\u{FFFD}"), Some(Some("a".as_bytes().into())));
+/// ```
+pub fn parse_lossy(slice: &[u8]) -> LossyQS<'_> {
+    let mut pairs: BTreeMap<String, Vec<Pair<'_>>> = BTreeMap::new();
+
+    for (key, key_pairs) in DuplicateQS::parse(slice).pairs {
+        pairs
+            .entry(String::from_utf8_lossy(&key).into_owned())
+            .or_default()
+            .extend(key_pairs);
+    }
+    for key_pairs in pairs.values_mut() {
+        key_pairs.sort_by_key(|pair| pair.2);
+    }
+
+    LossyQS { pairs }
+}
+
+/// A `DuplicateQS` with `String` keys, returned by `parse_lossy`
+pub struct LossyQS<'a> {
+    pairs: BTreeMap<String, Vec<Pair<'a>>>,
+}
+
+impl<'a> LossyQS<'a> {
+    /// Returns a vector containing all the keys in querystring.
+    pub fn keys(&self) -> Vec<&str> {
+        self.pairs.keys().map(String::as_str).collect()
+    }
+
+    /// Returns a vector containing all the values assigned to a key, like `DuplicateQS::values`.
+    pub fn values(&self, key: &str) -> Option<Vec<Option<Cow<'a, [u8]>>>> {
+        let mut scratch = Vec::new();
+
+        Some(
+            self.pairs
+                .get(key)?
+                .iter()
+                .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
+                .collect(),
+        )
+    }
+
+    /// Returns the last value assigned to a key, like `DuplicateQS::value`.
+    pub fn value(&self, key: &str) -> Option<Option<Cow<'a, [u8]>>> {
+        let mut scratch = Vec::new();
+
+        self.pairs
+            .get(key)?
+            .last()
+            .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
+    }
+}
+
+/// Prints the keys and all their decoded values, ex. `{"foo": [Some("bar"), None]}`
+impl<'a> fmt::Debug for LossyQS<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+
+        for key in self.pairs.keys() {
+            let values = self.values(key).unwrap_or_default();
+            let values: Vec<_> = values
+                .iter()
+                .map(|v| v.as_deref().map(DebugSlice))
+                .collect();
+
+            map.entry(key, &values);
+        }
+
+        map.finish()
+    }
+}
+
+/// Writes the re-encoded pairs of a key to the output
+fn write_pairs(key: &[u8], pairs: &[Pair<'_>], scratch: &mut Vec<u8>, output: &mut String) {
+    let key = encode(key);
+    for pair in pairs {
+        match &pair.1 {
+            Some(value) => write_pair(output, &key, Some(&value.decode(scratch))),
+            None => write_pair(output, &key, None),
+        }
+    }
+}
+
+/// Prints the decoded keys and all their values, ex. `{"foo": [Some("bar"), None]}`
+impl<'a> fmt::Debug for DuplicateQS<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut scratch = Vec::new();
+        let mut map = f.debug_map();
+
+        for (key, pairs) in &self.pairs {
+            let values: Vec<_> = pairs
+                .iter()
+                .map(|p| p.1.as_ref().map(|v| v.decode(&mut scratch).into_cow()))
+                .collect();
+            let values: Vec<_> = values
+                .iter()
+                .map(|v| v.as_deref().map(DebugSlice))
+                .collect();
+
+            map.entry(&DebugSlice(key), &values);
+        }
+
+        map.finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+mod de {
+    use std::{borrow::Cow, collections::BTreeMap};
+
+    use _serde::{de::DeserializeOwned, Deserialize};
+
+    use crate::config::Config;
+    use crate::de::{
+        __implementors::{
+            DecodedSlice, IntoDeserializer, IntoRawSlices, RawSlice, Scratch, Value as _,
+        },
+        Error, ErrorKind, ExtraPair, QSDeserializer,
+    };
+    use crate::decode::Decode;
+
+    use super::{DuplicateQS, Key, Pair, Value};
+
+    impl<'a> DuplicateQS<'a> {
+        /// Creates the parser from already percent decoded pairs, which are never decoded again
+        pub(crate) fn from_decoded_pairs<I>(iter: I, config: &Config) -> Self
+        where
+            I: Iterator<Item = (&'a [u8], &'a [u8])>,
+        {
+            let mut pairs: BTreeMap<Cow<'a, [u8]>, Vec<Pair<'a>>> = BTreeMap::new();
+
+            for (position, (key, value)) in iter.enumerate() {
+                pairs.entry(Cow::Borrowed(key)).or_default().push(Pair(
+                    Key(key, Decode::NONE),
+                    Some(Value(value, Decode::NONE)),
+                    position,
+                ));
+            }
+
+            Self {
+                pairs,
+                config: *config,
+            }
+        }
+
+        /// Deserialize the parsed slice into T, with the config it was parsed with
+        pub fn deserialize<T: Deserialize<'a>>(self) -> Result<T, Error> {
+            let config = self.config;
+            T::deserialize(QSDeserializer::new(
+                self.into_iter(),
+                config,
+                &mut Scratch::default(),
+            ))
+        }
+
+        /// Deserialize the last value assigned to a key into T, without a struct for the
+        /// whole querystring
+        ///
+        /// It returns `None` if the **key doesn't exist** in the querystring, a key without
+        /// a value is deserialized like an empty value.
+        ///
+        /// # Example
+        /// ```rust
+        /// use serde_querystring::DuplicateQS;
+        ///
+        /// let parser = DuplicateQS::parse(b"page=2&page=3&all=true");
+        /// assert_eq!(parser.get_as::<u32>(b"page").transpose(), Ok(Some(3)));
+        /// assert_eq!(parser.get_as::<bool>(b"all").transpose(), Ok(Some(true)));
+        /// assert_eq!(parser.get_as::<u32>(b"size").transpose(), Ok(None));
+        /// ```
+        pub fn get_as<T: DeserializeOwned>(&self, key: &[u8]) -> Option<Result<T, Error>> {
+            let value = self.raw_value(key)?.map(RawSlice);
+            let mut scratch = Scratch::default();
+            Some(T::deserialize(
+                value.into_deserializer(&mut scratch, &self.config),
+            ))
+        }
+
+        /// Deserialize all the values assigned to a key into T, in the order they appear
+        ///
+        /// It returns `None` if the **key doesn't exist** in the querystring, and stops at the
+        /// first value failing to deserialize.
+        ///
+        /// # Example
+        /// ```rust
+        /// use serde_querystring::DuplicateQS;
+        ///
+        /// let parser = DuplicateQS::parse(b"ids=1&ids=2&ids=3");
+        /// assert_eq!(parser.get_all_as::<u32>(b"ids").transpose(), Ok(Some(vec![1, 2, 3])));
+        /// ```
+        pub fn get_all_as<T: DeserializeOwned>(&self, key: &[u8]) -> Option<Result<Vec<T>, Error>> {
+            let mut scratch = Scratch::default();
+
+            Some(
+                self.pairs
+                    .get(key)?
+                    .iter()
+                    .map(|p| {
+                        let value = p.1.as_ref().map(|v| RawSlice(v.slice()));
+                        T::deserialize(value.into_deserializer(&mut scratch, &self.config))
+                    })
+                    .collect(),
+            )
+        }
+
+        pub(crate) fn into_iter(
+            self,
+        ) -> impl Iterator<
+            Item = (
+                DecodedSlice<'a>,
+                DuplicateValueIter<impl Iterator<Item = RawSlice<'a>> + Clone>,
+            ),
+        > {
+            self.pairs.into_iter().map(|(key, pairs)| {
+                (
+                    DecodedSlice(key),
+                    DuplicateValueIter(
+                        pairs
+                            .into_iter()
+                            .map(|v| RawSlice(v.1.map(|v| v.slice()).unwrap_or_default())),
+                    ),
+                )
+            })
+        }
+
+        /// Removes the pairs whose keys are not fields, and returns them as they were parsed
+        pub(crate) fn take_extra<F>(&mut self, mut is_field: F) -> Vec<ExtraPair<'a>>
+        where
+            F: FnMut(&[u8]) -> bool,
+        {
+            let (pairs, extra): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(&mut self.pairs)
+                .into_iter()
+                .partition(|(key, _)| is_field(key));
+            self.pairs = pairs;
+
+            extra
+                .into_iter()
+                .flat_map(|(key, pairs)| {
+                    pairs.into_iter().map(move |pair| ExtraPair {
+                        position: pair.2,
+                        key: key.clone(),
+                        raw_key: (pair.0.slice(), None),
+                        raw_value: pair.1.map(|v| v.slice()),
+                    })
+                })
+                .collect()
+        }
+    }
+
+    pub(crate) struct DuplicateValueIter<I>(I);
+
+    impl<'a, I> IntoRawSlices<'a> for DuplicateValueIter<I>
+    where
+        I: Iterator<Item = RawSlice<'a>> + Clone,
+    {
+        type SizedIterator = I;
+        type UnSizedIterator = I;
+
+        #[inline]
+        fn into_sized_iterator(self, size: usize) -> Result<I, Error> {
+            if self.0.size_hint().0 == size {
+                Ok(self.0)
+            } else {
+                Err(Error::new(ErrorKind::InvalidLength))
+            }
+        }
+
+        #[inline]
+        fn into_unsized_iterator(self) -> I {
+            self.0
+        }
+
+        #[inline]
+        fn into_single_slice(self) -> RawSlice<'a> {
+            self.0
+                .last()
+                .expect("Iterator has at least one value in it")
+        }
+
+        /// All the values of the key should be null tokens
+        #[inline]
+        fn is_null(&self, scratch: &mut Vec<u8>, config: &Config) -> bool {
+            config.has_null_values() && self.0.clone().all(|value| value.is_null(scratch, config))
+        }
+
+        /// Only repeated keys are a sequence, a single value is visited as it is
+        #[inline]
+        fn is_multi_valued(&self) -> bool {
+            self.0.clone().nth(1).is_some()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod ser {
+    use super::{write_pairs, DuplicateQS};
+
+    impl<'a> DuplicateQS<'a> {
+        /// Writes the re-encoded pairs in the order of their keys in `self`, using the pairs of
+        /// `overrides` for the keys it contains, followed by the pairs of the keys only present
+        /// in `overrides` in their own order
+        pub(crate) fn write_merged(&self, overrides: &DuplicateQS<'_>, output: &mut String) {
+            let mut scratch = Vec::new();
+
+            let mut keys: Vec<_> = self.pairs.iter().collect();
+            keys.sort_by_key(|(_, pairs)| pairs.first().map(|p| p.2));
+            for (key, pairs) in keys {
+                match overrides.pairs.get(key) {
+                    Some(pairs) => write_pairs(key, pairs, &mut scratch, output),
+                    None => write_pairs(key, pairs, &mut scratch, output),
+                }
+            }
+
+            let mut new_keys: Vec<_> = overrides
+                .pairs
+                .iter()
+                .filter(|(key, _)| !self.pairs.contains_key(*key))
+                .collect();
+            new_keys.sort_by_key(|(_, pairs)| pairs.first().map(|p| p.2));
+            for (key, pairs) in new_keys {
+                write_pairs(key, pairs, &mut scratch, output);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::BTreeMap};
+
+    use super::{parse_lossy, DuplicateQS, PairCursor};
+    use crate::{Config, EmptyKeys, ErrorKind};
+
+    #[test]
+    fn parse_pair() {
+        let slice = b"key=value";
+
+        let parser = DuplicateQS::parse(slice);
+
+        assert_eq!(parser.keys(), vec![&Cow::Borrowed(b"key")]);
+        assert_eq!(
+            parser.values(b"key"),
+            Some(vec![Some(Cow::Borrowed("value".as_bytes()))])
+        );
+        assert_eq!(
+            parser.value(b"key"),
+            Some(Some(Cow::Borrowed("value".as_bytes())))
+        );
+    }
+
+    #[test]
+    fn parse_multiple_pairs() {
+        let slice = b"foo=bar&foobar=baz&qux=box";
+
+        let parser = DuplicateQS::parse(slice);
+
+        assert_eq!(
+            parser.values(b"foo"),
+            Some(vec![Some("bar".as_bytes().into())])
+        );
+        assert_eq!(
+            parser.values(b"foobar"),
+            Some(vec![Some("baz".as_bytes().into())])
+        );
+        assert_eq!(
+            parser.values(b"qux"),
+            Some(vec![Some("box".as_bytes().into())])
+        );
+    }
+
+    #[test]
+    fn parse_no_value() {
+        let slice = b"foo&foobar=";
+
+        let parser = DuplicateQS::parse(slice);
+
+        assert_eq!(parser.value(b"key"), None);
+        assert_eq!(parser.values(b"key"), None);
+        assert_eq!(parser.value(b"foo"), Some(None));
+        assert_eq!(parser.values(b"foo"), Some(vec![None]));
+        assert_eq!(
+            parser.values(b"foobar"),
+            Some(vec![Some("".as_bytes().into())])
+        );
+        assert_eq!(parser.value(b"foobar"), Some(Some("".as_bytes().into())));
     }
 
     #[test]
@@ -327,4 +1319,553 @@ mod tests {
 
         assert_eq!(parser.value(b"foo"), Some(Some("".as_bytes().into())));
     }
+
+    #[test]
+    fn parse_newline_terminators() {
+        let slice = b"a=1\r\nb=2\n";
+
+        let parser = DuplicateQS::parse(slice);
+        assert_eq!(
+            parser.value(b"a"),
+            Some(Some("1\r\nb=2\n".as_bytes().into()))
+        );
+
+        let config = Config::default().newline_terminators(true);
+        let parser = DuplicateQS::try_parse(slice, &config).unwrap();
+        assert_eq!(parser.value(b"a"), Some(Some("1".as_bytes().into())));
+        assert_eq!(parser.value(b"b"), Some(Some("2".as_bytes().into())));
+    }
+
+    #[test]
+    fn parse_question_mark_terminators() {
+        let slice = b"a=1?b=2??c";
+
+        let parser = DuplicateQS::parse(slice);
+        assert_eq!(parser.value(b"a"), Some(Some("1?b=2??c".as_bytes().into())));
+
+        let config = Config::default().question_mark_terminators(true);
+        let parser = DuplicateQS::try_parse(slice, &config).unwrap();
+        assert_eq!(parser.value(b"a"), Some(Some("1".as_bytes().into())));
+        assert_eq!(parser.value(b"b"), Some(Some("2".as_bytes().into())));
+        assert_eq!(parser.value(b"c"), Some(None));
+
+        // Encoded question marks are still a part of the value
+        let parser = DuplicateQS::try_parse(b"a=1%3Fb=2", &config).unwrap();
+        assert_eq!(parser.value(b"a"), Some(Some("1?b=2".as_bytes().into())));
+    }
+
+    #[test]
+    fn parse_empty_keys() {
+        let parser = DuplicateQS::parse(b"a=1&=2");
+        assert_eq!(parser.value(b""), Some(Some("2".as_bytes().into())));
+
+        let config = Config::default().reject_empty_keys(true);
+        assert_eq!(
+            DuplicateQS::try_parse(b"=1", &config).err().map(|e| e.kind),
+            Some(ErrorKind::InvalidKey)
+        );
+        assert_eq!(
+            DuplicateQS::try_parse(b"a=1&=2", &config)
+                .err()
+                .map(|e| e.kind),
+            Some(ErrorKind::InvalidKey)
+        );
+
+        // Empty segments are not pairs with an empty key
+        let parser = DuplicateQS::try_parse(b"&&a=1&", &config).unwrap();
+        assert_eq!(parser.value(b"a"), Some(Some("1".as_bytes().into())));
+
+        let config = Config::default().empty_keys(EmptyKeys::Skip);
+        let parser = DuplicateQS::try_parse(b"a=1&=2&=&&b", &config).unwrap();
+        assert_eq!(
+            parser.keys(),
+            vec![
+                &Cow::Borrowed(&b""[..]),
+                &Cow::Borrowed(&b"a"[..]),
+                &Cow::Borrowed(&b"b"[..])
+            ]
+        );
+        assert_eq!(parser.values(b""), Some(vec![None]));
+
+        let config = Config::default().empty_keys(EmptyKeys::Keep);
+        let parser = DuplicateQS::try_parse(b"=2&=", &config).unwrap();
+        assert_eq!(
+            parser.values(b""),
+            Some(vec![
+                Some("2".as_bytes().into()),
+                Some("".as_bytes().into())
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_empty_segments() {
+        let slice = b"a=1&&&b=2&";
+
+        // Empty segments are stored as an empty key without a value by default
+        let parser = DuplicateQS::parse(slice);
+        assert_eq!(
+            parser.keys(),
+            vec![
+                &Cow::Borrowed(&b""[..]),
+                &Cow::Borrowed(&b"a"[..]),
+                &Cow::Borrowed(&b"b"[..])
+            ]
+        );
+        assert_eq!(parser.values(b""), Some(vec![None, None]));
+
+        let config = Config::default().skip_empty_segments(true);
+        let parser = DuplicateQS::try_parse(slice, &config).unwrap();
+        assert_eq!(
+            parser.keys(),
+            vec![&Cow::Borrowed(b"a"), &Cow::Borrowed(b"b")]
+        );
+        assert_eq!(parser.value(b"b"), Some(Some("2".as_bytes().into())));
+
+        // An empty key with a value is not an empty segment
+        let parser = DuplicateQS::try_parse(b"a=1&&=2", &config).unwrap();
+        assert_eq!(parser.value(b""), Some(Some("2".as_bytes().into())));
+    }
+
+    #[test]
+    fn parse_max_params() {
+        let config = Config::default().max_params(2);
+
+        // Empty segments are not counted
+        let parser = DuplicateQS::try_parse(b"a=1&&a=2&", &config).unwrap();
+        assert_eq!(parser.values_count(b"a"), 2);
+
+        assert_eq!(
+            DuplicateQS::try_parse(b"a=1&b&c=", &config)
+                .err()
+                .map(|e| e.kind),
+            Some(ErrorKind::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn parse_max_values_per_key() {
+        let config = Config::default().max_values_per_key(2);
+
+        let parser = DuplicateQS::try_parse(b"a=1&b=1&a=2&b", &config).unwrap();
+        assert_eq!(parser.values_count(b"a"), 2);
+
+        let error = DuplicateQS::try_parse(b"a=1&a=2&b=1&a=3", &config).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidLength);
+        assert_eq!(error.value, "a");
+    }
+
+    #[test]
+    fn parse_max_key_len() {
+        let config = Config::default().max_key_len(3);
+
+        // The limit is checked before decoding
+        let parser = DuplicateQS::try_parse(b"abc=1&b&=2", &config).unwrap();
+        assert_eq!(parser.value(b"abc"), Some(Some("1".as_bytes().into())));
+
+        let error = DuplicateQS::try_parse(b"a=1&%20b=2", &config).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidLength);
+        assert_eq!(error.value, "%20b");
+    }
+
+    #[test]
+    fn parse_max_value_len() {
+        let config = Config::default().max_value_len(3);
+
+        // The limit is checked before decoding
+        let parser = DuplicateQS::try_parse(b"a=abc&b&c=", &config).unwrap();
+        assert_eq!(parser.value(b"a"), Some(Some("abc".as_bytes().into())));
+
+        let error = DuplicateQS::try_parse(b"a=1&b=%20x", &config).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidLength);
+        assert_eq!(error.value, "b");
+    }
+
+    #[test]
+    fn parse_malformed_escapes() {
+        let parser = DuplicateQS::parse(b"a=100%&b%zz=1");
+        assert_eq!(parser.value(b"a"), Some(Some("100%".as_bytes().into())));
+
+        let config = Config::default().reject_malformed_escapes(true);
+        assert!(DuplicateQS::try_parse(b"a=100%25&b%20c=%2", &config).is_err());
+        assert!(DuplicateQS::try_parse(b"a=100%25&b%20c=%zz", &config).is_err());
+        assert!(DuplicateQS::try_parse(b"a%2=1", &config).is_err());
+
+        let error = DuplicateQS::try_parse(b"a=100%", &config).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidEncoding);
+        assert_eq!(error.index, Some(3));
+
+        let parser = DuplicateQS::try_parse(b"a=100%25&b%20c=1+2", &config).unwrap();
+        assert_eq!(parser.value(b"b c"), Some(Some("1 2".as_bytes().into())));
+    }
+
+    #[test]
+    fn parse_without_decoding() {
+        let config = Config::default().percent_decode(false);
+        let parser = DuplicateQS::try_parse(b"a%20b=c%20d&a%20b=e+f", &config).unwrap();
+
+        assert_eq!(parser.keys(), vec![&Cow::Borrowed(&b"a%20b"[..])]);
+        assert_eq!(
+            parser.values(b"a%20b"),
+            Some(vec![
+                Some("c%20d".as_bytes().into()),
+                Some("e+f".as_bytes().into())
+            ])
+        );
+        assert_eq!(parser.value(b"a b"), None);
+    }
+
+    #[test]
+    fn parse_chunks() {
+        let chunks: [&[u8]; 3] = [b"foo=bar", b"f%6Fo=b%20z", b"qux"];
+        let parser = DuplicateQS::from_chunks(chunks.iter().copied());
+        let joined = DuplicateQS::parse(b"foo=bar&f%6Fo=b%20z&qux");
+
+        assert_eq!(parser.keys(), joined.keys());
+        assert_eq!(parser.values(b"foo"), joined.values(b"foo"));
+        assert_eq!(parser.values(b"qux"), joined.values(b"qux"));
+
+        // The whole chunk after `=` is the value
+        let chunks: [&[u8]; 1] = [b"key=a&b=c"];
+        let parser = DuplicateQS::from_chunks(chunks.iter().copied());
+        assert_eq!(parser.value(b"key"), Some(Some("a&b=c".as_bytes().into())));
+    }
+
+    #[test]
+    fn parse_chunks_with_config() {
+        let chunks: [&[u8]; 3] = [b"a+b=c+d", b"a+b=%41", b"e=f"];
+        let config = Config::default().plus_as_space_in_values(false);
+        let parser = DuplicateQS::try_from_chunks(chunks.iter().copied(), &config).unwrap();
+
+        assert_eq!(
+            parser.values(b"a b"),
+            Some(vec![
+                Some("c+d".as_bytes().into()),
+                Some("A".as_bytes().into())
+            ])
+        );
+
+        let config = Config::default().max_params(2);
+        assert!(DuplicateQS::try_from_chunks(chunks.iter().copied(), &config).is_err());
+    }
+
+    #[test]
+    fn values_count() {
+        let parser = DuplicateQS::parse(b"foo=bar&foo&foo=&qux=1");
+
+        assert_eq!(parser.values_count(b"foo"), 3);
+        assert_eq!(parser.values_count(b"qux"), 1);
+        assert_eq!(parser.values_count(b"baz"), 0);
+    }
+
+    #[test]
+    fn raw_value() {
+        let parser = DuplicateQS::parse(b"foo=bar&foo=b%20z&qux");
+
+        assert_eq!(parser.raw_value(b"foo"), Some(Some("b%20z".as_bytes())));
+        assert_eq!(parser.raw_value(b"qux"), Some(None));
+        assert_eq!(parser.raw_value(b"baz"), None);
+    }
+
+    #[test]
+    fn needs_decoding() {
+        let parser = DuplicateQS::parse(b"foo=b%20z&bar=baz&qux&plus=a+b&bad=100%");
+
+        assert_eq!(parser.needs_decoding(b"foo"), Some(true));
+        assert_eq!(
+            parser.value(b"foo"),
+            Some(Some(Cow::Owned(b"b z".to_vec())))
+        );
+        assert_eq!(parser.needs_decoding(b"bar"), Some(false));
+        assert_eq!(parser.value(b"bar"), Some(Some(Cow::Borrowed(&b"baz"[..]))));
+        assert_eq!(parser.needs_decoding(b"plus"), Some(true));
+        assert_eq!(parser.needs_decoding(b"bad"), Some(false));
+        assert_eq!(parser.needs_decoding(b"qux"), Some(false));
+        assert_eq!(parser.needs_decoding(b"baz"), None);
+    }
+
+    #[test]
+    fn decode_into() {
+        let parser = DuplicateQS::parse(b"foo=first&foo=b%20z&bar=baz&qux");
+        let mut buf = Vec::with_capacity(16);
+        let capacity = buf.capacity();
+
+        assert_eq!(parser.decode_into(b"foo", &mut buf), Some(true));
+        assert_eq!(buf, b"b z");
+        assert_eq!(parser.decode_into(b"bar", &mut buf), Some(true));
+        assert_eq!(buf, b"baz");
+        assert_eq!(parser.decode_into(b"qux", &mut buf), Some(false));
+        assert!(buf.is_empty());
+        assert_eq!(parser.decode_into(b"baz", &mut buf), None);
+
+        // The buffer is reused
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn first_and_last_key() {
+        let parser = DuplicateQS::parse(b"b%20z=1&a=2&b+z=3&c&a=4");
+
+        assert_eq!(parser.first_key(), Some(&Cow::Owned(b"b z".to_vec())));
+        assert_eq!(parser.last_key(), Some(&Cow::Borrowed(&b"a"[..])));
+
+        let parser = DuplicateQS::parse(b"");
+        assert_eq!(parser.first_key(), None);
+        assert_eq!(parser.last_key(), None);
+    }
+
+    #[test]
+    fn parse_owned() {
+        let owned = {
+            let buffer = b"foo=bar&f%6Fo=b%20z&qux".to_vec();
+            DuplicateQS::parse_owned(buffer)
+        };
+        let parser = owned.parser();
+
+        assert_eq!(
+            parser.keys(),
+            vec![&Cow::Borrowed(&b"foo"[..]), &Cow::Borrowed(&b"qux"[..])]
+        );
+        assert_eq!(
+            parser.values(b"foo"),
+            Some(vec![
+                Some("bar".as_bytes().into()),
+                Some("b z".as_bytes().into())
+            ])
+        );
+        assert_eq!(parser.raw_value(b"qux"), Some(None));
+        assert_eq!(parser.first_key(), Some(&Cow::Borrowed(&b"foo"[..])));
+    }
+
+    #[test]
+    fn parse_with_observer() {
+        let mut observed = Vec::new();
+        let parser = DuplicateQS::parse_with_observer(
+            b"foo=bar&b%20z&foo=&&qux=1",
+            &Config::default(),
+            |key, value| observed.push((key.to_vec(), value.map(|v| v.to_vec()))),
+        )
+        .unwrap();
+
+        assert_eq!(
+            observed,
+            vec![
+                (b"foo".to_vec(), Some(b"bar".to_vec())),
+                (b"b%20z".to_vec(), None),
+                (b"foo".to_vec(), Some(b"".to_vec())),
+                (b"".to_vec(), None),
+                (b"qux".to_vec(), Some(b"1".to_vec())),
+            ]
+        );
+        assert_eq!(parser.values_count(b"foo"), 2);
+
+        // Skipped segments are not observed
+        let mut count = 0;
+        let config = Config::default().skip_empty_segments(true);
+        DuplicateQS::parse_with_observer(b"a&&b=1&", &config, |_, _| count += 1).unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn as_multimap() {
+        let parser = DuplicateQS::parse(b"foo=bar&b%20z&foo=b%20z&qux=1&foo");
+        let multimap = parser.as_multimap();
+
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            Cow::Borrowed(&b"foo"[..]),
+            vec![
+                Some("bar".as_bytes().into()),
+                Some("b z".as_bytes().into()),
+                None,
+            ],
+        );
+        expected.insert(Cow::Owned(b"b z".to_vec()), vec![None]);
+        expected.insert(
+            Cow::Borrowed(&b"qux"[..]),
+            vec![Some("1".as_bytes().into())],
+        );
+
+        assert_eq!(multimap, expected);
+    }
+
+    #[test]
+    fn debug_format() {
+        let parser = DuplicateQS::parse(b"foo=bar&foo=b%20z&foo&qux=%FF");
+
+        assert_eq!(
+            format!("{:?}", parser),
+            r#"{"foo": [Some("bar"), Some("b z"), None], "qux": [Some("�")]}"#
+        );
+    }
+
+    #[test]
+    fn retain() {
+        let mut parser = DuplicateQS::parse(b"_internal_id=1&foo=bar&_internal%5Fsecret=2&foo=baz");
+
+        parser.retain(|key| !key.starts_with(b"_internal"));
+        assert_eq!(parser.keys(), vec![&Cow::Borrowed(b"foo")]);
+        assert_eq!(parser.values_count(b"foo"), 2);
+    }
+
+    #[test]
+    fn parse_lazy() {
+        let slice = b"foo%5Fbar=1&baz&foo+bar=2&foo_bar=%33&baz=4";
+        let eager = DuplicateQS::parse(slice);
+        let lazy = DuplicateQS::parse_lazy(slice);
+
+        for key in [&b"foo_bar"[..], b"foo bar", b"baz", b"qux"] {
+            assert_eq!(lazy.values(key), eager.values(key));
+            assert_eq!(lazy.value(key), eager.value(key));
+        }
+
+        let config = Config::default().max_values_per_key(1);
+        assert!(DuplicateQS::try_parse_lazy(b"a=1&%61=2", &config).is_err());
+        assert!(DuplicateQS::try_parse_lazy(b"a=1&b=2", &config).is_ok());
+    }
+
+    #[test]
+    fn remove() {
+        let mut parser = DuplicateQS::parse(b"token=a%20b&foo=bar&token");
+
+        assert_eq!(
+            parser.remove(b"token"),
+            Some(vec![Some("a b".as_bytes().into()), None])
+        );
+        assert_eq!(parser.values(b"token"), None);
+        assert_eq!(parser.remove(b"token"), None);
+        assert_eq!(parser.keys(), vec![&Cow::Borrowed(b"foo")]);
+    }
+
+    #[test]
+    fn merge() {
+        let base = DuplicateQS::parse(b"a=1&b=2&a=3");
+        let overrides = DuplicateQS::parse(b"a=4&c=5&b=2");
+
+        let merged = base.merge(overrides);
+        assert_eq!(
+            merged.values(b"a"),
+            Some(vec![
+                Some("1".as_bytes().into()),
+                Some("3".as_bytes().into()),
+                Some("4".as_bytes().into())
+            ])
+        );
+        assert_eq!(merged.values_count(b"b"), 2);
+        assert_eq!(merged.value(b"c"), Some(Some("5".as_bytes().into())));
+
+        // The pairs of the merged querystring come after the pairs of the base
+        let overrides = b"z=1&b=2".to_vec();
+        let merged = DuplicateQS::parse(b"c=3&a=4").merge(DuplicateQS::parse(&overrides));
+        assert_eq!(merged.first_key(), Some(&Cow::Borrowed(&b"c"[..])));
+        assert_eq!(merged.last_key(), Some(&Cow::Borrowed(&b"b"[..])));
+    }
+
+    #[test]
+    fn with_prefix() {
+        let parser = DuplicateQS::parse(b"t1.user=a&t2.user=b&t1.role=x&t1%2Erole=y&t1=z");
+
+        let scoped = parser.with_prefix(b"t1.");
+        assert_eq!(
+            scoped.keys(),
+            vec![&Cow::Borrowed(b"role"), &Cow::Borrowed(b"user")]
+        );
+        assert_eq!(scoped.value(b"user"), Some(Some("a".as_bytes().into())));
+        assert_eq!(scoped.values_count(b"role"), 2);
+        assert_eq!(parser.value(b"t2.user"), Some(Some("b".as_bytes().into())));
+    }
+
+    #[test]
+    fn canonical_string() {
+        let first = DuplicateQS::parse(b"b=%7e&a=1&c%20d=x+y&b=%2F&e");
+        let second = DuplicateQS::parse(b"a=%31&c+d=x%20y&b=~&e&b=/");
+
+        assert_eq!(first.to_canonical_string(), "a=1&b=~&b=%2F&c+d=x+y&e");
+        assert_eq!(first.to_canonical_string(), second.to_canonical_string());
+    }
+
+    #[test]
+    fn parse_ordered() {
+        let pairs = super::parse_pairs_ordered(b"foo=bar&&b%20z&foo=qux&=1&");
+
+        assert_eq!(
+            pairs,
+            vec![
+                (
+                    Cow::Borrowed("foo".as_bytes()),
+                    Some("bar".as_bytes().into())
+                ),
+                (Cow::Owned(b"b z".to_vec()), None),
+                (
+                    Cow::Borrowed("foo".as_bytes()),
+                    Some("qux".as_bytes().into())
+                ),
+                (Cow::Borrowed("".as_bytes()), Some("1".as_bytes().into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn value_or() {
+        let parser = DuplicateQS::parse(b"foo=bar&foo=b%20z&qux&empty=");
+
+        assert_eq!(parser.value_or(b"foo", b"default"), Cow::Borrowed(b"b z"));
+        assert_eq!(parser.value_or(b"empty", b"default"), Cow::Borrowed(b""));
+        assert_eq!(
+            parser.value_or(b"qux", b"default"),
+            Cow::Borrowed(b"default")
+        );
+        assert_eq!(
+            parser.value_or(b"baz", b"default"),
+            Cow::Borrowed(b"default")
+        );
+    }
+
+    #[test]
+    fn pair_cursor() {
+        let slice = b"a=1&&b%20c&a=x+y&";
+        let mut cursor = PairCursor::new(slice);
+
+        let mut pairs = Vec::new();
+        while !cursor.is_done() {
+            let span = cursor.span().unwrap();
+            assert_eq!(cursor.offset(), Some(span.start));
+            pairs.push((span, cursor.key().unwrap(), cursor.value().unwrap()));
+            cursor.advance();
+        }
+
+        assert_eq!(
+            pairs,
+            vec![
+                (0..3, "a".as_bytes().into(), Some("1".as_bytes().into())),
+                (5..10, "b c".as_bytes().into(), None),
+                (11..16, "a".as_bytes().into(), Some("x y".as_bytes().into())),
+            ]
+        );
+        assert_eq!(&slice[11..16], b"a=x+y");
+        assert!(!cursor.advance());
+        assert_eq!(cursor.offset(), None);
+
+        let config = Config::default().newline_terminators(true);
+        let mut cursor = PairCursor::with_config(b"a=1\nb=2", &config);
+        assert!(cursor.advance());
+        assert_eq!(cursor.span(), Some(4..7));
+        assert_eq!(cursor.raw_key(), Some("b".as_bytes()));
+    }
+
+    #[test]
+    fn parse_lossy_keys() {
+        let parser = parse_lossy(b"k%FFey=1&key=2&k%FEey=3");
+
+        assert_eq!(parser.keys(), vec!["key", "k\u{FFFD}ey"]);
+        assert_eq!(parser.value("key"), Some(Some("2".as_bytes().into())));
+        assert_eq!(
+            parser.values("k\u{FFFD}ey"),
+            Some(vec![
+                Some("1".as_bytes().into()),
+                Some("3".as_bytes().into())
+            ])
+        );
+        assert_eq!(parser.value("missing"), None);
+    }
 }
@@ -5,5 +5,8 @@ mod urlencoded;
 
 pub use brackets::BracketsQS;
 pub use delimiter::DelimiterQS;
-pub use duplicate::DuplicateQS;
+pub use duplicate::{
+    parse_lossy, parse_pairs_ordered, DuplicateQS, LazyDuplicateQS, LossyQS, OwnedDuplicateQS,
+    PairCursor,
+};
 pub use urlencoded::UrlEncodedQS;
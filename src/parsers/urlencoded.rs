@@ -1,20 +1,24 @@
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{borrow::Cow, collections::BTreeMap, fmt};
 
-use crate::decode::{parse_bytes, Reference};
+use crate::config::{Config, ParseMode};
+use crate::decode::{decode_if, DebugSlice, Decode, Reference};
+use crate::encode::{encode, write_pair};
+use crate::error::Error;
 
-struct Key<'a>(&'a [u8]);
+struct Key<'a>(&'a [u8], Decode);
 
 impl<'a> Key<'a> {
-    fn parse(slice: &'a [u8]) -> Self {
+    fn parse(slice: &'a [u8], config: &Config) -> Self {
         let mut index = 0;
         while index < slice.len() {
             match slice[index] {
-                b'&' | b'=' => break,
+                b'=' => break,
+                c if config.is_terminator(c) => break,
                 _ => index += 1,
             }
         }
 
-        Self(&slice[..index])
+        Self(&slice[..index], config.key_decode())
     }
 
     fn len(&self) -> usize {
@@ -22,27 +26,31 @@ impl<'a> Key<'a> {
     }
 
     fn decode<'s>(&self, scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+        decode_if(self.0, scratch, self.1)
+    }
+
+    fn slice(&self) -> &'a [u8] {
+        self.0
     }
 }
 
-struct Value<'a>(&'a [u8]);
+struct Value<'a>(&'a [u8], Decode);
 
 impl<'a> Value<'a> {
-    fn parse(slice: &'a [u8]) -> Option<Self> {
-        if *slice.get(0)? == b'&' {
+    fn parse(slice: &'a [u8], config: &Config) -> Option<Self> {
+        if config.is_terminator(*slice.first()?) {
             return None;
         }
 
         let mut index = 1;
         while index < slice.len() {
             match slice[index] {
-                b'&' => break,
+                c if config.is_terminator(c) => break,
                 _ => index += 1,
             }
         }
 
-        Some(Self(&slice[1..index]))
+        Some(Self(&slice[1..index], config.value_decode()))
     }
 
     fn len(&self) -> usize {
@@ -50,18 +58,28 @@ impl<'a> Value<'a> {
     }
 
     fn decode_to<'s>(&self, scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
-        parse_bytes(self.0, scratch)
+        decode_if(self.0, scratch, self.1)
+    }
+
+    fn slice(&self) -> &'a [u8] {
+        self.0
     }
 }
 
-struct Pair<'a>(Key<'a>, Option<Value<'a>>);
+/// A pair with its index in the querystring, which is only used to find the order of the pairs
+/// a struct didn't use when deserializing
+struct Pair<'a>(
+    Key<'a>,
+    Option<Value<'a>>,
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))] usize,
+);
 
 impl<'a> Pair<'a> {
-    fn parse(slice: &'a [u8]) -> Self {
-        let key = Key::parse(slice);
-        let value = Value::parse(&slice[key.len()..]);
+    fn parse(slice: &'a [u8], config: &Config, position: usize) -> Self {
+        let key = Key::parse(slice, config);
+        let value = Value::parse(&slice[key.len()..], config);
 
-        Self(key, value)
+        Self(key, value, position)
     }
 
     /// It report how many chars we should move forward after this pair, to see a new one.
@@ -107,14 +125,38 @@ pub struct UrlEncodedQS<'a> {
 impl<'a> UrlEncodedQS<'a> {
     /// Parse a slice of bytes into a `UrlEncodedQS`
     pub fn parse(slice: &'a [u8]) -> Self {
+        Self::try_parse(slice, &Config::default())
+            .expect("Parsing with the default config doesn't fail")
+    }
+
+    /// Parse a slice of bytes into a `UrlEncodedQS`, using the provided `Config`
+    ///
+    /// It returns an error if the slice violates any of the config's restrictions.
+    pub fn try_parse(slice: &'a [u8], config: &Config) -> Result<Self, Error> {
+        config.validate_for(ParseMode::UrlEncoded)?;
         let mut pairs = BTreeMap::new();
         let mut scratch = Vec::new();
 
         let mut index = 0;
+        let mut params = 0;
+        let mut position = 0;
 
         while index < slice.len() {
-            let pair = Pair::parse(&slice[index..]);
+            let pair = Pair::parse(&slice[index..], config, position);
             index += pair.skip_len();
+            position += 1;
+
+            config.check_pair(pair.0.slice(), pair.1.as_ref().map(|v| v.slice()))?;
+            config.check_key_len(pair.0.slice(), pair.0.slice().len())?;
+            if config.skips_empty_key(pair.0.slice(), pair.1.as_ref().map(|v| v.slice())) {
+                continue;
+            }
+            if !pair.0.slice().is_empty() || pair.1.is_some() {
+                params += 1;
+                config.check_params(params)?;
+            } else if config.skip_empty_segments {
+                continue;
+            }
 
             let decoded_key = pair.0.decode(&mut scratch);
 
@@ -125,7 +167,7 @@ impl<'a> UrlEncodedQS<'a> {
             }
         }
 
-        Self { pairs }
+        Ok(Self { pairs })
     }
 
     /// Returns a vector containing all the keys in querystring.
@@ -146,23 +188,117 @@ impl<'a> UrlEncodedQS<'a> {
             .get(key)
             .map(|p| p.1.as_ref().map(|v| v.decode_to(&mut scratch).into_cow()))
     }
+
+    /// Returns the last value assigned to a key, or `default` if the **key doesn't exist** or
+    /// its last assignment **doesn't have a value**, ex `"&key&"`
+    pub fn value_or(&self, key: &'a [u8], default: &'a [u8]) -> Cow<'a, [u8]> {
+        self.value(key).flatten().unwrap_or(Cow::Borrowed(default))
+    }
+
+    /// Re-encodes the querystring in a canonical form, ex. for cache keys or signatures.
+    ///
+    /// Only the last value of each key is kept, the other rules are the same as
+    /// [`DuplicateQS::to_canonical_string`](crate::DuplicateQS::to_canonical_string).
+    pub fn to_canonical_string(&self) -> String {
+        let mut output = String::new();
+        let mut scratch = Vec::new();
+
+        for (key, pair) in &self.pairs {
+            let value = pair.1.as_ref().map(|v| v.decode_to(&mut scratch));
+            write_pair(&mut output, &encode(key), value.as_deref());
+        }
+
+        output
+    }
+
+    /// Removes the keys not matching the predicate, along with all their values.
+    ///
+    /// The predicate gets the percent decoded keys, ex. to drop the keys starting with `_`
+    /// before deserializing.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        self.pairs.retain(|key, _| f(key));
+    }
+
+    /// Removes a key and returns its last value, percent decoded like `value`.
+    ///
+    /// It returns `None` if the **key doesn't exist**.
+    pub fn remove(&mut self, key: &[u8]) -> Option<Option<Cow<'a, [u8]>>> {
+        let mut scratch = Vec::new();
+
+        Some(
+            self.pairs
+                .remove(key)?
+                .1
+                .map(|v| v.decode_to(&mut scratch).into_cow()),
+        )
+    }
+}
+
+/// Prints the decoded keys and their last value, ex. `{"foo": Some("bar"), "baz": None}`
+impl<'a> fmt::Debug for UrlEncodedQS<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut scratch = Vec::new();
+        let mut map = f.debug_map();
+
+        for (key, pair) in &self.pairs {
+            let value = pair
+                .1
+                .as_ref()
+                .map(|v| v.decode_to(&mut scratch).into_cow());
+
+            map.entry(&DebugSlice(key), &value.as_deref().map(DebugSlice));
+        }
+
+        map.finish()
+    }
 }
 
 #[cfg(feature = "serde")]
 mod de {
+    use std::{borrow::Cow, collections::BTreeMap};
+
     use _serde::Deserialize;
 
+    use crate::config::Config;
     use crate::de::{
-        Error, QSDeserializer,
-        __implementors::{DecodedSlice, RawSlice},
+        __implementors::{DecodedSlice, RawSlice, Scratch},
+        Error, ExtraPair, QSDeserializer,
     };
+    use crate::decode::Decode;
 
-    use super::UrlEncodedQS;
+    use super::{Key, Pair, UrlEncodedQS, Value};
 
     impl<'a> UrlEncodedQS<'a> {
+        /// Creates the parser from already percent decoded pairs, which are never decoded again
+        pub(crate) fn from_decoded_pairs<I>(iter: I) -> Self
+        where
+            I: Iterator<Item = (&'a [u8], &'a [u8])>,
+        {
+            let pairs = iter
+                .enumerate()
+                .map(|(position, (key, value))| {
+                    let pair = Pair(
+                        Key(key, Decode::NONE),
+                        Some(Value(value, Decode::NONE)),
+                        position,
+                    );
+                    (Cow::Borrowed(key), pair)
+                })
+                .collect();
+
+            Self { pairs }
+        }
+
         /// Deserialize the parsed slice into T
         pub fn deserialize<T: Deserialize<'a>>(self) -> Result<T, Error> {
-            T::deserialize(QSDeserializer::new(self.into_iter()))
+            T::deserialize(QSDeserializer::new(
+                self.into_iter(),
+                Config::default(),
+                &mut Scratch::default(),
+            ))
         }
 
         pub(crate) fn into_iter(
@@ -172,6 +308,27 @@ mod de {
                 .into_iter()
                 .map(|(key, pair)| (DecodedSlice(key), pair.1.map(|v| RawSlice(v.0))))
         }
+
+        /// Removes the pairs whose keys are not fields, and returns them as they were parsed
+        pub(crate) fn take_extra<F>(&mut self, mut is_field: F) -> Vec<ExtraPair<'a>>
+        where
+            F: FnMut(&[u8]) -> bool,
+        {
+            let (pairs, extra): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(&mut self.pairs)
+                .into_iter()
+                .partition(|(key, _)| is_field(key));
+            self.pairs = pairs;
+
+            extra
+                .into_iter()
+                .map(|(key, pair)| ExtraPair {
+                    position: pair.2,
+                    key,
+                    raw_key: (pair.0 .0, None),
+                    raw_value: pair.1.map(|v| v.0),
+                })
+                .collect()
+        }
     }
 }
 
@@ -225,4 +382,37 @@ mod tests {
 
         assert_eq!(parser.value(b"foo"), Some(Some("".as_bytes().into())));
     }
+
+    #[test]
+    fn retain() {
+        let mut parser = UrlEncodedQS::parse(b"_internal_id=1&foo=bar&_internal%5Fsecret=2");
+
+        parser.retain(|key| !key.starts_with(b"_internal"));
+        assert_eq!(parser.keys(), vec![&Cow::Borrowed(b"foo")]);
+    }
+
+    #[test]
+    fn canonical_string() {
+        let first = UrlEncodedQS::parse(b"b=%7e&a=1&b=%2F&e");
+        let second = UrlEncodedQS::parse(b"e&a=%31&b=/");
+
+        assert_eq!(first.to_canonical_string(), "a=1&b=%2F&e");
+        assert_eq!(first.to_canonical_string(), second.to_canonical_string());
+    }
+
+    #[test]
+    fn value_or() {
+        let parser = UrlEncodedQS::parse(b"foo=bar&foo=b%20z&qux&empty=");
+
+        assert_eq!(parser.value_or(b"foo", b"default"), Cow::Borrowed(b"b z"));
+        assert_eq!(parser.value_or(b"empty", b"default"), Cow::Borrowed(b""));
+        assert_eq!(
+            parser.value_or(b"qux", b"default"),
+            Cow::Borrowed(b"default")
+        );
+        assert_eq!(
+            parser.value_or(b"baz", b"default"),
+            Cow::Borrowed(b"default")
+        );
+    }
 }
@@ -0,0 +1,434 @@
+mod values;
+
+use std::io;
+
+use _serde::ser::{self, Impossible, Serialize};
+
+pub use crate::config::{Config, KeyOrder, ParseMode};
+pub use crate::encode::PercentEncodeSet;
+pub use crate::error::{Error, ErrorKind};
+
+use crate::parsers::DuplicateQS;
+
+use values::{KeySerializer, ValueSerializer};
+
+macro_rules! unsupported_methods {
+    ($error:expr; $($method:ident($($arg:ty),*) -> $ok:ty,)*) => {
+        $(
+            #[inline]
+            fn $method(self, $(_: $arg),*) -> Result<$ok, Error> {
+                Err($error)
+            }
+        )*
+    };
+}
+pub(crate) use unsupported_methods;
+
+fn unsupported_root() -> Error {
+    Error::new(ErrorKind::InvalidType)
+        .message("only structs and maps are supported at the root level".to_string())
+}
+
+pub(crate) struct QSSerializer<'o> {
+    output: &'o mut String,
+    config: Config,
+    sink: Option<Sink<'o>>,
+}
+
+/// Receives the pairs of each root entry as soon as they are serialized, used by `to_writer`
+pub(crate) struct Sink<'o> {
+    writer: &'o mut dyn io::Write,
+    // Whether any pair is already written, so the next ones start with a `&`
+    written: bool,
+}
+
+impl<'o> Sink<'o> {
+    fn flush(&mut self, output: &mut String) -> Result<(), Error> {
+        if output.is_empty() {
+            return Ok(());
+        }
+
+        let separator: &[u8] = if self.written { b"&" } else { b"" };
+        self.writer
+            .write_all(separator)
+            .and_then(|_| self.writer.write_all(output.as_bytes()))
+            .map_err(|e| Error::new(ErrorKind::Other).message(e.to_string()))?;
+
+        self.written = true;
+        output.clear();
+        Ok(())
+    }
+}
+
+impl<'o> ser::Serializer for QSSerializer<'o> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = MapSerializer<'o>;
+    type SerializeStruct = MapSerializer<'o>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    unsupported_methods! {
+        unsupported_root();
+        serialize_bool(bool) -> (),
+        serialize_i8(i8) -> (),
+        serialize_i16(i16) -> (),
+        serialize_i32(i32) -> (),
+        serialize_i64(i64) -> (),
+        serialize_u8(u8) -> (),
+        serialize_u16(u16) -> (),
+        serialize_u32(u32) -> (),
+        serialize_u64(u64) -> (),
+        serialize_f32(f32) -> (),
+        serialize_f64(f64) -> (),
+        serialize_char(char) -> (),
+        serialize_str(&str) -> (),
+        serialize_bytes(&[u8]) -> (),
+        serialize_unit_variant(&'static str, u32, &'static str) -> (),
+        serialize_seq(Option<usize>) -> Self::SerializeSeq,
+        serialize_tuple(usize) -> Self::SerializeTuple,
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct,
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant,
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant,
+    }
+
+    /// `None` at the root is an empty querystring
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), Error> {
+        Err(unsupported_root())
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(MapSerializer::root(self.output, self.config, self.sink))
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Ok(MapSerializer::root(self.output, self.config, self.sink))
+    }
+}
+
+/// Serializes the entries of a map or the fields of a struct as pairs
+///
+/// At the root level the keys are written as they are, while nested maps(in brackets mode)
+/// write them as subkeys of the prefix, ex. `prefix[key]=value`
+pub(crate) struct MapSerializer<'o> {
+    output: &'o mut String,
+    config: Config,
+    prefix: Option<String>,
+    // The key of the current entry, waiting for its value
+    key: Option<String>,
+    sink: Option<Sink<'o>>,
+    // The keys and the pairs of the entries, written in order by `end` with `KeyOrder::Sorted`
+    entries: Vec<(String, String)>,
+}
+
+impl<'o> MapSerializer<'o> {
+    pub(crate) fn new(output: &'o mut String, config: Config, prefix: Option<String>) -> Self {
+        Self {
+            output,
+            config,
+            prefix,
+            key: None,
+            sink: None,
+            entries: Vec::new(),
+        }
+    }
+
+    fn root(output: &'o mut String, config: Config, sink: Option<Sink<'o>>) -> Self {
+        Self {
+            sink,
+            ..Self::new(output, config, None)
+        }
+    }
+
+    /// Moves the pairs of the last entry to the sink, if there is one
+    fn flush(&mut self) -> Result<(), Error> {
+        match &mut self.sink {
+            Some(sink) => sink.flush(self.output),
+            None => Ok(()),
+        }
+    }
+
+    /// Serializes the value of an entry, it's kept aside to be sorted with `KeyOrder::Sorted`
+    fn serialize_entry<T: ?Sized + Serialize>(
+        &mut self,
+        key: String,
+        value: &T,
+    ) -> Result<(), Error> {
+        match self.config.key_order {
+            KeyOrder::Preserve => {
+                value.serialize(ValueSerializer::new(self.output, self.config, key))?;
+                self.flush()
+            }
+            KeyOrder::Sorted => {
+                let mut pairs = String::new();
+                value.serialize(ValueSerializer::new(&mut pairs, self.config, key.clone()))?;
+                self.entries.push((key, pairs));
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the sorted entries, the entries of the same key keep their order
+    fn write_sorted(mut self) -> Result<(), Error> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut entries = std::mem::take(&mut self.entries);
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, pairs) in entries.iter().filter(|(_, pairs)| !pairs.is_empty()) {
+            if !self.output.is_empty() {
+                self.output.push('&');
+            }
+            self.output.push_str(pairs);
+        }
+        self.flush()
+    }
+
+    fn full_key(&self, key: &[u8]) -> String {
+        match &self.prefix {
+            Some(prefix) => format!(
+                "{}[{}]",
+                prefix,
+                self.config.encode_set.for_keys().encode(key)
+            ),
+            None => self.config.encode_set.for_keys().encode(key),
+        }
+    }
+}
+
+impl<'o> ser::SerializeMap for MapSerializer<'o> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        let key = key.serialize(KeySerializer)?;
+        self.key = Some(self.full_key(&key));
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .expect("Method serialize_value called before serialize_key");
+        self.serialize_entry(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.write_sorted()
+    }
+}
+
+impl<'o> ser::SerializeStruct for MapSerializer<'o> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let key = self.full_key(key.as_bytes());
+        self.serialize_entry(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.write_sorted()
+    }
+}
+
+impl<'o> ser::SerializeStructVariant for MapSerializer<'o> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.write_sorted()
+    }
+}
+
+/// Serialize an instance of type `T` into a query string, laid out for the provided `ParseMode`
+///
+/// Only structs and maps are supported at the root level and `None` values are skipped.
+/// Sequences are written as repeated keys in `Duplicate` mode, as indexed subkeys(`key[0]=value`)
+/// in `Brackets` mode and joined by the delimiter(`key=1,2`) in `Delimiter` mode, nested maps, structs and enum variants with data are only supported in
+/// `Brackets` mode.
+///
+/// # Example
+/// ```rust
+/// # use _serde::Serialize;
+/// use serde_querystring::{to_string, ParseMode};
+///
+/// #[derive(Serialize)]
+/// # #[serde(crate = "_serde")]
+/// struct Query {
+///     name: &'static str,
+///     tags: Vec<u32>,
+/// }
+///
+/// let query = Query { name: "foo bar", tags: vec![1, 2] };
+/// assert_eq!(
+///     to_string(&query, ParseMode::Duplicate).unwrap(),
+///     "name=foo+bar&tags=1&tags=2"
+/// );
+/// ```
+pub fn to_string<T>(value: &T, mode: ParseMode) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    to_string_with_config(value, &Config::new(mode))
+}
+
+/// Serialize an instance of type `T` into a query string, laid out for the mode of the config
+///
+/// Keys and values are percent encoded with the `PercentEncodeSet` of the config, see
+/// [`to_string`] for the supported types.
+///
+/// # Example
+/// ```rust
+/// # use _serde::Serialize;
+/// use serde_querystring::{to_string_with_config, Config, ParseMode, PercentEncodeSet};
+///
+/// #[derive(Serialize)]
+/// # #[serde(crate = "_serde")]
+/// struct Query {
+///     path: &'static str,
+/// }
+///
+/// let config = Config::new(ParseMode::Duplicate).encode_set(PercentEncodeSet::QUERY);
+/// assert_eq!(
+///     to_string_with_config(&Query { path: "/a b" }, &config).unwrap(),
+///     "path=/a%20b"
+/// );
+/// ```
+pub fn to_string_with_config<T>(value: &T, config: &Config) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    config.validate()?;
+    let mut output = String::new();
+    value.serialize(QSSerializer {
+        output: &mut output,
+        config: *config,
+        sink: None,
+    })?;
+    Ok(output)
+}
+
+/// Serialize an instance of type `T` into a query string, writing it to `writer`
+///
+/// The pairs of each field of the root struct(or entry of the root map) are written as soon as
+/// the field is serialized, so only a single field is buffered at a time. See [`to_string`]
+/// for the supported types.
+///
+/// # Example
+/// ```rust
+/// # use _serde::Serialize;
+/// use serde_querystring::{to_writer, Config, ParseMode};
+///
+/// #[derive(Serialize)]
+/// # #[serde(crate = "_serde")]
+/// struct Query {
+///     name: &'static str,
+///     tags: Vec<u32>,
+/// }
+///
+/// let mut output = Vec::new();
+/// let query = Query { name: "foo bar", tags: vec![1, 2] };
+/// to_writer(&mut output, &query, &Config::new(ParseMode::Duplicate)).unwrap();
+/// assert_eq!(output, b"name=foo+bar&tags=1&tags=2");
+/// ```
+pub fn to_writer<W, T>(mut writer: W, value: &T, config: &Config) -> Result<(), Error>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    config.validate()?;
+    let mut output = String::new();
+    value.serialize(QSSerializer {
+        output: &mut output,
+        config: *config,
+        sink: Some(Sink {
+            writer: &mut writer,
+            written: false,
+        }),
+    })
+}
+
+/// Serialize `overrides` in duplicate mode and merge the result with an already parsed querystring
+///
+/// All the values of a key present in `overrides` replace the values of the same key in `base`,
+/// and the keys not present in `base` are appended after its keys. The pairs are re-encoded, the
+/// keys of `base` keep their order and the appended keys follow the order they're serialized in.
+///
+/// # Example
+/// ```rust
+/// # use _serde::Serialize;
+/// use serde_querystring::{to_string_merged, DuplicateQS};
+///
+/// #[derive(Serialize)]
+/// # #[serde(crate = "_serde")]
+/// struct Overrides {
+///     page: u32,
+///     sort: Option<&'static str>,
+/// }
+///
+/// let base = DuplicateQS::parse(b"q=rust%20lang&page=1");
+/// assert_eq!(
+///     to_string_merged(&base, &Overrides { page: 2, sort: Some("asc") }).unwrap(),
+///     "q=rust+lang&page=2&sort=asc"
+/// );
+/// ```
+pub fn to_string_merged<T>(base: &DuplicateQS<'_>, overrides: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    let serialized = to_string(overrides, ParseMode::Duplicate)?;
+    let overrides = DuplicateQS::parse(serialized.as_bytes());
+
+    let mut output = String::new();
+    base.write_merged(&overrides, &mut output);
+    Ok(output)
+}
@@ -0,0 +1,430 @@
+use _serde::ser::{self, Impossible, Serialize};
+
+use crate::config::{Config, ParseMode};
+use crate::error::{Error, ErrorKind};
+
+use super::{unsupported_methods, MapSerializer};
+
+macro_rules! serialize_display {
+    ($($method:ident($ty:ty),)*) => {
+        $(
+            #[inline]
+            fn $method(self, v: $ty) -> Result<(), Error> {
+                self.write(v.to_string().as_bytes())
+            }
+        )*
+    };
+}
+
+/// Serializes the value of a single key, it may write more than one pair for sequences and
+/// nested maps
+pub(crate) struct ValueSerializer<'o> {
+    output: &'o mut String,
+    config: Config,
+    // The already encoded key
+    key: String,
+    // Set for the elements of a sequence in delimiter mode, which are written without their key
+    element: bool,
+}
+
+impl<'o> ValueSerializer<'o> {
+    pub(crate) fn new(output: &'o mut String, config: Config, key: String) -> Self {
+        Self {
+            output,
+            config,
+            key,
+            element: false,
+        }
+    }
+
+    fn write(self, value: &[u8]) -> Result<(), Error> {
+        if self.element {
+            self.config.encode_set.encode_into(value, self.output);
+        } else {
+            self.config
+                .encode_set
+                .write_pair(self.output, &self.key, Some(value));
+        }
+        Ok(())
+    }
+
+    fn subkey(&self, subkey: &str) -> String {
+        format!(
+            "{}[{}]",
+            self.key,
+            self.config.encode_set.for_keys().encode(subkey.as_bytes())
+        )
+    }
+
+    fn unsupported(&self, kind: &str) -> Error {
+        Error::new(ErrorKind::InvalidType)
+            .message(format!(
+                "{} is not supported in {:?} mode",
+                kind, self.config.mode
+            ))
+            .value(self.key.as_bytes())
+    }
+}
+
+impl<'o> ser::Serializer for ValueSerializer<'o> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer<'o>;
+    type SerializeTuple = SeqSerializer<'o>;
+    type SerializeTupleStruct = SeqSerializer<'o>;
+    type SerializeTupleVariant = SeqSerializer<'o>;
+    type SerializeMap = MapSerializer<'o>;
+    type SerializeStruct = MapSerializer<'o>;
+    type SerializeStructVariant = MapSerializer<'o>;
+
+    serialize_display! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.write(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.write(v)
+    }
+
+    /// `None` values are skipped, or written as an empty value with `Config::write_none`
+    fn serialize_none(self) -> Result<(), Error> {
+        if self.config.write_none {
+            return self.write(b"");
+        }
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    /// Units are written as a key without a value, ex. `key`
+    fn serialize_unit(self) -> Result<(), Error> {
+        if self.element {
+            return Ok(());
+        }
+        self.config
+            .encode_set
+            .write_pair(self.output, &self.key, None);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.write(variant.as_bytes())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        match self.config.mode {
+            ParseMode::Brackets => {
+                let key = self.subkey(variant);
+                value.serialize(ValueSerializer::new(self.output, self.config, key))
+            }
+            _ => Err(self.unsupported("an enum variant with data")),
+        }
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        match self.config.mode {
+            ParseMode::Duplicate | ParseMode::Brackets => {
+                Ok(SeqSerializer::new(self.output, self.config, self.key))
+            }
+            ParseMode::Delimiter(_) if !self.element => {
+                Ok(SeqSerializer::new(self.output, self.config, self.key))
+            }
+            ParseMode::Delimiter(_) => Err(self.unsupported("a nested sequence")),
+            _ => Err(self.unsupported("a sequence")),
+        }
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        match self.config.mode {
+            ParseMode::Brackets => {
+                let key = self.subkey(variant);
+                Ok(SeqSerializer::new(self.output, self.config, key))
+            }
+            _ => Err(self.unsupported("an enum variant with data")),
+        }
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        match self.config.mode {
+            ParseMode::Brackets => Ok(MapSerializer::new(self.output, self.config, Some(self.key))),
+            _ => Err(self.unsupported("a nested map")),
+        }
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        match self.config.mode {
+            ParseMode::Brackets => Ok(MapSerializer::new(self.output, self.config, Some(self.key))),
+            _ => Err(self.unsupported("a nested struct")),
+        }
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        match self.config.mode {
+            ParseMode::Brackets => {
+                let key = self.subkey(variant);
+                Ok(MapSerializer::new(self.output, self.config, Some(key)))
+            }
+            _ => Err(self.unsupported("an enum variant with data")),
+        }
+    }
+}
+
+/// Serializes the elements of a sequence, as repeated keys in duplicate mode, as indexed subkeys
+/// in brackets mode, ex. `key[0]=value`, or joined by the delimiter in delimiter mode, ex. `key=1,2`
+pub(crate) struct SeqSerializer<'o> {
+    output: &'o mut String,
+    config: Config,
+    key: String,
+    index: usize,
+    // The encoded elements joined by the delimiter, only used in delimiter mode
+    joined: String,
+}
+
+impl<'o> SeqSerializer<'o> {
+    fn new(output: &'o mut String, config: Config, key: String) -> Self {
+        Self {
+            output,
+            config,
+            key,
+            index: 0,
+            joined: String::new(),
+        }
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        if let ParseMode::Delimiter(delimiter) = self.config.mode {
+            return self.delimited_element(delimiter, value);
+        }
+
+        let key = match self.config.mode {
+            ParseMode::Brackets => format!("{}[{}]", self.key, self.index),
+            _ => self.key.clone(),
+        };
+        self.index += 1;
+
+        value.serialize(ValueSerializer::new(self.output, self.config, key))
+    }
+
+    fn delimited_element<T: ?Sized + Serialize>(
+        &mut self,
+        delimiter: u8,
+        value: &T,
+    ) -> Result<(), Error> {
+        if self.index > 0 {
+            self.joined.push(char::from(delimiter));
+        }
+        self.index += 1;
+
+        // The delimiter is always encoded inside the elements, so they split back the same way
+        let mut config = self.config;
+        config.encode_set = config.encode_set.encode_byte(delimiter);
+
+        value.serialize(ValueSerializer {
+            output: &mut self.joined,
+            config,
+            key: self.key.clone(),
+            element: true,
+        })
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if let ParseMode::Delimiter(_) = self.config.mode {
+            self.config
+                .encode_set
+                .write_encoded_pair(self.output, &self.key, &self.joined);
+        }
+        Ok(())
+    }
+}
+
+macro_rules! impl_seq_serializer {
+    ($($trait:ident::$method:ident,)*) => {
+        $(
+            impl<'o> ser::$trait for SeqSerializer<'o> {
+                type Ok = ();
+                type Error = Error;
+
+                fn $method<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+                    self.element(value)
+                }
+
+                fn end(self) -> Result<(), Error> {
+                    self.finish()
+                }
+            }
+        )*
+    };
+}
+
+impl_seq_serializer! {
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field,
+}
+
+fn unsupported_key() -> Error {
+    Error::new(ErrorKind::InvalidKey).message(
+        "map keys should be strings, numbers, booleans, chars or unit variants".to_string(),
+    )
+}
+
+macro_rules! serialize_display_key {
+    ($($method:ident($ty:ty),)*) => {
+        $(
+            #[inline]
+            fn $method(self, v: $ty) -> Result<Vec<u8>, Error> {
+                Ok(v.to_string().into_bytes())
+            }
+        )*
+    };
+}
+
+/// Serializes a map key into its unencoded bytes
+pub(crate) struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<Vec<u8>, Error>;
+    type SerializeTuple = Impossible<Vec<u8>, Error>;
+    type SerializeTupleStruct = Impossible<Vec<u8>, Error>;
+    type SerializeTupleVariant = Impossible<Vec<u8>, Error>;
+    type SerializeMap = Impossible<Vec<u8>, Error>;
+    type SerializeStruct = Impossible<Vec<u8>, Error>;
+    type SerializeStructVariant = Impossible<Vec<u8>, Error>;
+
+    serialize_display_key! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+    }
+
+    unsupported_methods! {
+        unsupported_key();
+        serialize_none() -> Vec<u8>,
+        serialize_unit() -> Vec<u8>,
+        serialize_unit_struct(&'static str) -> Vec<u8>,
+        serialize_seq(Option<usize>) -> Self::SerializeSeq,
+        serialize_tuple(usize) -> Self::SerializeTuple,
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct,
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant,
+        serialize_map(Option<usize>) -> Self::SerializeMap,
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct,
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant,
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(v.to_vec())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Vec<u8>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Vec<u8>, Error> {
+        Ok(variant.as_bytes().to_vec())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Vec<u8>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Vec<u8>, Error> {
+        Err(unsupported_key())
+    }
+}
@@ -1,7 +1,8 @@
 //! These tests are meant for the `BracketsQS` method
 
 use _serde::Deserialize;
-use serde_querystring::de::{from_bytes, ParseMode};
+use serde_querystring::de::{from_bytes, from_str_with_config, ParseMode};
+use serde_querystring::{Config, ErrorKind};
 
 /// It is a helper struct we use to test primitive types
 /// as we don't support anything beside maps/structs at the root level
@@ -86,6 +87,15 @@ fn deserialize_sequence() {
         from_bytes(b"value[0]=1&value[1]=3&value[2]=1337", ParseMode::Brackets),
         Ok(p!((true, "3", 1337)))
     );
+
+    // percent encoded indexes
+    assert_eq!(
+        from_bytes(
+            b"value[%31]=3&value[%30]=1&value[1%30]=1337",
+            ParseMode::Brackets
+        ),
+        Ok(p!(vec![1, 3, 1337]))
+    );
 }
 
 #[test]
@@ -253,6 +263,75 @@ fn deserialize_enums() {
     );
 }
 
+#[test]
+fn deserialize_enums_in_sequence() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    enum Shape {
+        Circle { r: u32 },
+        Square { s: u32 },
+        Line(u32, u32),
+        Point,
+    }
+
+    assert_eq!(
+        from_bytes(
+            b"value[0][Circle][r]=1&value[1][Square][s]=2",
+            ParseMode::Brackets
+        ),
+        Ok(p!(vec![Shape::Circle { r: 1 }, Shape::Square { s: 2 }]))
+    );
+
+    // Elements are ordered by their index, and direct values can be mixed with nested ones
+    assert_eq!(
+        from_bytes(
+            b"value[3][Line][1]=20&value[1]=Point&value[3][Line][0]=10&value[0][Circle][r]=5",
+            ParseMode::Brackets
+        ),
+        Ok(p!(vec![
+            Shape::Circle { r: 5 },
+            Shape::Point,
+            Shape::Line(10, 20)
+        ]))
+    );
+}
+
+#[test]
+fn deserialize_ignored_any() {
+    use _serde::de::IgnoredAny;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Ignored {
+        value: u32,
+        ignored: IgnoredAny,
+    }
+
+    // Nested groups are skipped as a whole
+    assert_eq!(
+        from_bytes(
+            b"ignored[a][0]=1&ignored[b][c]=%FF&value=1&ignored=x",
+            ParseMode::Brackets
+        ),
+        Ok(Ignored {
+            value: 1,
+            ignored: IgnoredAny
+        })
+    );
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    enum Enum {
+        Ignored(IgnoredAny),
+    }
+
+    // Enum variants without values
+    assert_eq!(
+        from_bytes(b"value=Ignored", ParseMode::Brackets),
+        Ok(p!(Enum::Ignored(IgnoredAny)))
+    );
+}
+
 #[test]
 fn deserialize_invalid_sequence() {
     // array length
@@ -275,6 +354,13 @@ fn deserialize_invalid_sequence() {
         ParseMode::Brackets
     )
     .is_err());
+
+    // non numeric indexes, reported decoded
+    let error = from_bytes::<Primitive<Vec<u32>>>(b"value[0]=1&value[%78]=2", ParseMode::Brackets)
+        .unwrap_err();
+    assert_eq!(error.kind, ErrorKind::InvalidNumber);
+    assert_eq!(error.message, "expected numeric index, found 'x'");
+    assert_eq!(error.value, "x");
 }
 
 #[test]
@@ -301,6 +387,97 @@ fn deserialize_maps_of_maps() {
     );
 }
 
+#[test]
+fn deserialize_values_mixed_with_subkeys() {
+    use std::collections::HashMap;
+
+    // Scalars get the last direct value, wherever the subkeys are
+    for input in [&b"value=1&value[bar]=2"[..], b"value[bar]=2&value=1"] {
+        assert_eq!(from_bytes(input, ParseMode::Brackets), Ok(p!(1)));
+        assert_eq!(from_bytes(input, ParseMode::Brackets), Ok(p!("1")));
+    }
+
+    // Maps and structs only see the subkeys
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Inner {
+        bar: u32,
+    }
+
+    let mut map = HashMap::new();
+    map.insert("bar".to_string(), 2);
+    assert_eq!(
+        from_bytes(b"value=1&value[bar]=2", ParseMode::Brackets),
+        Ok(p!(map))
+    );
+    assert_eq!(
+        from_bytes(b"value[bar]=2&value=1", ParseMode::Brackets),
+        Ok(p!(Inner { bar: 2 }))
+    );
+
+    // Without direct values, the last value is used
+    assert_eq!(
+        from_bytes(b"value[bar]=2&value[baz]=3", ParseMode::Brackets),
+        Ok(p!(3))
+    );
+}
+
+#[test]
+fn deserialize_maps_as_sequences_of_pairs() {
+    assert_eq!(
+        from_bytes(b"value[a]=1&value[b]=2&value[c]=3", ParseMode::Brackets),
+        Ok(p!(vec![("a", 1), ("b", 2), ("c", 3)]))
+    );
+
+    // In submission order, with nested values
+    assert_eq!(
+        from_bytes(
+            b"value[y]=1&value[x][]=2&value[x][]=3&value[0]=4",
+            ParseMode::Brackets
+        ),
+        Ok(p!(vec![
+            ("y".to_string(), vec![1]),
+            ("x".to_string(), vec![2, 3]),
+            ("0".to_string(), vec![4])
+        ]))
+    );
+
+    // Indexes are still values
+    assert_eq!(
+        from_bytes(b"value[1]=b&value[0]=a", ParseMode::Brackets),
+        Ok(p!(vec!["a", "b"]))
+    );
+}
+
+#[test]
+fn deserialize_maps_of_sequences() {
+    let map = map! {
+        "status" => vec!["a", "b"],
+        "type" => vec!["x"],
+    };
+
+    assert_eq!(
+        from_bytes(
+            b"value[status]=a&value[status]=b&value[type]=x",
+            ParseMode::Brackets
+        ),
+        Ok(p!(map))
+    );
+
+    // Repeated subkeys with indices are still ordered by index
+    let map = map! {
+        "status" => vec!["a", "b", "c"],
+    };
+
+    assert_eq!(
+        from_bytes(
+            b"value[status][1]=b&value[status][0]=a&value[status][2]=c",
+            ParseMode::Brackets
+        ),
+        Ok(p!(map))
+    );
+}
+
 #[test]
 fn deserialize_pencoded_brackets() {
     let map = map! {
@@ -409,3 +586,454 @@ fn deserialize_option() {
         Ok(expected)
     );
 }
+
+/// The examples from the `serde_qs` docs
+#[test]
+fn deserialize_qs_compatible() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Address {
+        city: String,
+        postcode: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct QueryParams {
+        id: u8,
+        name: String,
+        address: Address,
+        phone: u32,
+        user_ids: Vec<u8>,
+    }
+
+    let expected = QueryParams {
+        id: 42,
+        name: "Acme".to_string(),
+        phone: 12345,
+        address: Address {
+            city: "Carrot City".to_string(),
+            postcode: "12345".to_string(),
+        },
+        user_ids: vec![1, 2, 3, 4],
+    };
+    let config = Config::qs_compatible();
+
+    assert_eq!(
+        from_str_with_config(
+            "name=Acme&id=42&phone=12345&address[postcode]=12345&\
+            address[city]=Carrot+City&user_ids[0]=1&user_ids[1]=2&\
+            user_ids[2]=3&user_ids[3]=4",
+            &config
+        ),
+        Ok(expected)
+    );
+
+    // Indexes give the order, and encoded brackets work as brackets
+    let res: QueryParams = from_str_with_config(
+        "name=Acme&id=42&phone=12345&address%5Bpostcode%5D=12345&\
+        address%5Bcity%5D=Carrot+City&user_ids[3]=4&user_ids[1]=2&\
+        user_ids[0]=1&user_ids[2]=3",
+        &config,
+    )
+    .unwrap();
+    assert_eq!(res.user_ids, vec![1, 2, 3, 4]);
+    assert_eq!(res.address.city, "Carrot City");
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Nested {
+        a: std::collections::HashMap<String, Vec<u8>>,
+    }
+
+    assert!(from_str_with_config::<Nested>("a[b][c][d][e][f]=1", &config).is_err());
+}
+
+/// Common patterns of the `qs` package of Node.js
+#[test]
+fn deserialize_node_qs_compatible() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Filter {
+        status: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        a: Vec<u32>,
+        b: Vec<String>,
+        filter: Filter,
+        list: String,
+    }
+
+    let config = Config::node_qs_compatible();
+    assert_eq!(
+        from_str_with_config(
+            "a[]=1&a[]=2&b[0]=x&b[1]=y%20z&filter[status]=open&list=1,2",
+            &config
+        ),
+        Ok(Query {
+            a: vec![1, 2],
+            b: vec!["x".to_string(), "y z".to_string()],
+            filter: Filter {
+                status: "open".to_string()
+            },
+            list: "1,2".to_string(),
+        })
+    );
+}
+
+#[test]
+fn get_as() {
+    use serde_querystring::BracketsQS;
+
+    let parser = BracketsQS::parse(b"page=2&filter[page]=3&all=false");
+    assert_eq!(parser.get_as::<u32>(b"page"), Some(Ok(2)));
+    assert_eq!(parser.get_as::<bool>(b"all"), Some(Ok(false)));
+
+    // Only direct values are used
+    assert_eq!(parser.get_as::<u32>(b"filter"), None);
+
+    // The values are deserialized with the config of the parser
+    let config = Config::new(ParseMode::Brackets).bool_format(serde_querystring::BoolFormat::YesNo);
+    let parser = BracketsQS::try_parse(b"all=yes&filter[all]=no", &config).unwrap();
+    assert_eq!(parser.get_as::<bool>(b"all"), Some(Ok(true)));
+    assert_eq!(
+        parser.sub_values(b"filter").unwrap().get_as::<bool>(b"all"),
+        Some(Ok(false))
+    );
+}
+
+#[test]
+fn deserialize_mixed_encoded_brackets() {
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        filter: HashMap<String, Vec<u32>>,
+    }
+
+    let expected = Query {
+        filter: [("ids".to_string(), vec![1, 2, 3])].into_iter().collect(),
+    };
+    assert_eq!(
+        from_bytes(
+            b"filter%5Bids%5D[0]=1&filter[ids]%5B1%5D=2&filter%5bids%5d%5b2%5d=3",
+            ParseMode::Brackets
+        ),
+        Ok(expected)
+    );
+}
+
+#[test]
+fn deserialize_adjacently_tagged_enums() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde", tag = "type", content = "data")]
+    enum Shape {
+        Circle { r: u32 },
+        Line(u32, u32),
+        Size(u32),
+        Point,
+    }
+
+    assert_eq!(
+        from_bytes(b"type=Circle&data[r]=1", ParseMode::Brackets),
+        Ok(Shape::Circle { r: 1 })
+    );
+    assert_eq!(
+        from_bytes(b"data[r]=1&type=Circle", ParseMode::Brackets),
+        Ok(Shape::Circle { r: 1 })
+    );
+    assert_eq!(
+        from_bytes(b"type=Line&data[0]=1&data[1]=2", ParseMode::Brackets),
+        Ok(Shape::Line(1, 2))
+    );
+    assert_eq!(
+        from_bytes(b"type=Size&data=3", ParseMode::Brackets),
+        Ok(Shape::Size(3))
+    );
+    assert_eq!(
+        from_bytes(b"type=Point", ParseMode::Brackets),
+        Ok(Shape::Point)
+    );
+
+    // As the value of a field
+    assert_eq!(
+        from_bytes(b"value[type]=Circle&value[data][r]=5", ParseMode::Brackets),
+        Ok(p!(Shape::Circle { r: 5 }))
+    );
+}
+
+#[test]
+fn deserialize_internally_tagged_enums() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde", tag = "type")]
+    enum Shape {
+        Circle { r: u32 },
+        Labeled { name: String },
+        Point,
+    }
+
+    // Serde buffers the fields as strings, unless the types are detected
+    let config = Config::new(ParseMode::Brackets).detect_types(true);
+    assert_eq!(
+        from_str_with_config("type=Circle&r=1", &config),
+        Ok(Shape::Circle { r: 1 })
+    );
+    assert_eq!(
+        from_str_with_config("r=1&type=Circle", &config),
+        Ok(Shape::Circle { r: 1 })
+    );
+    assert_eq!(
+        from_bytes::<Shape>(b"type=Circle&r=1", ParseMode::Brackets)
+            .unwrap_err()
+            .kind,
+        ErrorKind::InvalidType
+    );
+
+    assert_eq!(
+        from_bytes(b"type=Labeled&name=a%20b", ParseMode::Brackets),
+        Ok(Shape::Labeled {
+            name: "a b".to_string()
+        })
+    );
+    assert_eq!(
+        from_bytes(b"type=Point", ParseMode::Brackets),
+        Ok(Shape::Point)
+    );
+
+    // As the value of a field and in sequences
+    assert_eq!(
+        from_str_with_config("value[type]=Circle&value[r]=5", &config),
+        Ok(p!(Shape::Circle { r: 5 }))
+    );
+    assert_eq!(
+        from_str_with_config(
+            "value[0][type]=Point&value[1][r]=2&value[1][type]=Circle",
+            &config
+        ),
+        Ok(p!(vec![Shape::Point, Shape::Circle { r: 2 }]))
+    );
+}
+
+#[test]
+fn deserialize_seq_order() {
+    use serde_querystring::SeqOrder;
+
+    assert_eq!(
+        from_bytes(b"value[2]=x&value[0]=y", ParseMode::Brackets),
+        Ok(p!(vec!["y", "x"]))
+    );
+
+    let config = Config::new(ParseMode::Brackets).seq_order(SeqOrder::BySubmission);
+    assert_eq!(
+        from_str_with_config("value[2]=x&value[0]=y", &config),
+        Ok(p!(vec!["x", "y"]))
+    );
+
+    // Nested values with the same index are still grouped
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Item {
+        a: u32,
+        b: u32,
+    }
+    assert_eq!(
+        from_str_with_config(
+            "value[1][a]=1&value[0][a]=2&value[1][b]=3&value[0][b]=4",
+            &config
+        ),
+        Ok(p!(vec![Item { a: 1, b: 3 }, Item { a: 2, b: 4 }]))
+    );
+}
+
+#[test]
+fn deserialize_fill_seq_gaps() {
+    let config = Config::new(ParseMode::Brackets).fill_seq_gaps(100);
+
+    assert_eq!(
+        from_str_with_config("value[0]=x&value[5]=y", &config),
+        Ok(p!(vec![
+            Some("x".to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some("y".to_string())
+        ]))
+    );
+    assert_eq!(
+        from_str_with_config("value[2]=y", &config),
+        Ok(p!(vec!["", "", "y"]))
+    );
+
+    // Collapsed by default
+    assert_eq!(
+        from_bytes(b"value[0]=x&value[5]=y", ParseMode::Brackets),
+        Ok(p!(vec![Some("x".to_string()), Some("y".to_string())]))
+    );
+
+    // Indexes over the limit
+    assert_eq!(
+        from_str_with_config::<Primitive<Vec<Option<String>>>>("value[100]=x", &config)
+            .unwrap_err()
+            .kind,
+        ErrorKind::InvalidLength
+    );
+
+    // The indexes are ignored when keeping the submission order
+    let config = config.seq_order(serde_querystring::SeqOrder::BySubmission);
+    assert_eq!(
+        from_str_with_config::<Primitive<Vec<String>>>("value[0]=x", &config)
+            .unwrap_err()
+            .kind,
+        ErrorKind::InvalidConfig
+    );
+}
+
+#[test]
+fn deserialize_nested_sequences_of_structs() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Item<'a> {
+        sku: &'a str,
+        qty: u32,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Order<'a> {
+        #[serde(borrow)]
+        items: Vec<Item<'a>>,
+        note: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query<'a> {
+        #[serde(borrow)]
+        orders: Vec<Order<'a>>,
+    }
+
+    assert_eq!(
+        from_bytes(
+            b"orders[0][items][0][sku]=A&orders[0][items][0][qty]=2",
+            ParseMode::Brackets
+        ),
+        Ok(Query {
+            orders: vec![Order {
+                items: vec![Item { sku: "A", qty: 2 }],
+                note: None
+            }]
+        })
+    );
+
+    // Interleaved keys, unordered indexes and a sibling field on each level
+    assert_eq!(
+        from_bytes(
+            b"orders[1][items][0][sku]=C&orders[0][items][1][qty]=1&orders[0][note]=gift\
+              &orders[0][items][0][sku]=A&orders[1][items][0][qty]=5&orders[0][items][1][sku]=B\
+              &orders[0][items][0][qty]=2",
+            ParseMode::Brackets
+        ),
+        Ok(Query {
+            orders: vec![
+                Order {
+                    items: vec![Item { sku: "A", qty: 2 }, Item { sku: "B", qty: 1 }],
+                    note: Some("gift".to_string())
+                },
+                Order {
+                    items: vec![Item { sku: "C", qty: 5 }],
+                    note: None
+                }
+            ]
+        })
+    );
+}
+
+#[test]
+fn deserialize_indexmap_in_submission_order() {
+    use indexmap::IndexMap;
+
+    let map: IndexMap<String, u32> = from_bytes(b"c=3&a=1&b=2&a=4", ParseMode::Brackets).unwrap();
+    assert_eq!(
+        map.into_iter().collect::<Vec<_>>(),
+        vec![
+            ("c".to_string(), 3),
+            ("a".to_string(), 4),
+            ("b".to_string(), 2)
+        ]
+    );
+
+    #[derive(Debug, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Nested {
+        map: IndexMap<String, u32>,
+    }
+
+    let nested: Nested = from_bytes(b"map[z]=1&map[x]=2&map[y]=3", ParseMode::Brackets).unwrap();
+    assert_eq!(nested.map.keys().collect::<Vec<_>>(), vec!["z", "x", "y"]);
+
+    // The keys of a merged querystring come after the keys of the base, whatever their buffers
+    let overrides = b"c=3&a=4".to_vec();
+    let merged = serde_querystring::BracketsQS::parse(b"b=1&a=2")
+        .merge(serde_querystring::BracketsQS::parse(&overrides));
+    let map: IndexMap<String, u32> = merged.deserialize().unwrap();
+    assert_eq!(
+        map.into_iter().collect::<Vec<_>>(),
+        vec![
+            ("b".to_string(), 1),
+            ("a".to_string(), 4),
+            ("c".to_string(), 3)
+        ]
+    );
+}
+
+#[test]
+fn deserialize_map_of_structs() {
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Person {
+        name: String,
+        age: u32,
+    }
+
+    let people: HashMap<String, Person> = from_bytes(
+        b"user[name]=a&user[age]=3&admin[name]=b&admin[age]=4",
+        ParseMode::Brackets,
+    )
+    .unwrap();
+
+    assert_eq!(people.len(), 2);
+    assert_eq!(
+        people["user"],
+        Person {
+            name: "a".to_string(),
+            age: 3
+        }
+    );
+    assert_eq!(
+        people["admin"],
+        Person {
+            name: "b".to_string(),
+            age: 4
+        }
+    );
+
+    #[derive(Debug, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Team {
+        members: HashMap<String, Person>,
+    }
+
+    let team: Team = from_bytes(
+        b"members[lead][name]=c&members[lead][age]=5",
+        ParseMode::Brackets,
+    )
+    .unwrap();
+    assert_eq!(team.members["lead"].age, 5);
+}
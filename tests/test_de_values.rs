@@ -1,7 +1,11 @@
 //! These tests are common between different deserialization methods
 
 use _serde::Deserialize;
-use serde_querystring::de::{from_bytes, from_str, ErrorKind, ParseMode};
+use serde_querystring::de::{
+    from_bytes, from_bytes_deny_extra, from_bytes_loose, from_bytes_partial, from_str,
+    from_str_with_config, ErrorKind, ParseMode,
+};
+use serde_querystring::{BoolFormat, Config, ConfigError, EmptyKeys, FloatFormat, RawQuery};
 
 /// It is a helper struct we use to test primitive types
 /// as we don't support anything beside maps/structs at the root level
@@ -134,6 +138,78 @@ fn deserialize_float_valid() {
     );
 }
 
+#[test]
+fn deserialize_hex_integers() {
+    check_result(|mode| from_str("value=-5", mode), Ok(p!(-5_i32)));
+
+    let hex = |mode| Config::new(mode).hex_integers(true);
+    check_result(
+        |mode| from_str_with_config("value=0xFF", &hex(mode)),
+        Ok(p!(255_u32)),
+    );
+    check_result(
+        |mode| from_str_with_config("value=-0X1a", &hex(mode)),
+        Ok(p!(-26_i32)),
+    );
+    check_result(
+        |mode| from_str_with_config("value=-5", &hex(mode)),
+        Ok(p!(-5_i32)),
+    );
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<u8>>("value=0x100", &hex(mode))
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidNumber,
+    );
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<u32>>("value=0x-1", &hex(mode))
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidNumber,
+    );
+
+    // Decimal only by default
+    check_result(
+        |mode| {
+            from_str::<Primitive<u32>>("value=0xFF", mode)
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidNumber,
+    );
+}
+
+/// Check the floats in scientific notation
+#[test]
+fn deserialize_float_exponent() {
+    check_result(|mode| from_str("value=1.5e-3", mode), Ok(p!(1.5e-3_f64)));
+    check_result(|mode| from_str("value=2E3", mode), Ok(p!(2000_f32)));
+
+    let no_exponent = |mode| Config::new(mode).float_format(FloatFormat::NoExponent);
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<f64>>("value=1.5e-3", &no_exponent(mode))
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidNumber,
+    );
+    check_result(
+        |mode| from_str_with_config("value=-1.5", &no_exponent(mode)),
+        Ok(p!(-1.5_f64)),
+    );
+
+    // Integers are not affected
+    check_result(
+        |mode| from_str_with_config("value=15", &no_exponent(mode)),
+        Ok(p!(15_u32)),
+    );
+}
+
 /// Check if different boolean idents work
 #[test]
 fn deserialize_bool() {
@@ -158,6 +234,47 @@ fn deserialize_bool() {
     );
 }
 
+#[test]
+fn deserialize_bool_yes_no() {
+    let yes_no = |mode| Config::new(mode).bool_format(BoolFormat::YesNo);
+
+    check_result(
+        |mode| from_str_with_config("value=Yes", &yes_no(mode)),
+        Ok(p!(true)),
+    );
+    check_result(
+        |mode| from_str_with_config("value=NO", &yes_no(mode)),
+        Ok(p!(false)),
+    );
+
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<bool>>("value=maybe", &yes_no(mode))
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidBoolean,
+    );
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<bool>>("value=true", &yes_no(mode))
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidBoolean,
+    );
+
+    // Not accepted by default
+    check_result(
+        |mode| {
+            from_str::<Primitive<bool>>("value=yes", mode)
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidBoolean,
+    );
+}
+
 /// Check if we can directly deserialize non percent encoded values to str
 #[test]
 fn deserialize_str() {
@@ -213,6 +330,26 @@ fn deserialize_strings() {
     );
 }
 
+/// Shared strings are deserialized through the owned `String` path
+#[test]
+fn deserialize_shared_strings() {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    check_result(
+        |mode| from_str("value=foo", mode),
+        Ok(p!(Arc::<str>::from("foo"))),
+    );
+    check_result(
+        |mode| from_str("value=f%20o+o", mode),
+        Ok(p!(Rc::<str>::from("f o o"))),
+    );
+    check_result(
+        |mode| from_str("value=%D8%A8", mode),
+        Ok(p!(Box::<str>::from("ب"))),
+    );
+}
+
 #[test]
 fn deserialize_bytes() {
     use serde_bytes::Bytes;
@@ -280,6 +417,62 @@ fn deserialize_byte_vecs() {
     );
 }
 
+#[test]
+fn deserialize_borrowed_bytes() {
+    use std::borrow::Cow;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Borrowed<'a> {
+        #[serde(borrow)]
+        value: &'a [u8],
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct MaybeBorrowed<'a> {
+        #[serde(borrow)]
+        value: Cow<'a, [u8]>,
+    }
+
+    // Unencoded values point into the original input
+    let input = b"value=binary";
+    check_result(
+        |mode| from_bytes::<Borrowed>(input, mode).map(|b| b.value.as_ptr()),
+        Ok(input[6..].as_ptr()),
+    );
+
+    // Decoded values can't be borrowed
+    check_result(
+        |mode| {
+            from_bytes::<Borrowed>(b"value=bin%61ry", mode)
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidType,
+    );
+
+    // But Cow falls back to an owned value for them
+    check_result(
+        |mode| {
+            matches!(
+                from_bytes::<MaybeBorrowed>(input, mode).unwrap().value,
+                Cow::Borrowed(b"binary")
+            )
+        },
+        true,
+    );
+    check_result(
+        |mode| {
+            matches!(
+                from_bytes::<MaybeBorrowed>(b"value=bin%61ry", mode).unwrap().value,
+                Cow::Owned(v) if v == b"binary"
+            )
+        },
+        true,
+    );
+}
+
 /// Check if unit enums work as keys and values
 #[test]
 fn deserialize_unit_enum() {
@@ -317,34 +510,693 @@ fn deserialize_option() {
     );
 
     check_result(
-        |mode| from_str::<Primitive<Option<u32>>>("value=", mode).is_err(),
-        true,
+        |mode| from_str::<Primitive<Option<u32>>>("value=", mode).is_err(),
+        true,
+    );
+    check_result(
+        |mode| from_str::<Primitive<Option<u32>>>("value=1337&value=", mode).is_err(),
+        true,
+    );
+}
+
+#[test]
+fn deserialize_optional_root() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Root {
+        a: u32,
+    }
+
+    check_result(|mode| from_str::<Option<Root>>("", mode), Ok(None));
+    check_result(|mode| from_str("a=1", mode), Ok(Some(Root { a: 1 })));
+}
+
+#[test]
+fn deserialize_unit_root() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Empty;
+
+    check_result(|mode| from_bytes::<()>(b"", mode), Ok(()));
+    check_result(|mode| from_bytes::<Empty>(b"", mode), Ok(Empty));
+    check_result(
+        |mode| from_bytes::<()>(b"a=1", mode).unwrap_err().kind,
+        ErrorKind::InvalidType,
+    );
+}
+
+#[test]
+fn deserialize_new_type() {
+    #[derive(Debug, Deserialize, Eq, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct NewType(i32);
+
+    check_result(
+        |mode| from_str("value=-2500000", mode),
+        Ok(p!(NewType(-2_500_000))),
+    );
+}
+
+#[test]
+fn deserialize_extra_ampersands() {
+    check_result(|mode| from_str("&&value=bar", mode), Ok(p!("bar")));
+    check_result(|mode| from_str("value=bar&&", mode), Ok(p!("bar")));
+    check_result(
+        |mode| from_str("value=bar&&&value=baz", mode),
+        Ok(p!("baz")),
+    );
+}
+
+#[test]
+fn deserialize_newline_terminators() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Lines {
+        a: u32,
+        b: u32,
+    }
+
+    check_result(
+        |mode| from_str_with_config("a=1\r\nb=2\n", &Config::new(mode).newline_terminators(true)),
+        Ok(Lines { a: 1, b: 2 }),
+    );
+
+    // Newlines are a part of the value by default
+    check_result(|mode| from_str("value=line\n", mode), Ok(p!("line\n")));
+}
+
+#[test]
+fn deserialize_question_mark_terminators() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Dirty {
+        a: u32,
+        b: u32,
+    }
+
+    check_result(
+        |mode| {
+            from_str_with_config(
+                "?a=1?b=2",
+                &Config::new(mode).question_mark_terminators(true),
+            )
+        },
+        Ok(Dirty { a: 1, b: 2 }),
+    );
+
+    // Question marks are a part of the value by default
+    check_result(|mode| from_str("value=1?b=2", mode), Ok(p!("1?b=2")));
+}
+
+#[test]
+fn deserialize_empty_keys() {
+    check_result(
+        |mode| {
+            from_str::<Primitive<u32>>("value=1&=2", mode)
+                .unwrap()
+                .value
+        },
+        1,
+    );
+
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<u32>>(
+                "value=1&=2",
+                &Config::new(mode).reject_empty_keys(true),
+            )
+            .unwrap_err()
+            .kind
+        },
+        ErrorKind::InvalidKey,
+    );
+
+    // Skipped pairs don't override the value
+    check_result(
+        |mode| {
+            from_str_with_config::<std::collections::HashMap<String, u32>>(
+                "value=1&=2",
+                &Config::new(mode).empty_keys(EmptyKeys::Skip),
+            )
+        },
+        Ok(map! {"value".to_string() => 1}),
+    );
+    check_result(
+        |mode| {
+            from_str_with_config::<std::collections::HashMap<String, u32>>(
+                "value=1&=2",
+                &Config::new(mode).empty_keys(EmptyKeys::Keep),
+            )
+        },
+        Ok(map! {"value".to_string() => 1, "".to_string() => 2}),
+    );
+}
+
+#[test]
+fn deserialize_max_seq_len() {
+    let cases = [
+        (ParseMode::Duplicate, "value=1&value=2&value=3"),
+        (ParseMode::Delimiter(b'|'), "value=1|2|3"),
+        (ParseMode::Brackets, "value[0]=1&value[1]=2&value[2]=3"),
+    ];
+
+    for (mode, query) in cases {
+        assert_eq!(
+            from_str_with_config(query, &Config::new(mode).max_seq_len(3)),
+            Ok(p!(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            from_str_with_config::<Primitive<Vec<u32>>>(query, &Config::new(mode).max_seq_len(2))
+                .unwrap_err()
+                .kind,
+            ErrorKind::InvalidLength
+        );
+    }
+}
+
+#[test]
+fn deserialize_strict_limits() {
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<u32>>("value=1&a=2", &Config::new(mode).max_params(1))
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidLength,
+    );
+
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<String>>(
+                "value=100%",
+                &Config::new(mode).reject_malformed_escapes(true),
+            )
+            .unwrap_err()
+            .kind
+        },
+        ErrorKind::InvalidEncoding,
+    );
+
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<String>>(
+                "value=12345",
+                &Config::new(mode).max_value_len(4),
+            )
+            .unwrap_err()
+            .kind
+        },
+        ErrorKind::InvalidLength,
+    );
+    check_result(
+        |mode| from_str_with_config("value=1234", &Config::new(mode).max_value_len(4)),
+        Ok(p!(1234)),
+    );
+
+    // Depth is only limited in brackets mode, the other modes reject it
+    let config = Config::new(ParseMode::Brackets).max_depth(0);
+    assert_eq!(
+        from_str_with_config::<Primitive<u32>>("value=1&a[b]=2", &config)
+            .unwrap_err()
+            .kind,
+        ErrorKind::InvalidKey
+    );
+    for mode in [
+        ParseMode::UrlEncoded,
+        ParseMode::Duplicate,
+        ParseMode::Delimiter(b'|'),
+    ] {
+        let config = config.mode(mode);
+        assert_eq!(config.validate(), Err(ConfigError::DepthWithoutBrackets));
+        assert_eq!(
+            from_str_with_config::<Primitive<u32>>("value=1", &config)
+                .unwrap_err()
+                .kind,
+            ErrorKind::InvalidConfig
+        );
+    }
+}
+
+/// `deserialize_any` on a leaf gives a string, whatever the value looks like
+#[test]
+fn deserialize_any_leaf() {
+    mod probe {
+        use std::fmt;
+
+        use _serde::de::{Deserializer, Error, SeqAccess, Visitor};
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<String, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct ProbeVisitor;
+
+            impl<'de> Visitor<'de> for ProbeVisitor {
+                type Value = String;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                    formatter.write_str("anything")
+                }
+
+                fn visit_str<E: Error>(self, v: &str) -> Result<String, E> {
+                    Ok(format!("str:{}", v))
+                }
+
+                fn visit_u64<E: Error>(self, v: u64) -> Result<String, E> {
+                    Ok(format!("u64:{}", v))
+                }
+
+                fn visit_bool<E: Error>(self, v: bool) -> Result<String, E> {
+                    Ok(format!("bool:{}", v))
+                }
+
+                fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<String, A::Error> {
+                    let mut values = Vec::new();
+                    while let Some(value) = seq.next_element::<String>()? {
+                        values.push(value);
+                    }
+                    Ok(format!("seq:{}", values.join(",")))
+                }
+            }
+
+            deserializer.deserialize_any(ProbeVisitor)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        #[serde(with = "probe")]
+        number: String,
+        #[serde(with = "probe")]
+        flag: String,
+    }
+
+    let query = b"number=42&flag=true";
+    for mode in [
+        ParseMode::UrlEncoded,
+        ParseMode::Delimiter(b'|'),
+        ParseMode::Brackets,
+    ] {
+        assert_eq!(
+            from_bytes(query, mode),
+            Ok(Query {
+                number: "str:42".to_string(),
+                flag: "str:true".to_string(),
+            })
+        );
+    }
+
+    // Repeated keys in duplicate mode are sequences of strings
+    assert_eq!(
+        from_bytes(b"number=42&flag=true&flag=%20", ParseMode::Duplicate),
+        Ok(Query {
+            number: "str:42".to_string(),
+            flag: "seq:true, ".to_string(),
+        })
+    );
+}
+
+#[test]
+fn deserialize_raw_str() {
+    /// A timestamp in rfc3339 format
+    #[derive(Debug, PartialEq)]
+    struct Timestamp(time::OffsetDateTime);
+
+    impl<'de> Deserialize<'de> for Timestamp {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: _serde::Deserializer<'de>,
+        {
+            time::serde::rfc3339::deserialize(deserializer).map(Self)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        #[serde(deserialize_with = "serde_querystring::raw_str")]
+        since: Timestamp,
+        #[serde(deserialize_with = "serde_querystring::raw_str")]
+        raw: String,
+        decoded: String,
+    }
+
+    check_result(
+        |mode| {
+            from_str(
+                "since=2023-01-01T10:00:00+03:30&raw=a+b%20c&decoded=a+b%20c",
+                mode,
+            )
+        },
+        Ok(Query {
+            since: Timestamp(time::macros::datetime!(2023-01-01 10:00:00 +03:30)),
+            raw: "a+b%20c".to_string(),
+            decoded: "a b c".to_string(),
+        }),
+    );
+
+    // The `+` is a space otherwise
+    check_result(
+        |mode| {
+            from_str::<Primitive<Timestamp>>("value=2023-01-01T10:00:00+03:30", mode)
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::Other,
+    );
+}
+
+/// Keys without percent encoding are borrowed from the input
+#[test]
+fn deserialize_borrowed_keys() {
+    use std::collections::HashMap;
+
+    let input = b"a=1&bb=2";
+    let in_input = |key: &str| input.as_ptr_range().contains(&key.as_ptr());
+
+    check_result(
+        |mode| {
+            let map: HashMap<&str, u32> = from_bytes(input, mode).unwrap();
+            let mut keys: Vec<_> = map.keys().map(|key| (*key, in_input(key))).collect();
+            keys.sort_unstable();
+            keys
+        },
+        vec![("a", true), ("bb", true)],
+    );
+
+    // Nested keys as well
+    let input = b"map[key]=1";
+    let map: HashMap<&str, HashMap<&str, u32>> = from_bytes(input, ParseMode::Brackets).unwrap();
+    let key = *map["map"].keys().next().unwrap();
+    assert_eq!(key, "key");
+    assert!(input.as_ptr_range().contains(&key.as_ptr()));
+
+    // Encoded keys can't be borrowed
+    check_result(
+        |mode| {
+            from_bytes::<HashMap<&str, u32>>(b"a%20b=1", mode)
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidType,
+    );
+}
+
+#[test]
+fn deserialize_raw_query() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query<'a> {
+        #[serde(borrow)]
+        next: RawQuery<'a>,
+        #[serde(borrow)]
+        empty: RawQuery<'a>,
+        decoded: String,
+    }
+
+    check_result(
+        |mode| from_str("next=%2Fa%3Fb%3D1+2&empty=&decoded=%2Fa+b", mode),
+        Ok(Query {
+            next: RawQuery(b"%2Fa%3Fb%3D1+2"),
+            empty: RawQuery(b""),
+            decoded: "/a b".to_string(),
+        }),
+    );
+}
+
+#[test]
+fn deserialize_case_insensitive_enums() {
+    let config = |mode| Config::new(mode).case_insensitive_enums(true);
+
+    check_result(
+        |mode| from_str_with_config("value=left", &config(mode)),
+        Ok(p!(Side::Left)),
+    );
+    check_result(
+        |mode| from_str_with_config("value=GOD", &config(mode)),
+        Ok(p!(Side::God)),
+    );
+    check_result(
+        |mode| {
+            from_str_with_config::<Primitive<Side>>("value=up", &config(mode))
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::Other,
+    );
+
+    // Exact matches only by default
+    check_result(
+        |mode| from_str::<Primitive<Side>>("value=left", mode).is_err(),
+        true,
+    );
+}
+
+#[test]
+fn deserialize_enum_index() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Status {
+        #[serde(deserialize_with = "serde_querystring::enum_index")]
+        value: Side,
+    }
+
+    check_result(
+        |mode| from_str("value=2", mode),
+        Ok(Status { value: Side::God }),
+    );
+    check_result(
+        |mode| from_str("value=0", mode),
+        Ok(Status { value: Side::Left }),
+    );
+
+    // Out of range and non numeric values
+    check_result(
+        |mode| from_str::<Status>("value=3", mode).unwrap_err().kind,
+        ErrorKind::Other,
+    );
+    check_result(
+        |mode| from_str::<Status>("value=Left", mode).unwrap_err().kind,
+        ErrorKind::InvalidNumber,
+    );
+}
+
+#[test]
+fn deserialize_unix_seconds() {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Since {
+        #[serde(deserialize_with = "serde_querystring::unix_seconds")]
+        value: SystemTime,
+    }
+
+    check_result(
+        |mode| from_str("value=1700000000", mode),
+        Ok(Since {
+            value: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        }),
+    );
+    check_result(
+        |mode| from_str("value=-60", mode),
+        Ok(Since {
+            value: UNIX_EPOCH - Duration::from_secs(60),
+        }),
+    );
+
+    // Out of range and non numeric values
+    check_result(
+        |mode| from_str::<Since>("value=18446744073709551615", mode).is_err(),
+        true,
+    );
+    check_result(
+        |mode| from_str::<Since>("value=yesterday", mode).unwrap_err().kind,
+        ErrorKind::InvalidNumber,
+    );
+}
+
+#[test]
+fn deserialize_partial() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Known {
+        a: u32,
+        #[serde(rename = "c")]
+        renamed: String,
+    }
+
+    check_result(
+        |mode| from_bytes_partial(b"d=y+z&a=1&b=x&c=foo", &Config::new(mode)),
+        Ok((
+            Known {
+                a: 1,
+                renamed: "foo".to_string(),
+            },
+            "d=y+z&b=x".to_string(),
+        )),
+    );
+
+    // The pairs are kept as they were, without decoding them
+    check_result(
+        |mode| {
+            from_bytes_partial::<Known>(b"b=%7e&a=1&x%20y&c=foo&e[f]=+", &Config::new(mode))
+                .unwrap()
+                .1
+        },
+        "b=%7e&x%20y&e[f]=+".to_string(),
+    );
+
+    // Repeated keys are only overwritten in urlencoded and delimiter modes
+    let partial = |mode| {
+        from_bytes_partial::<Known>(b"b=1&a=1&c=foo&b=2", &Config::new(mode))
+            .unwrap()
+            .1
+    };
+    assert_eq!(partial(ParseMode::UrlEncoded), "b=2");
+    assert_eq!(partial(ParseMode::Delimiter(b'|')), "b=2");
+    assert_eq!(partial(ParseMode::Duplicate), "b=1&b=2");
+    assert_eq!(partial(ParseMode::Brackets), "b=1&b=2");
+
+    // Maps use all the pairs
+    check_result(
+        |mode| {
+            from_bytes_partial::<std::collections::HashMap<String, String>>(
+                b"a=1&b=2",
+                &Config::new(mode),
+            )
+            .unwrap()
+            .1
+        },
+        String::new(),
+    );
+
+    // Nothing left
+    check_result(
+        |mode| {
+            from_bytes_partial::<Known>(b"a=1&c=foo", &Config::new(mode))
+                .unwrap()
+                .1
+        },
+        String::new(),
+    );
+}
+
+#[test]
+fn deserialize_deny_extra() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Known {
+        a: u32,
+        #[serde(rename = "c")]
+        renamed: String,
+    }
+
+    check_result(
+        |mode| from_bytes_deny_extra(b"a=1&c=foo", &Config::new(mode)),
+        Ok(Known {
+            a: 1,
+            renamed: "foo".to_string(),
+        }),
+    );
+
+    check_result(
+        |mode| {
+            let error = from_bytes_deny_extra::<Known>(b"a=1&b%20x=2&c=foo", &Config::new(mode))
+                .unwrap_err();
+            (error.kind, error.message, error.value)
+        },
+        (
+            ErrorKind::Other,
+            "unknown field `b x`, expected `a` or `c`".to_string(),
+            "b x".to_string(),
+        ),
+    );
+
+    // The first unknown key fails, before the values are parsed
+    check_result(
+        |mode| {
+            from_bytes_deny_extra::<Known>(b"z=1&a=x&c=foo&b=2", &Config::new(mode))
+                .unwrap_err()
+                .value
+        },
+        "z".to_string(),
     );
+
+    // Maps accept all the keys
     check_result(
-        |mode| from_str::<Primitive<Option<u32>>>("value=1337&value=", mode).is_err(),
-        true,
+        |mode| {
+            from_bytes_deny_extra::<std::collections::HashMap<String, String>>(
+                b"a=1&b=2",
+                &Config::new(mode),
+            )
+            .map(|map| map.len())
+        },
+        Ok(2),
     );
 }
 
 #[test]
-fn deserialize_new_type() {
-    #[derive(Debug, Deserialize, Eq, PartialEq)]
+fn deserialize_ignored_any() {
+    #[derive(Debug, PartialEq, Deserialize)]
     #[serde(crate = "_serde")]
-    struct NewType(i32);
+    struct Ignored {
+        value: u32,
+        ignored: _serde::de::IgnoredAny,
+    }
 
+    // Ignored values are not decoded nor validated
     check_result(
-        |mode| from_str("value=-2500000", mode),
-        Ok(p!(NewType(-2_500_000))),
+        |mode| from_str("value=1&ignored=%FF%FE&ignored", mode),
+        Ok(Ignored {
+            value: 1,
+            ignored: _serde::de::IgnoredAny,
+        }),
+    );
+
+    check_result(
+        |mode| from_str::<_serde::de::IgnoredAny>("value=1&ignored=%FF", mode),
+        Ok(_serde::de::IgnoredAny),
     );
 }
 
 #[test]
-fn deserialize_extra_ampersands() {
-    check_result(|mode| from_str("&&value=bar", mode), Ok(p!("bar")));
-    check_result(|mode| from_str("value=bar&&", mode), Ok(p!("bar")));
+fn deserialize_lenient_numbers() {
+    // strict by default
     check_result(
-        |mode| from_str("value=bar&&&value=baz", mode),
-        Ok(p!("baz")),
+        |mode| {
+            from_str::<Primitive<i32>>("value=+5", mode)
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidNumber,
+    );
+    check_result(
+        |mode| {
+            from_str::<Primitive<u32>>("value=1_000", mode)
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidNumber,
+    );
+
+    let lenient = |mode| Config::new(mode).lenient_numbers(true);
+    check_result(
+        |mode| from_str_with_config("value=+5", &lenient(mode)),
+        Ok(p!(5i32)),
+    );
+    check_result(
+        |mode| from_str_with_config("value=1_000", &lenient(mode)),
+        Ok(p!(1000u32)),
+    );
+    check_result(
+        |mode| from_str_with_config("value=+1_000.5", &lenient(mode)),
+        Ok(p!(1000.5f64)),
+    );
+    check_result(
+        |mode| from_str_with_config("value=-1_0", &lenient(mode)),
+        Ok(p!(-10i64)),
     );
 }
 
@@ -576,6 +1428,100 @@ fn deserialize_invalid_precent_decoding() {
     );
 }
 
+#[test]
+fn deserialize_validate_utf8() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        a: String,
+    }
+
+    let validated = |mode| Config::new(mode).validate_utf8(true);
+    check_result(
+        |mode| {
+            let error = from_str_with_config::<Query>("a=%FF", &validated(mode)).unwrap_err();
+            (error.kind, error.value)
+        },
+        (ErrorKind::InvalidEncoding, "a".to_string()),
+    );
+
+    // Even if the pair is not used
+    check_result(
+        |mode| {
+            from_str_with_config::<Query>("a=1&b%FF=2", &validated(mode))
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidEncoding,
+    );
+    check_result(
+        |mode| from_str_with_config("a=%C3%A9", &validated(mode)),
+        Ok(Query {
+            a: "\u{e9}".to_string(),
+        }),
+    );
+    // Keys are decoded with the options of the keys
+    check_result(
+        |mode| {
+            let config = validated(mode).plus_as_space_in_values(false);
+            from_str_with_config::<Query>("a=1&b+%FF=2", &config)
+                .unwrap_err()
+                .kind
+        },
+        ErrorKind::InvalidEncoding,
+    );
+}
+
+#[test]
+fn deserialize_without_decoding() {
+    use std::collections::HashMap;
+
+    let config = |mode| Config::new(mode).percent_decode(false);
+    check_result(
+        |mode| from_str_with_config::<HashMap<String, String>>("a%20b=c%20d", &config(mode)),
+        Ok(vec![("a%20b".to_string(), "c%20d".to_string())]
+            .into_iter()
+            .collect()),
+    );
+
+    // Bytes that are not valid utf-8 are not produced by decoding
+    check_result(
+        |mode| from_str_with_config::<HashMap<String, String>>("a=%FF+1", &config(mode)),
+        Ok(vec![("a".to_string(), "%FF+1".to_string())]
+            .into_iter()
+            .collect()),
+    );
+}
+
+#[test]
+fn deserialize_plus_in_keys() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        #[serde(rename = "a+b")]
+        ab: String,
+    }
+
+    let config = |mode| Config::new(mode).plus_as_space_in_keys(false);
+    check_result(
+        |mode| from_str_with_config("a+b=c+d", &config(mode)),
+        Ok(Query {
+            ab: "c d".to_string(),
+        }),
+    );
+
+    // The `+` is still replaced in keys by default
+    check_result(|mode| from_str::<Query>("a+b=c", mode).is_err(), true);
+
+    let config = |mode| Config::new(mode).plus_as_space_in_values(false);
+    check_result(
+        |mode| from_str_with_config("a%2Bb=c+d", &config(mode)),
+        Ok(Query {
+            ab: "c+d".to_string(),
+        }),
+    );
+}
+
 #[test]
 fn deserialize_error_test() {
     check_result(
@@ -605,3 +1551,350 @@ fn deserialize_error_test() {
         ErrorKind::InvalidBoolean,
     );
 }
+
+#[test]
+fn error_classification() {
+    let error = from_str::<Primitive<i32>>("value=12foo", ParseMode::Duplicate).unwrap_err();
+    assert!(error.is_type());
+    assert!(!error.is_syntax());
+    assert!(!error.is_length());
+
+    let config = Config::new(ParseMode::Duplicate).reject_malformed_escapes(true);
+    let error = from_str_with_config::<Primitive<i32>>("value=1%", &config).unwrap_err();
+    assert!(error.is_syntax());
+    assert!(!error.is_type());
+
+    let config = Config::new(ParseMode::Duplicate).max_params(1);
+    let error = from_str_with_config::<Primitive<i32>>("value=1&value=2", &config).unwrap_err();
+    assert!(error.is_length());
+    assert!(!error.is_syntax());
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(crate = "_serde")]
+struct Listing {
+    name: String,
+    page: u32,
+    limit: u32,
+    active: bool,
+}
+
+#[test]
+fn deserialize_loose() {
+    check_result(
+        |mode| {
+            let (value, failures) = from_bytes_loose::<Listing>(
+                b"name=shoes&page=2x&limit=10&active=on",
+                &Config::new(mode),
+            )
+            .unwrap();
+            let kinds: Vec<_> = failures.into_iter().map(|error| error.kind).collect();
+            (value, kinds)
+        },
+        (
+            Listing {
+                name: "shoes".to_string(),
+                page: 0,
+                limit: 10,
+                active: true,
+            },
+            vec![ErrorKind::InvalidNumber],
+        ),
+    );
+
+    // Options hold the default of their type when their value fails
+    check_result(
+        |mode| {
+            let (value, failures) =
+                from_bytes_loose::<Primitive<Option<u32>>>(b"value=zz", &Config::new(mode))
+                    .unwrap();
+            (value, failures.len())
+        },
+        (Primitive::new(Some(0)), 1),
+    );
+    check_result(
+        |mode| {
+            from_bytes_loose::<Primitive<Option<bool>>>(b"value=on", &Config::new(mode))
+                .unwrap()
+                .0
+        },
+        Primitive::new(Some(true)),
+    );
+
+    // Other errors inside options are not recovered
+    check_result(
+        |mode| {
+            from_bytes_loose::<Primitive<Option<Side>>>(b"value=Up", &Config::new(mode)).is_err()
+        },
+        true,
+    );
+
+    // Missing fields are not recovered
+    check_result(
+        |mode| from_bytes_loose::<Listing>(b"name=shoes", &Config::new(mode)).is_err(),
+        true,
+    );
+}
+
+#[test]
+fn deserialize_null_tokens() {
+    check_result(
+        |mode| {
+            let config = Config::new(mode).null_tokens(&["null", ""]);
+            (
+                from_str_with_config("value=null", &config),
+                from_str_with_config("value=", &config),
+                from_str_with_config("value=nu%6Cl", &config),
+                from_str_with_config("value=3", &config),
+            )
+        },
+        (
+            Ok(p!(None, Option<u32>)),
+            Ok(p!(None, Option<u32>)),
+            Ok(p!(None, Option<u32>)),
+            Ok(p!(Some(3), Option<u32>)),
+        ),
+    );
+
+    // Only options are affected
+    check_result(
+        |mode| {
+            let config = Config::new(mode).null_tokens(&["null"]);
+            from_str_with_config("value=null", &config)
+        },
+        Ok(p!("null".to_string())),
+    );
+    check_result(
+        |mode| from_str::<Primitive<Option<String>>>("value=null", mode),
+        Ok(p!(Some("null".to_string()))),
+    );
+}
+
+#[test]
+fn deserialize_empty_numbers_as_none() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        n: Option<u32>,
+        f: Option<f64>,
+        b: Option<bool>,
+        s: Option<String>,
+    }
+
+    check_result(
+        |mode| {
+            let config = Config::new(mode).empty_numbers_as_none(true);
+            from_str_with_config("n=&f=&b=&s=", &config)
+        },
+        Ok(Query {
+            n: None,
+            f: None,
+            b: None,
+            s: None,
+        }),
+    );
+
+    // Only options are affected
+    check_result(
+        |mode| {
+            let config = Config::new(mode).empty_numbers_as_none(true);
+            from_str_with_config("value=", &config)
+        },
+        Ok(p!(String::new())),
+    );
+    check_result(
+        |mode| {
+            let config = Config::new(mode).empty_numbers_as_none(true);
+            from_str_with_config("value=3", &config)
+        },
+        Ok(p!(Some(3), Option<u32>)),
+    );
+
+    // Including types failing on empty values, ex. non zero integers and newtypes around numbers
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Meters(u32);
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Wrapped {
+        y: Option<std::num::NonZeroU32>,
+        m: Option<Meters>,
+    }
+
+    check_result(
+        |mode| {
+            let config = Config::new(mode).empty_numbers_as_none(true);
+            from_str_with_config("y=&m=", &config)
+        },
+        Ok(Wrapped { y: None, m: None }),
+    );
+    check_result(
+        |mode| {
+            let config = Config::new(mode).empty_numbers_as_none(true);
+            from_str_with_config("y=2&m=3", &config)
+        },
+        Ok(Wrapped {
+            y: std::num::NonZeroU32::new(2),
+            m: Some(Meters(3)),
+        }),
+    );
+
+    // Values which are not empty still fail
+    check_result(
+        |mode| {
+            let config = Config::new(mode).empty_numbers_as_none(true);
+            from_str_with_config::<Primitive<Option<u32>>>("value=x", &config).is_err()
+        },
+        true,
+    );
+
+    // Disabled by default
+    check_result(
+        |mode| from_str::<Primitive<Option<u32>>>("value=", mode).is_err(),
+        true,
+    );
+    check_result(
+        |mode| from_str("value=", mode),
+        Ok(p!(Some(String::new()), Option<String>)),
+    );
+}
+
+#[test]
+fn deserialize_detected_types() {
+    use serde_json::{json, Value};
+
+    let config = Config::new(ParseMode::UrlEncoded).detect_types(true);
+    assert_eq!(
+        from_str_with_config::<Value>("a=5&b=true&c=x&d=-1.5&e=18446744073709551615", &config),
+        Ok(json!({"a": 5, "b": true, "c": "x", "d": -1.5, "e": u64::MAX}))
+    );
+
+    // Values which only look like numbers after decoding, and not finite floats, stay strings
+    assert_eq!(
+        from_str_with_config::<Value>("a=inf&b=%35&c=True&d=", &config),
+        Ok(json!({"a": "inf", "b": "5", "c": "True", "d": ""}))
+    );
+
+    let config = Config::new(ParseMode::Brackets).detect_types(true);
+    assert_eq!(
+        from_str_with_config::<Value>("a[b]=1&a[c]=false", &config),
+        Ok(json!({"a": {"b": 1, "c": false}}))
+    );
+
+    // Strings by default
+    assert_eq!(
+        from_str_with_config::<Value>("a=5&b=true", &Config::new(ParseMode::UrlEncoded)),
+        Ok(json!({"a": "5", "b": "true"}))
+    );
+}
+
+#[test]
+fn deserialize_from_pairs_iter() {
+    use serde_querystring::from_pairs_iter;
+    use std::borrow::Cow;
+
+    // The pairs are already decoded
+    check_result(
+        |mode| {
+            let pairs = vec![
+                (Cow::Borrowed("value"), Cow::Borrowed("a+b%20")),
+                (Cow::Borrowed("other"), Cow::Borrowed("1")),
+            ];
+            from_pairs_iter(pairs, &Config::new(mode))
+        },
+        Ok(p!("a+b%20".to_string())),
+    );
+
+    let pairs = vec![
+        (Cow::Borrowed("value"), Cow::Owned("1".to_string())),
+        (Cow::Owned("value".to_string()), Cow::Borrowed("2")),
+    ];
+    assert_eq!(
+        from_pairs_iter(pairs, &Config::new(ParseMode::Duplicate)),
+        Ok(p!(vec![1, 2]))
+    );
+
+    let pairs = vec![
+        (Cow::Borrowed("value[1][%5B]"), Cow::Borrowed("b")),
+        (Cow::Borrowed("value[0][%5B]"), Cow::Borrowed("a")),
+    ];
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Item {
+        #[serde(rename = "%5B")]
+        name: String,
+    }
+    assert_eq!(
+        from_pairs_iter(pairs, &Config::new(ParseMode::Brackets)),
+        Ok(p!(vec![
+            Item {
+                name: "a".to_string()
+            },
+            Item {
+                name: "b".to_string()
+            }
+        ]))
+    );
+}
+
+#[test]
+fn deserialize_dates() {
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        date: NaiveDate,
+        time: NaiveTime,
+        at: NaiveDateTime,
+        until: Option<NaiveDate>,
+    }
+
+    let date = NaiveDate::from_ymd_opt(2023, 1, 15).unwrap();
+    let time = NaiveTime::from_hms_opt(10, 30, 0).unwrap();
+    let expected = Query {
+        date,
+        time,
+        at: date.and_time(time),
+        until: Some(date),
+    };
+
+    check_result(
+        |mode| {
+            from_str(
+                "date=2023-01-15&time=10:30:00&at=2023-01-15T10:30:00&until=2023-01-15",
+                mode,
+            )
+        },
+        Ok(expected),
+    );
+
+    // Percent encoded separators are decoded like any other character
+    check_result(
+        |mode| {
+            from_str(
+                "date=2023%2D01-15&time=10%3A30%3a00&at=2023-01-15T10%3A30:00&until=2023-01-15",
+                mode,
+            )
+        },
+        Ok(expected),
+    );
+
+    // Sequences of dates
+    assert_eq!(
+        from_str("value=2023-01-15&value=2023-01-16", ParseMode::Duplicate),
+        Ok(p!(vec![date, date.succ_opt().unwrap()]))
+    );
+    assert_eq!(
+        from_str("value=2023-01-15|2023-01-16", ParseMode::Delimiter(b'|')),
+        Ok(p!(vec![date, date.succ_opt().unwrap()]))
+    );
+    assert_eq!(
+        from_str(
+            "value[1]=2023-01-16&value[0]=2023-01-15",
+            ParseMode::Brackets
+        ),
+        Ok(p!(vec![date, date.succ_opt().unwrap()]))
+    );
+}
@@ -3,7 +3,7 @@
 use std::collections::HashMap;
 
 use _serde::Deserialize;
-use serde_querystring::de::{from_bytes, ParseMode};
+use serde_querystring::de::{from_bytes, ErrorKind, ParseMode};
 
 /// It is a helper struct we use to test primitive types
 /// as we don't support anything beside maps/structs at the root level
@@ -105,13 +105,15 @@ fn deserialize_sequence() {
         Ok(p!((true, "3", 1337)))
     );
 
-    // More values than expected, we will try to recover if possible
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Point {
+        point: (u32, u32),
+    }
+
     assert_eq!(
-        from_bytes(
-            b"value=more|values|than|expected",
-            ParseMode::Delimiter(b'|')
-        ),
-        Ok(p!(("more", "values", "than|expected")))
+        from_bytes(b"point=1,2", ParseMode::Delimiter(b',')),
+        Ok(Point { point: (1, 2) })
     );
 }
 
@@ -133,6 +135,61 @@ fn deserialize_optional_seq() {
             seq: Some(vec![20, 30, 40])
         })
     );
+
+    // A single empty value is an empty sequence
+    assert_eq!(
+        from_bytes(b"seq=", ParseMode::Delimiter(b'|')),
+        Ok(OptionalSeq { seq: Some(vec![]) })
+    );
+    assert_eq!(
+        from_bytes(b"seq", ParseMode::Delimiter(b'|')),
+        Ok(OptionalSeq { seq: Some(vec![]) })
+    );
+}
+
+#[test]
+fn deserialize_smallvec() {
+    use smallvec::SmallVec;
+
+    let res: Primitive<SmallVec<[u32; 4]>> =
+        from_bytes(b"value=1|2|3", ParseMode::Delimiter(b'|')).unwrap();
+    assert_eq!(res.value.as_slice(), &[1, 2, 3]);
+    assert!(!res.value.spilled());
+
+    let res: Primitive<SmallVec<[u32; 2]>> =
+        from_bytes(b"value=1|2|3", ParseMode::Delimiter(b'|')).unwrap();
+    assert_eq!(res.value.as_slice(), &[1, 2, 3]);
+    assert!(res.value.spilled());
+}
+
+#[test]
+fn deserialize_root_sequence() {
+    assert_eq!(
+        from_bytes::<Vec<u32>>(b"1,2,3", ParseMode::Delimiter(b',')),
+        Ok(vec![1, 2, 3])
+    );
+    assert_eq!(
+        from_bytes::<Vec<u32>>(b"", ParseMode::Delimiter(b',')),
+        Ok(vec![])
+    );
+    assert_eq!(
+        from_bytes::<(u32, bool, String)>(b"1,true,a%2Cb", ParseMode::Delimiter(b',')),
+        Ok((1, true, "a,b".to_string()))
+    );
+    assert!(from_bytes::<(u32, u32)>(b"1,2,3", ParseMode::Delimiter(b',')).is_err());
+}
+
+#[test]
+fn deserialize_sets() {
+    use std::collections::{BTreeSet, HashSet};
+
+    let res: Primitive<HashSet<&str>> =
+        from_bytes(b"value=a|b|a", ParseMode::Delimiter(b'|')).unwrap();
+    assert_eq!(res.value, ["a", "b"].into_iter().collect::<HashSet<_>>());
+
+    let res: Primitive<BTreeSet<u32>> =
+        from_bytes(b"value=3|1|3|2", ParseMode::Delimiter(b'|')).unwrap();
+    assert_eq!(res.value.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
 }
 
 /// Check if unit enums work as keys and values
@@ -201,6 +258,16 @@ fn deserialize_invalid_sequence() {
     )
     .is_err());
 
+    // more or less values than the tuple's length
+    let error = from_bytes::<Primitive<(&str, &str, &str)>>(
+        b"value=more|values|than|expected",
+        ParseMode::Delimiter(b'|'),
+    )
+    .unwrap_err();
+    assert_eq!(error.kind, ErrorKind::InvalidLength);
+    assert_eq!(error.message, "expected 3 values, found 4");
+    assert!(from_bytes::<Primitive<(u32, u32)>>(b"value=1", ParseMode::Delimiter(b'|')).is_err());
+
     // tuple value types
     assert!(from_bytes::<Primitive<(&str, usize, &str)>>(
         b"value=foo|bar|baz",
@@ -208,3 +275,32 @@ fn deserialize_invalid_sequence() {
     )
     .is_err());
 }
+
+#[test]
+fn deserialize_contradictory_delimiter() {
+    use serde_querystring::{from_bytes_with_config, Config, ConfigError, DelimiterQS};
+
+    let error =
+        from_bytes::<Primitive<Vec<u32>>>(b"value=1&2", ParseMode::Delimiter(b'&')).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::InvalidConfig);
+    assert_eq!(error.value, "&");
+
+    assert_eq!(
+        Config::new(ParseMode::Delimiter(b'=')).validate(),
+        Err(ConfigError::DelimiterConflict(b'='))
+    );
+    assert!(Config::new(ParseMode::Delimiter(b'\n')).validate().is_ok());
+
+    let config = Config::new(ParseMode::Delimiter(b'\n')).newline_terminators(true);
+    assert_eq!(
+        from_bytes_with_config::<Primitive<Vec<u32>>>(b"value=1\n2", &config)
+            .unwrap_err()
+            .kind,
+        ErrorKind::InvalidConfig
+    );
+
+    // The parsers check the config as well, with their own delimiter
+    let error = DelimiterQS::try_parse(b"value=1\n2", b'\n', &config).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::InvalidConfig);
+    assert!(DelimiterQS::try_parse(b"value=1|2", b'|', &config).is_ok());
+}
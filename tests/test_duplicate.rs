@@ -1,7 +1,7 @@
 //! These tests are meant for the `DuplicateQS` method
 
 use _serde::Deserialize;
-use serde_querystring::de::{from_bytes, ParseMode};
+use serde_querystring::de::{from_bytes, ErrorKind, ParseMode};
 
 /// It is a helper struct we use to test primitive types
 /// as we don't support anything beside maps/structs at the root level
@@ -70,6 +70,68 @@ fn deserialize_optional_seq() {
             seq: Some(vec![20, 30, 40])
         })
     );
+
+    // A single empty value is an empty sequence
+    assert_eq!(
+        from_bytes(b"seq=", ParseMode::Duplicate),
+        Ok(OptionalSeq { seq: Some(vec![]) })
+    );
+    assert_eq!(
+        from_bytes(b"seq", ParseMode::Duplicate),
+        Ok(OptionalSeq { seq: Some(vec![]) })
+    );
+
+    // Even for strings, but not when there are more values
+    assert_eq!(
+        from_bytes(b"value=", ParseMode::Duplicate),
+        Ok(p!(Some(Vec::<String>::new())))
+    );
+    assert_eq!(
+        from_bytes(b"value=&value=", ParseMode::Duplicate),
+        Ok(p!(Some(vec!["", ""])))
+    );
+}
+
+#[test]
+fn deserialize_smallvec() {
+    use smallvec::SmallVec;
+
+    let res: Primitive<SmallVec<[u32; 4]>> =
+        from_bytes(b"value=1&value=2&value=3", ParseMode::Duplicate).unwrap();
+    assert_eq!(res.value.as_slice(), &[1, 2, 3]);
+    assert!(!res.value.spilled());
+
+    let res: Primitive<SmallVec<[u32; 2]>> =
+        from_bytes(b"value=1&value=2&value=3", ParseMode::Duplicate).unwrap();
+    assert_eq!(res.value.as_slice(), &[1, 2, 3]);
+    assert!(res.value.spilled());
+
+    // The skipped empty value is not counted
+    let res: Primitive<SmallVec<[u32; 4]>> = from_bytes(b"value=", ParseMode::Duplicate).unwrap();
+    assert!(res.value.is_empty());
+    assert!(!res.value.spilled());
+}
+
+#[test]
+fn deserialize_sets() {
+    use std::collections::{BTreeSet, HashSet};
+
+    let res: Primitive<HashSet<String>> =
+        from_bytes(b"value=a&value=b&value=a", ParseMode::Duplicate).unwrap();
+    assert_eq!(
+        res.value,
+        ["a", "b"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<HashSet<_>>()
+    );
+
+    let res: Primitive<BTreeSet<u32>> =
+        from_bytes(b"value=3&value=1&value=3&value=2", ParseMode::Duplicate).unwrap();
+    assert_eq!(res.value.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+    let res: Primitive<HashSet<u32>> = from_bytes(b"value=", ParseMode::Duplicate).unwrap();
+    assert!(res.value.is_empty());
 }
 
 #[test]
@@ -143,6 +205,46 @@ fn deserialize_sequence() {
     );
 }
 
+#[test]
+fn deserialize_sequence_of_bytes() {
+    // Each value is decoded into its own vector
+    assert_eq!(
+        from_bytes(b"value=%01%02&value=%03%04&value=ab", ParseMode::Duplicate),
+        Ok(p!(vec![vec![1u8, 2], vec![3, 4], vec![b'a', b'b']]))
+    );
+    assert_eq!(
+        from_bytes(b"value=%01%02&value", ParseMode::Duplicate),
+        Ok(p!(vec![vec![1u8, 2], vec![]]))
+    );
+
+    // Other types don't get the bytes
+    assert!(
+        from_bytes::<Primitive<Vec<Vec<u32>>>>(b"value=1&value=2", ParseMode::Duplicate).is_err()
+    );
+}
+
+#[test]
+fn deserialize_sequence_of_borrowed_bytes() {
+    let input = b"d=ab&d=cd";
+    let res: Primitive<Vec<&[u8]>> =
+        from_bytes(b"value=ab&value=cd", ParseMode::Duplicate).unwrap();
+    assert_eq!(res.value, vec![&b"ab"[..], &b"cd"[..]]);
+
+    // The elements point into the input
+    #[derive(Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Binary<'a> {
+        #[serde(borrow)]
+        d: Vec<&'a [u8]>,
+    }
+    let res: Binary = from_bytes(input, ParseMode::Duplicate).unwrap();
+    assert_eq!(res.d[0].as_ptr(), input[2..].as_ptr());
+    assert_eq!(res.d[1].as_ptr(), input[7..].as_ptr());
+
+    // Encoded elements can't be borrowed
+    assert!(from_bytes::<Binary>(b"d=ab&d=c%20d", ParseMode::Duplicate).is_err());
+}
+
 #[test]
 fn deserialize_decoded_keys() {
     // having different encoded kinds of the string `value` for key
@@ -176,3 +278,252 @@ fn deserialize_invalid_sequence() {
     )
     .is_err());
 }
+
+#[test]
+fn deserialize_nested_struct_error() {
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Inner {
+        field: u32,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Outer {
+        inner: Inner,
+    }
+
+    // Nested structs and maps need the brackets mode
+    let error = from_bytes::<Outer>(b"inner=1", ParseMode::Duplicate).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::InvalidType);
+    assert!(error.message.contains("ParseMode::Brackets"));
+
+    let error = from_bytes::<Primitive<HashMap<String, u32>>>(b"value=1", ParseMode::Duplicate)
+        .unwrap_err();
+    assert!(error.message.contains("ParseMode::Brackets"));
+}
+
+#[test]
+fn deserialize_flatten_map_of_sequences() {
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Page {
+        page: u32,
+        #[serde(flatten)]
+        rest: HashMap<String, Vec<String>>,
+    }
+
+    let mut rest = HashMap::new();
+    rest.insert("tag".to_string(), vec!["a".to_string(), "b".to_string()]);
+    rest.insert("q".to_string(), vec!["x".to_string(), "y".to_string()]);
+
+    assert_eq!(
+        from_bytes(b"page=1&tag=a&tag=b&q=x&q=y", ParseMode::Duplicate),
+        Ok(Page { page: 1, rest })
+    );
+}
+
+#[test]
+fn deserialize_flatten_map_of_single_values() {
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Page {
+        page: u32,
+        #[serde(flatten)]
+        rest: HashMap<String, String>,
+    }
+
+    let mut rest = HashMap::new();
+    rest.insert("a".to_string(), "2".to_string());
+    rest.insert("b".to_string(), "x".to_string());
+
+    assert_eq!(
+        from_bytes(b"page=1&a=2&b=x", ParseMode::Duplicate),
+        Ok(Page { page: 1, rest })
+    );
+}
+
+#[test]
+fn deserialize_untagged_enums() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde", untagged)]
+    enum U {
+        N(u32),
+        S(String),
+        Many(Vec<String>),
+    }
+
+    assert_eq!(
+        from_bytes(b"value=x", ParseMode::Duplicate),
+        Ok(Primitive {
+            value: U::S("x".to_string())
+        })
+    );
+    assert_eq!(
+        from_bytes(b"value=x&value=y", ParseMode::Duplicate),
+        Ok(Primitive {
+            value: U::Many(vec!["x".to_string(), "y".to_string()])
+        })
+    );
+
+    // Values are buffered as strings by serde, so numbers need `Config::detect_types`
+    use serde_querystring::{from_bytes_with_config, Config};
+    let config = Config::new(ParseMode::Duplicate).detect_types(true);
+    assert_eq!(
+        from_bytes_with_config(b"value=1", &config),
+        Ok(Primitive { value: U::N(1) })
+    );
+}
+
+#[test]
+fn deserialize_map_with_integer_keys() {
+    use std::collections::{BTreeMap, HashMap};
+
+    let mut expected = HashMap::new();
+    expected.insert(10u32, "a".to_string());
+    expected.insert(20, "b".to_string());
+    assert_eq!(from_bytes(b"10=a&20=b", ParseMode::Duplicate), Ok(expected));
+
+    // Repeated keys and percent encoded keys
+    let mut expected = BTreeMap::new();
+    expected.insert(-1i32, vec!["a".to_string(), "c".to_string()]);
+    expected.insert(3, vec!["b".to_string()]);
+    assert_eq!(
+        from_bytes(b"-1=a&%33=b&-1=c", ParseMode::Duplicate),
+        Ok(expected)
+    );
+
+    assert_eq!(
+        from_bytes::<HashMap<u32, String>>(b"10=a&x=b", ParseMode::Duplicate)
+            .unwrap_err()
+            .kind,
+        ErrorKind::InvalidNumber
+    );
+}
+
+#[test]
+fn deserialize_root_sequence() {
+    assert_eq!(
+        from_bytes::<Vec<u32>>(b"1&2&&3", ParseMode::Duplicate),
+        Ok(vec![1, 2, 3])
+    );
+    assert_eq!(
+        from_bytes::<Vec<String>>(b"", ParseMode::Duplicate),
+        Ok(vec![])
+    );
+    assert_eq!(
+        from_bytes::<(u32, String)>(b"1&foo%20bar", ParseMode::Duplicate),
+        Ok((1, "foo bar".to_string()))
+    );
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "_serde")]
+    struct Pair(u32, u32);
+
+    assert_eq!(
+        from_bytes::<Pair>(b"1&2", ParseMode::Duplicate),
+        Ok(Pair(1, 2))
+    );
+    assert_eq!(
+        from_bytes::<Pair>(b"1&2&3", ParseMode::Duplicate)
+            .unwrap_err()
+            .kind,
+        ErrorKind::InvalidLength
+    );
+
+    // Only flat sequences are supported at the root
+    assert_eq!(
+        from_bytes::<Vec<u32>>(b"1&2", ParseMode::Brackets)
+            .unwrap_err()
+            .kind,
+        ErrorKind::InvalidType
+    );
+}
+
+#[test]
+fn get_as() {
+    use serde_querystring::DuplicateQS;
+
+    let parser = DuplicateQS::parse(b"page=1&page=20&all=on&empty&name=a%20b&bad=x");
+    assert_eq!(parser.get_as::<u32>(b"page"), Some(Ok(20)));
+    assert_eq!(parser.get_as::<bool>(b"all"), Some(Ok(true)));
+    assert_eq!(parser.get_as::<bool>(b"empty"), Some(Ok(true)));
+    assert_eq!(parser.get_as::<Option<u32>>(b"empty"), Some(Ok(None)));
+    assert_eq!(
+        parser.get_as::<String>(b"name"),
+        Some(Ok("a b".to_string()))
+    );
+    assert_eq!(parser.get_as::<u32>(b"missing"), None);
+    assert_eq!(
+        parser.get_as::<u32>(b"bad").map(|r| r.unwrap_err().kind),
+        Some(ErrorKind::InvalidNumber)
+    );
+}
+
+#[test]
+fn get_as_with_config() {
+    use serde_querystring::{BoolFormat, Config, DuplicateQS};
+
+    // The values are deserialized with the config of the parser, like `value` decodes them
+    let config = Config::default()
+        .bool_format(BoolFormat::YesNo)
+        .null_tokens(&["null"])
+        .plus_as_space_in_values(false);
+    let parser = DuplicateQS::try_parse(b"all=yes&page=null&name=a+b", &config).unwrap();
+    assert_eq!(parser.get_as::<bool>(b"all"), Some(Ok(true)));
+    assert_eq!(parser.get_as::<Option<u32>>(b"page"), Some(Ok(None)));
+    assert_eq!(
+        parser.get_as::<String>(b"name"),
+        Some(Ok("a+b".to_string()))
+    );
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        all: bool,
+        page: Option<u32>,
+    }
+
+    let parser = DuplicateQS::try_parse(b"all=no&page=null", &config).unwrap();
+    assert_eq!(
+        parser.deserialize::<Query>(),
+        Ok(Query {
+            all: false,
+            page: None
+        })
+    );
+}
+
+#[test]
+fn get_all_as() {
+    use serde_querystring::DuplicateQS;
+
+    let parser = DuplicateQS::parse(b"ids=1&ids=2&ids=3&tags=a&tags=b%20c&tags&bad=1&bad=x");
+    assert_eq!(parser.get_all_as::<u32>(b"ids"), Some(Ok(vec![1, 2, 3])));
+    assert_eq!(
+        parser.get_all_as::<String>(b"tags"),
+        Some(Ok(vec!["a".to_string(), "b c".to_string(), "".to_string()]))
+    );
+    assert_eq!(parser.get_all_as::<u32>(b"missing"), None);
+    assert_eq!(
+        parser
+            .get_all_as::<u32>(b"bad")
+            .map(|r| r.unwrap_err().kind),
+        Some(ErrorKind::InvalidNumber)
+    );
+
+    // The values are deserialized with the config of the parser
+    let config =
+        serde_querystring::Config::default().bool_format(serde_querystring::BoolFormat::YesNo);
+    let parser = DuplicateQS::try_parse(b"flags=yes&flags=no", &config).unwrap();
+    assert_eq!(
+        parser.get_all_as::<bool>(b"flags"),
+        Some(Ok(vec![true, false]))
+    );
+}
@@ -0,0 +1,475 @@
+//! These tests are meant for the serializer
+
+use _serde::{Deserialize, Serialize};
+use serde_querystring::{
+    de::{from_str, from_str_with_config, ParseMode},
+    ser::{to_string, to_string_merged, to_string_with_config, to_writer, PercentEncodeSet},
+    Config, DuplicateQS, ErrorKind,
+};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "_serde")]
+struct Sample {
+    name: String,
+    count: u32,
+    tags: Vec<String>,
+    flag: Option<bool>,
+}
+
+fn sample() -> Sample {
+    Sample {
+        name: "foo bar&baz".to_string(),
+        count: 1337,
+        tags: vec!["a=b".to_string(), "c+d".to_string()],
+        flag: None,
+    }
+}
+
+#[test]
+fn serialize_duplicate() {
+    let serialized = to_string(&sample(), ParseMode::Duplicate).unwrap();
+    assert_eq!(
+        serialized,
+        "name=foo+bar%26baz&count=1337&tags=a%3Db&tags=c%2Bd"
+    );
+
+    assert_eq!(from_str(&serialized, ParseMode::Duplicate), Ok(sample()));
+}
+
+#[test]
+fn serialize_brackets() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "_serde")]
+    enum Shape {
+        Circle { r: u32 },
+        Point,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Nested {
+        sample: Sample,
+        shape: Shape,
+        point: Shape,
+    }
+
+    let nested = Nested {
+        sample: sample(),
+        shape: Shape::Circle { r: 3 },
+        point: Shape::Point,
+    };
+
+    let serialized = to_string(&nested, ParseMode::Brackets).unwrap();
+    assert_eq!(
+        serialized,
+        "sample[name]=foo+bar%26baz&sample[count]=1337&sample[tags][0]=a%3Db&sample[tags][1]=c%2Bd\
+         &shape[Circle][r]=3&point=Point"
+    );
+
+    assert_eq!(from_str(&serialized, ParseMode::Brackets), Ok(nested));
+}
+
+#[test]
+fn serialize_unsupported() {
+    // nested structs in flat modes
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct Nested {
+        sample: Sample,
+    }
+    let nested = Nested { sample: sample() };
+    assert_eq!(
+        to_string(&nested, ParseMode::Duplicate).map_err(|e| e.kind),
+        Err(ErrorKind::InvalidType)
+    );
+
+    // sequences in urlencoded mode
+    assert_eq!(
+        to_string(&sample(), ParseMode::UrlEncoded).map_err(|e| e.kind),
+        Err(ErrorKind::InvalidType)
+    );
+
+    // primitives at the root level
+    assert_eq!(
+        to_string(&10, ParseMode::Duplicate).map_err(|e| e.kind),
+        Err(ErrorKind::InvalidType)
+    );
+}
+
+#[test]
+fn serialize_merged() {
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct Overrides {
+        page: u32,
+    }
+
+    let base = DuplicateQS::parse(b"q=rust%20lang&page=1&sort=asc");
+    assert_eq!(
+        to_string_merged(&base, &Overrides { page: 2 }).unwrap(),
+        "q=rust+lang&page=2&sort=asc"
+    );
+
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct NewKeys {
+        tags: Vec<u32>,
+        sort: Option<String>,
+    }
+
+    // Skipped values don't replace anything, repeated keys are kept
+    let base = DuplicateQS::parse(b"tags=1&tags=2&sort=asc&empty");
+    assert_eq!(
+        to_string_merged(
+            &base,
+            &NewKeys {
+                tags: vec![3, 4, 5],
+                sort: None
+            }
+        )
+        .unwrap(),
+        "tags=3&tags=4&tags=5&sort=asc&empty"
+    );
+
+    // New keys are appended in the order they are serialized
+    let base = DuplicateQS::parse(b"q=rust");
+    assert_eq!(
+        to_string_merged(
+            &base,
+            &NewKeys {
+                tags: vec![1],
+                sort: Some("asc".to_string())
+            }
+        )
+        .unwrap(),
+        "q=rust&tags=1&sort=asc"
+    );
+}
+
+#[test]
+fn serialize_encode_set() {
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        #[serde(rename = "a b")]
+        value: &'static str,
+    }
+
+    let query = Query { value: "x y/z-w" };
+    let config = Config::new(ParseMode::Duplicate);
+
+    // FORM is the default
+    assert_eq!(
+        to_string(&query, ParseMode::Duplicate).unwrap(),
+        "a+b=x+y%2Fz-w"
+    );
+    assert_eq!(
+        to_string_with_config(&query, &config.encode_set(PercentEncodeSet::FORM)).unwrap(),
+        "a+b=x+y%2Fz-w"
+    );
+    assert_eq!(
+        to_string_with_config(&query, &config.encode_set(PercentEncodeSet::QUERY)).unwrap(),
+        "a%20b=x%20y/z-w"
+    );
+    assert_eq!(
+        to_string_with_config(&query, &config.encode_set(PercentEncodeSet::STRICT)).unwrap(),
+        "a%20b=x%20y%2Fz%2Dw"
+    );
+
+    // Custom sets
+    let set = PercentEncodeSet::STRICT.keep(b"/").space_as_plus(true);
+    assert_eq!(
+        to_string_with_config(&query, &config.encode_set(set)).unwrap(),
+        "a+b=x+y/z%2Dw"
+    );
+}
+
+/// The example from the `serde_qs` docs
+#[test]
+fn serialize_qs_compatible() {
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct Address {
+        city: &'static str,
+        postcode: &'static str,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct QueryParams {
+        id: u8,
+        name: &'static str,
+        phone: u32,
+        address: Address,
+        user_ids: Vec<u8>,
+    }
+
+    let params = QueryParams {
+        id: 42,
+        name: "Acme",
+        phone: 12345,
+        address: Address {
+            city: "Carrot City",
+            postcode: "12345",
+        },
+        user_ids: vec![1, 2, 3, 4],
+    };
+
+    assert_eq!(
+        to_string_with_config(&params, &Config::qs_compatible()).unwrap(),
+        "id=42&name=Acme&phone=12345&address[city]=Carrot+City&address[postcode]=12345&\
+        user_ids[0]=1&user_ids[1]=2&user_ids[2]=3&user_ids[3]=4"
+    );
+}
+
+#[test]
+fn serialize_node_qs_compatible() {
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        name: &'static str,
+        tags: Vec<&'static str>,
+    }
+
+    let query = Query {
+        name: "a b*c",
+        tags: vec!["x~y"],
+    };
+    assert_eq!(
+        to_string_with_config(&query, &Config::node_qs_compatible()).unwrap(),
+        "name=a%20b%2Ac&tags[0]=x~y"
+    );
+}
+
+#[test]
+fn serialize_delimiter() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Query {
+        ids: Vec<u32>,
+        tags: Vec<String>,
+        empty: Vec<u32>,
+    }
+
+    let query = Query {
+        ids: vec![1, 2, 3],
+        tags: vec!["a,b".to_string(), "c d".to_string()],
+        empty: vec![],
+    };
+
+    let serialized = to_string(&query, ParseMode::Delimiter(b',')).unwrap();
+    assert_eq!(serialized, "ids=1,2,3&tags=a%2Cb,c+d&empty=");
+    assert_eq!(from_str(&serialized, ParseMode::Delimiter(b',')), Ok(query));
+
+    // The delimiter is encoded even if the encode set keeps it
+    let config = Config::new(ParseMode::Delimiter(b',')).encode_set(PercentEncodeSet::QUERY);
+    let tags = Query {
+        ids: vec![],
+        tags: vec!["a,b".to_string(), "c".to_string()],
+        empty: vec![],
+    };
+    assert_eq!(
+        to_string_with_config(&tags, &config).unwrap(),
+        "ids=&tags=a%2Cb,c&empty="
+    );
+
+    // Nested sequences can't be joined
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct Nested {
+        key: Vec<Vec<u32>>,
+    }
+    assert_eq!(
+        to_string(
+            &Nested {
+                key: vec![vec![1, 2]]
+            },
+            ParseMode::Delimiter(b',')
+        )
+        .map_err(|e| e.kind),
+        Err(ErrorKind::InvalidType)
+    );
+}
+
+#[test]
+fn serialize_nested_options() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Inner {
+        a: u32,
+        b: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Outer {
+        some: Option<Inner>,
+        none: Option<Inner>,
+        count: u32,
+    }
+
+    let outer = Outer {
+        some: Some(Inner {
+            a: 1,
+            b: "x y".to_string(),
+        }),
+        none: None,
+        count: 2,
+    };
+
+    let serialized = to_string(&outer, ParseMode::Brackets).unwrap();
+    assert_eq!(serialized, "some[a]=1&some[b]=x+y&count=2");
+    assert_eq!(from_str(&serialized, ParseMode::Brackets), Ok(outer));
+
+    let outer = Outer {
+        some: None,
+        none: Some(Inner {
+            a: 3,
+            b: String::new(),
+        }),
+        count: 4,
+    };
+
+    let config = Config::new(ParseMode::Brackets).write_none(true);
+    assert_eq!(
+        to_string_with_config(&outer, &config).unwrap(),
+        "some=&none[a]=3&none[b]=&count=4"
+    );
+}
+
+#[test]
+fn serialize_to_writer() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Large {
+        skipped: Option<u32>,
+        name: String,
+        samples: Vec<Sample>,
+        ids: Vec<u64>,
+    }
+
+    let large = Large {
+        skipped: None,
+        name: "large & small".to_string(),
+        samples: (0..100).map(|count| Sample { count, ..sample() }).collect(),
+        ids: (0..1000).collect(),
+    };
+
+    let config = Config::new(ParseMode::Brackets);
+    let mut output = Vec::new();
+    to_writer(&mut output, &large, &config).unwrap();
+
+    assert_eq!(
+        output,
+        to_string_with_config(&large, &config).unwrap().as_bytes()
+    );
+    assert_eq!(
+        from_str(std::str::from_utf8(&output).unwrap(), ParseMode::Brackets),
+        Ok(large)
+    );
+}
+
+#[test]
+fn serialize_brackets_in_key_names() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Inner {
+        #[serde(rename = "x]y")]
+        xy: u32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "_serde")]
+    struct Literal {
+        #[serde(rename = "a[b]")]
+        ab: u32,
+        inner: Inner,
+    }
+
+    let literal = Literal {
+        ab: 1,
+        inner: Inner { xy: 2 },
+    };
+
+    // Brackets in names are encoded even if the set keeps them
+    let config = Config::new(ParseMode::Brackets)
+        .encode_set(PercentEncodeSet::QUERY.keep(b"[]"))
+        .literal_encoded_brackets(true);
+    let serialized = to_string_with_config(&literal, &config).unwrap();
+    assert_eq!(serialized, "a%5Bb%5D=1&inner[x%5Dy]=2");
+
+    assert_eq!(from_str_with_config(&serialized, &config), Ok(literal));
+
+    // Without the option, encoded brackets are nesting
+    assert!(from_str::<Literal>(&serialized, ParseMode::Brackets).is_err());
+}
+
+#[test]
+fn serialize_sorted_keys() {
+    use serde_querystring::KeyOrder;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct First {
+        b: u32,
+        a: Vec<u32>,
+        c: BTreeMap<&'static str, u32>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "_serde")]
+    struct Second {
+        c: HashMap<&'static str, u32>,
+        a: Vec<u32>,
+        b: u32,
+    }
+
+    let first = First {
+        b: 1,
+        a: vec![3, 2],
+        c: [("y", 1), ("x", 2)].into_iter().collect(),
+    };
+    let second = Second {
+        c: [("x", 2), ("y", 1)].into_iter().collect(),
+        a: vec![3, 2],
+        b: 1,
+    };
+
+    let config = Config::new(ParseMode::Brackets).key_order(KeyOrder::Sorted);
+    let expected = "a[0]=3&a[1]=2&b=1&c[x]=2&c[y]=1";
+    assert_eq!(to_string_with_config(&first, &config).unwrap(), expected);
+    assert_eq!(to_string_with_config(&second, &config).unwrap(), expected);
+
+    let mut output = Vec::new();
+    to_writer(&mut output, &second, &config).unwrap();
+    assert_eq!(output, expected.as_bytes());
+
+    // The values of a key keep their order
+    let mut map = HashMap::new();
+    map.insert("z", vec![2, 1]);
+    map.insert("a", vec![]);
+    map.insert("m", vec![3]);
+    let config = Config::new(ParseMode::Duplicate).key_order(KeyOrder::Sorted);
+    assert_eq!(to_string_with_config(&map, &config).unwrap(), "m=3&z=2&z=1");
+
+    // Preserved by default
+    assert_eq!(
+        to_string(&first, ParseMode::Brackets).unwrap(),
+        "b=1&a[0]=3&a[1]=2&c[x]=2&c[y]=1"
+    );
+}
+
+#[test]
+fn serialize_contradictory_config() {
+    let config = Config::new(ParseMode::Delimiter(b'&'));
+
+    assert_eq!(
+        to_string_with_config(&sample(), &config).unwrap_err().kind,
+        ErrorKind::InvalidConfig
+    );
+    assert_eq!(
+        to_writer(Vec::new(), &sample(), &config).unwrap_err().kind,
+        ErrorKind::InvalidConfig
+    );
+}
@@ -92,6 +92,7 @@ fn deserialize_error_type() {
     // We don't support non-unit enums
     #[derive(Debug, Deserialize)]
     #[serde(crate = "_serde")]
+    #[allow(dead_code)]
     enum ValueEnum {
         A(i32, i32),
         B(i32),
@@ -117,3 +118,22 @@ fn deserialize_error_type() {
         ErrorKind::InvalidType
     );
 }
+
+#[test]
+fn deserialize_last_value_wins() {
+    assert_eq!(
+        from_bytes(b"foo=bar&foobar=1337&foo=baz&bar=13", ParseMode::UrlEncoded),
+        Ok(UrlEncoded {
+            foo: "baz",
+            foobar: 1337,
+            bar: Some(13)
+        })
+    );
+
+    assert_eq!(
+        from_bytes::<Primitive<Vec<u32>>>(b"value=1&value=2", ParseMode::UrlEncoded)
+            .unwrap_err()
+            .kind,
+        ErrorKind::InvalidType
+    );
+}